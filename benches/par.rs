@@ -0,0 +1,71 @@
+use std::hint::black_box;
+
+use criterion::BenchmarkId;
+use criterion::Throughput;
+use criterion::{criterion_group, criterion_main, Criterion};
+
+const SIZE: usize = 16 * 1024 * 1024;
+const CHUNK_SIZE: usize = 64 * 1024;
+
+fn threads() -> Vec<usize> {
+    let max = std::thread::available_parallelism().map_or(1, usize::from);
+    let mut v = vec![1];
+    while v[v.len() - 1] * 2 <= max {
+        v.push(v[v.len() - 1] * 2);
+    }
+    v
+}
+
+fn par_keystream(c: &mut Criterion) {
+    let key = [0x3d; 32];
+    let nonce = [0x84; 15];
+
+    let mut group = c.benchmark_group("par_keystream");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(SIZE as u64));
+
+    for n in threads() {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(n)
+            .build()
+            .unwrap();
+        let mut buffer = vec![0u8; SIZE];
+        group.bench_function(BenchmarkId::from_parameter(n), |b| {
+            b.iter(|| {
+                pool.install(|| {
+                    zuc::par::par_apply_keystream(&key, &nonce, CHUNK_SIZE, &mut buffer)
+                });
+                black_box(&buffer);
+            });
+        });
+    }
+    group.finish();
+}
+
+fn par_mac(c: &mut Criterion) {
+    let ik = [0xff; 32];
+    let nonce = [0xff; 15];
+    let input: Vec<u8> = (0..SIZE).map(|_| rand::random::<u8>()).collect();
+
+    let mut group = c.benchmark_group("par_mac_128");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(SIZE as u64));
+
+    for n in threads() {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(n)
+            .build()
+            .unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(n), &input, |b, input| {
+            b.iter(|| {
+                black_box(pool.install(|| {
+                    zuc::par::par_generate_macs::<u128>(&ik, &nonce, CHUNK_SIZE, input)
+                }))
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, par_keystream, par_mac);
+criterion_main!(benches);
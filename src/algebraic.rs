@@ -0,0 +1,879 @@
+//! Algebraic models of reduced-round ZUC for SAT-solver cryptanalysis
+//!
+//! [`Model`] describes the key and IV loading, the initialization rounds and the
+//! keystream generation of ZUC-128 or ZUC-256 as a circuit of XOR, AND and S box gates
+//! over the key and IV bits. It exports as DIMACS CNF or as a system of ANF equations,
+//! with optional fixed key, IV or keystream bits.
+//!
+//! Bits of keys and IVs are numbered from the most significant bit of the first byte,
+//! bits of keystream words from the most significant bit of the first word.
+//! Variable `n` of both exports is node `n - 1` of the circuit.
+
+use crate::analysis::anf;
+use crate::research::ZucBuilder;
+use crate::zuc::{Zuc, S0, S1};
+use crate::{zuc128, zuc256};
+
+use std::fmt::Write as _;
+
+/// Literal: a node output, possibly negated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Lit {
+    /// node index
+    var: usize,
+    /// whether the literal is negated
+    neg: bool,
+}
+
+/// Bit of the circuit: a constant or a literal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bit {
+    /// constant
+    Const(bool),
+    /// literal
+    Lit(Lit),
+}
+
+impl Bit {
+    /// Negation
+    fn not(self) -> Self {
+        match self {
+            Bit::Const(b) => Bit::Const(!b),
+            Bit::Lit(l) => Bit::Lit(Lit {
+                var: l.var,
+                neg: !l.neg,
+            }),
+        }
+    }
+}
+
+/// Word of 32 bits, least significant bit first
+type Word = [Bit; 32];
+
+/// Circuit node
+#[derive(Debug, Clone)]
+enum Node {
+    /// key or IV bit
+    Input,
+    /// XOR of two node outputs
+    Xor(usize, usize),
+    /// AND of two literals
+    And(Lit, Lit),
+    /// output bit `bit` of S box `S0` (`sbox == 0`) or `S1` (`sbox == 1`)
+    Sbox {
+        /// S box index
+        sbox: usize,
+        /// output bit, least significant first
+        bit: usize,
+        /// input bits, least significant first
+        inputs: [Bit; 8],
+    },
+}
+
+/// Algebraic model of reduced-round ZUC
+#[derive(Debug, Clone)]
+pub struct Model {
+    /// whether the model uses the ZUC-256 loading
+    zuc256: bool,
+    /// number of initialization rounds
+    init_rounds: u32,
+    /// circuit nodes in evaluation order
+    nodes: Vec<Node>,
+    /// key bits
+    key: Vec<usize>,
+    /// IV bits
+    iv: Vec<usize>,
+    /// keystream bits
+    keystream: Vec<Bit>,
+    /// fixed bits
+    fixed: Vec<(Bit, bool)>,
+}
+
+impl Model {
+    /// Models ZUC-128 with `init_rounds` initialization rounds and `words` keystream words
+    #[must_use]
+    pub fn zuc128(init_rounds: u32, words: usize) -> Self {
+        Self::build(false, init_rounds, words, 16, 16, &|k, iv| {
+            let mut key = [0; 16];
+            let mut v = [0; 16];
+            key.copy_from_slice(k);
+            v.copy_from_slice(iv);
+            zuc128::load(&key, &v, &zuc128::D)
+        })
+    }
+
+    /// Models ZUC-256 with `init_rounds` initialization rounds and `words` keystream words
+    #[must_use]
+    pub fn zuc256(init_rounds: u32, words: usize) -> Self {
+        Self::build(true, init_rounds, words, 32, 23, &|k, iv| {
+            let mut key = [0; 32];
+            let mut v = [0; 23];
+            key.copy_from_slice(k);
+            v.copy_from_slice(iv);
+            zuc256::load(&key, &v, &zuc256::D)
+        })
+    }
+
+    /// Builds the circuit
+    fn build(
+        zuc256: bool,
+        init_rounds: u32,
+        words: usize,
+        key_len: usize,
+        iv_len: usize,
+        load: &dyn Fn(&[u8], &[u8]) -> Zuc,
+    ) -> Self {
+        let mut model = Self {
+            zuc256,
+            init_rounds,
+            nodes: Vec::new(),
+            key: Vec::new(),
+            iv: Vec::new(),
+            keystream: Vec::new(),
+            fixed: Vec::new(),
+        };
+        model.key = (0..key_len * 8).map(|_| model.push(Node::Input)).collect();
+        model.iv = (0..iv_len * 8).map(|_| model.push(Node::Input)).collect();
+
+        let mut state = model.load(key_len, iv_len, load);
+        for _ in 0..init_rounds {
+            let x = bit_reconstruction(&state.s);
+            let w = model.f(&mut state, &x);
+            let mut u = [Bit::Const(false); 32];
+            u[..31].copy_from_slice(&w[1..]);
+            model.lfsr(&mut state.s, &u);
+        }
+        let x = bit_reconstruction(&state.s);
+        model.f(&mut state, &x);
+
+        for _ in 0..words {
+            model.lfsr(&mut state.s, &[Bit::Const(false); 32]);
+            let x = bit_reconstruction(&state.s);
+            let w = model.f(&mut state, &x);
+            let z = model.xor_word(&w, &x[3]);
+            model.keystream.extend(z.iter().rev());
+        }
+        model
+    }
+
+    /// Returns the number of variables
+    #[must_use]
+    pub fn num_vars(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Fixes key bit `i`
+    ///
+    /// # Panics
+    /// + Panics if `i` is out of range.
+    pub fn fix_key_bit(&mut self, i: usize, value: bool) {
+        let bit = input_bit(self.key[i]);
+        self.fixed.push((bit, value));
+    }
+
+    /// Fixes IV bit `i`
+    ///
+    /// # Panics
+    /// + Panics if `i` is out of range.
+    pub fn fix_iv_bit(&mut self, i: usize, value: bool) {
+        let bit = input_bit(self.iv[i]);
+        self.fixed.push((bit, value));
+    }
+
+    /// Fixes keystream bit `i`
+    ///
+    /// # Panics
+    /// + Panics if `i` is out of range.
+    pub fn fix_keystream_bit(&mut self, i: usize, value: bool) {
+        let bit = self.keystream[i];
+        self.fixed.push((bit, value));
+    }
+
+    /// Fixes the first bits of the key to `key`
+    ///
+    /// # Panics
+    /// + Panics if `key` is longer than the key.
+    pub fn fix_key(&mut self, key: &[u8]) {
+        for (i, value) in bytes_to_bits(key).enumerate() {
+            self.fix_key_bit(i, value);
+        }
+    }
+
+    /// Fixes the first bits of the IV to `iv`
+    ///
+    /// # Panics
+    /// + Panics if `iv` is longer than the IV.
+    pub fn fix_iv(&mut self, iv: &[u8]) {
+        for (i, value) in bytes_to_bits(iv).enumerate() {
+            self.fix_iv_bit(i, value);
+        }
+    }
+
+    /// Fixes the first keystream words to `words`
+    ///
+    /// # Panics
+    /// + Panics if more words are given than modelled.
+    pub fn fix_keystream(&mut self, words: &[u32]) {
+        let bits = words
+            .iter()
+            .flat_map(|w| (0..32).rev().map(move |i| w >> i & 1 == 1));
+        for (i, value) in bits.enumerate() {
+            self.fix_keystream_bit(i, value);
+        }
+    }
+
+    /// Evaluates the circuit and returns the modelled keystream words
+    ///
+    /// Fixed bits are ignored.
+    ///
+    /// # Panics
+    /// + Panics if `key` or `iv` has the wrong length.
+    #[must_use]
+    pub fn evaluate(&self, key: &[u8], iv: &[u8]) -> Vec<u32> {
+        let values = self.assignment(key, iv);
+        self.keystream
+            .chunks(32)
+            .map(|bits| {
+                bits.iter()
+                    .fold(0, |w, &b| (w << 1) | u32::from(value(&values, b)))
+            })
+            .collect()
+    }
+
+    /// Checks the model against the keystream generator and its CNF against the evaluation
+    ///
+    /// A fixed key and IV are loaded into the circuit. Returns whether the modelled
+    /// keystream equals the output of a generator with the same number of
+    /// initialization rounds, and the evaluated variables satisfy the CNF clauses
+    /// of the circuit.
+    #[must_use]
+    pub fn self_check(&self) -> bool {
+        let key: Vec<u8> = (0..=255u8)
+            .take(self.key.len() / 8)
+            .map(|i| i.wrapping_mul(37))
+            .collect();
+        let iv: Vec<u8> = (0..=255u8)
+            .take(self.iv.len() / 8)
+            .map(|i| i.wrapping_mul(91) ^ 5)
+            .collect();
+        let builder = if self.zuc256 {
+            let (mut k, mut v) = ([0; 32], [0; 23]);
+            k.copy_from_slice(&key);
+            v.copy_from_slice(&iv);
+            ZucBuilder::zuc256(&k, &v)
+        } else {
+            let (mut k, mut v) = ([0; 16], [0; 16]);
+            k.copy_from_slice(&key);
+            v.copy_from_slice(&iv);
+            ZucBuilder::zuc128(&k, &v)
+        };
+        let mut core = builder.init_rounds(self.init_rounds).build();
+        let expected: Vec<u32> = (0..self.keystream.len() / 32)
+            .map(|_| core.generate())
+            .collect();
+
+        let values = self.assignment(&key, &iv);
+        let satisfied = self.circuit_clauses().iter().all(|clause| {
+            clause.iter().any(|&l| {
+                let var = usize::try_from(l.unsigned_abs() - 1).unwrap_or(usize::MAX);
+                values.get(var).is_some_and(|&v| v == (l > 0))
+            })
+        });
+        satisfied && self.evaluate(&key, &iv) == expected
+    }
+
+    /// Renders the model as DIMACS CNF
+    ///
+    /// Comment lines list the variables of the key, IV and keystream bits;
+    /// keystream bits may be negated literals or the constants `T`/`F`.
+    #[must_use]
+    pub fn to_dimacs(&self) -> String {
+        let mut clauses = self.circuit_clauses();
+        for &(bit, value) in &self.fixed {
+            if let Some(clause) = clause(&[(bit, value)]) {
+                clauses.push(clause);
+            }
+        }
+
+        let mut out = String::new();
+        let vars = |bits: &mut dyn Iterator<Item = Bit>| -> String {
+            let names: Vec<_> = bits.map(bit_name).collect();
+            names.join(" ")
+        };
+        let _ = writeln!(
+            out,
+            "c key {}",
+            vars(&mut self.key.iter().map(|&v| input_bit(v)))
+        );
+        let _ = writeln!(
+            out,
+            "c iv {}",
+            vars(&mut self.iv.iter().map(|&v| input_bit(v)))
+        );
+        let _ = writeln!(
+            out,
+            "c keystream {}",
+            vars(&mut self.keystream.iter().copied())
+        );
+        let _ = writeln!(out, "p cnf {} {}", self.nodes.len(), clauses.len());
+        for clause in clauses {
+            for l in clause {
+                let _ = write!(out, "{l} ");
+            }
+            out.push_str("0\n");
+        }
+        out
+    }
+
+    /// Renders the model as ANF equations over GF(2)
+    ///
+    /// Each line is a polynomial in the variables `x1`, `x2`, ... equal to zero.
+    /// Lines starting with `#` list the key, IV and keystream bits.
+    #[must_use]
+    pub fn to_anf(&self) -> String {
+        let mut out = String::new();
+        let vars = |bits: &mut dyn Iterator<Item = Bit>| -> String {
+            let names: Vec<_> = bits.map(bit_name).collect();
+            names.join(" ")
+        };
+        let _ = writeln!(
+            out,
+            "# key {}",
+            vars(&mut self.key.iter().map(|&v| input_bit(v)))
+        );
+        let _ = writeln!(
+            out,
+            "# iv {}",
+            vars(&mut self.iv.iter().map(|&v| input_bit(v)))
+        );
+        let _ = writeln!(
+            out,
+            "# keystream {}",
+            vars(&mut self.keystream.iter().copied())
+        );
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            let terms = match node {
+                Node::Input => continue,
+                Node::Xor(a, b) => vec![format!("x{}", a + 1), format!("x{}", b + 1)],
+                Node::And(a, b) => {
+                    // (a + na)(b + nb) = ab + nb a + na b + na nb
+                    let (x, y) = (format!("x{}", a.var + 1), format!("x{}", b.var + 1));
+                    let mut terms = vec![format!("{x}*{y}")];
+                    if b.neg {
+                        terms.push(x);
+                    }
+                    if a.neg {
+                        terms.push(y);
+                    }
+                    if a.neg && b.neg {
+                        terms.push("1".into());
+                    }
+                    terms
+                }
+                Node::Sbox { sbox, bit, inputs } => sbox_terms([&S0, &S1][*sbox], *bit, inputs),
+            };
+            let _ = write!(out, "x{}", i + 1);
+            write_sum(&mut out, &terms);
+        }
+        for &(bit, value) in &self.fixed {
+            match bit {
+                Bit::Const(b) => {
+                    if b != value {
+                        out.push_str("1\n");
+                    }
+                }
+                Bit::Lit(l) => {
+                    let _ = write!(out, "x{}", l.var + 1);
+                    let terms = if l.neg == value {
+                        vec![]
+                    } else {
+                        vec!["1".into()]
+                    };
+                    write_sum(&mut out, &terms);
+                }
+            }
+        }
+        out
+    }
+
+    /// Adds a node
+    fn push(&mut self, node: Node) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    /// Evaluates every node
+    ///
+    /// # Panics
+    /// + Panics if `key` or `iv` has the wrong length.
+    fn assignment(&self, key: &[u8], iv: &[u8]) -> Vec<bool> {
+        assert_eq!(key.len() * 8, self.key.len(), "key length");
+        assert_eq!(iv.len() * 8, self.iv.len(), "iv length");
+        let mut values = vec![false; self.nodes.len()];
+        for (&var, v) in self.key.iter().zip(bytes_to_bits(key)) {
+            values[var] = v;
+        }
+        for (&var, v) in self.iv.iter().zip(bytes_to_bits(iv)) {
+            values[var] = v;
+        }
+        let sboxes = [&S0, &S1];
+        for i in 0..self.nodes.len() {
+            values[i] = match &self.nodes[i] {
+                Node::Input => values[i],
+                Node::Xor(a, b) => values[*a] ^ values[*b],
+                Node::And(a, b) => value(&values, Bit::Lit(*a)) && value(&values, Bit::Lit(*b)),
+                Node::Sbox { sbox, bit, inputs } => {
+                    let x = inputs
+                        .iter()
+                        .rev()
+                        .fold(0, |x, &b| (x << 1) | usize::from(value(&values, b)));
+                    sboxes[*sbox][x] >> bit & 1 == 1
+                }
+            };
+        }
+        values
+    }
+
+    /// Returns the CNF clauses of the circuit gates
+    fn circuit_clauses(&self) -> Vec<Vec<i64>> {
+        let mut clauses = Vec::new();
+        let sboxes = [&S0, &S1];
+        for (i, node) in self.nodes.iter().enumerate() {
+            let c = Bit::Lit(Lit { var: i, neg: false });
+            let gate: Vec<Vec<(Bit, bool)>> = match node {
+                Node::Input => continue,
+                Node::Xor(a, b) => {
+                    let (a, b) = (input_bit(*a), input_bit(*b));
+                    vec![
+                        vec![(c, false), (a, true), (b, true)],
+                        vec![(c, false), (a, false), (b, false)],
+                        vec![(c, true), (a, false), (b, true)],
+                        vec![(c, true), (a, true), (b, false)],
+                    ]
+                }
+                Node::And(a, b) => {
+                    let (a, b) = (Bit::Lit(*a), Bit::Lit(*b));
+                    vec![
+                        vec![(c, false), (a, true)],
+                        vec![(c, false), (b, true)],
+                        vec![(c, true), (a, false), (b, false)],
+                    ]
+                }
+                Node::Sbox { sbox, bit, inputs } => (0..256)
+                    .map(|x: usize| {
+                        // inputs == x implies c == S(x)[bit]
+                        let mut clause: Vec<_> = inputs
+                            .iter()
+                            .enumerate()
+                            .map(|(j, &b)| (b, x >> j & 1 == 0))
+                            .collect();
+                        clause.push((c, sboxes[*sbox][x] >> bit & 1 == 1));
+                        clause
+                    })
+                    .collect(),
+            };
+            clauses.extend(gate.iter().filter_map(|lits| clause(lits)));
+        }
+        clauses
+    }
+
+    /// XOR of two bits
+    fn xor(&mut self, a: Bit, b: Bit) -> Bit {
+        match (a, b) {
+            (Bit::Const(x), y) | (y, Bit::Const(x)) => {
+                if x {
+                    y.not()
+                } else {
+                    y
+                }
+            }
+            (Bit::Lit(x), Bit::Lit(y)) => {
+                let var = self.push(Node::Xor(x.var, y.var));
+                Bit::Lit(Lit {
+                    var,
+                    neg: x.neg ^ y.neg,
+                })
+            }
+        }
+    }
+
+    /// AND of two bits
+    fn and(&mut self, a: Bit, b: Bit) -> Bit {
+        match (a, b) {
+            (Bit::Const(x), y) | (y, Bit::Const(x)) => {
+                if x {
+                    y
+                } else {
+                    Bit::Const(false)
+                }
+            }
+            (Bit::Lit(x), Bit::Lit(y)) => {
+                let var = self.push(Node::And(x, y));
+                Bit::Lit(Lit { var, neg: false })
+            }
+        }
+    }
+
+    /// Bitwise XOR of two words
+    fn xor_word(&mut self, a: &Word, b: &Word) -> Word {
+        std::array::from_fn(|i| self.xor(a[i], b[i]))
+    }
+
+    /// Ripple-carry addition of `a` and `b` with carry-in `c`,
+    /// returns the sum bits and the carry-out
+    fn add_bits(&mut self, a: &[Bit], b: &[Bit], mut c: Bit) -> (Vec<Bit>, Bit) {
+        let mut sum = Vec::with_capacity(a.len());
+        for (&x, &y) in a.iter().zip(b) {
+            let t = self.xor(x, y);
+            sum.push(self.xor(t, c));
+            // majority, the two products are never both set
+            let xy = self.and(x, y);
+            let tc = self.and(t, c);
+            c = self.xor(xy, tc);
+        }
+        (sum, c)
+    }
+
+    /// Addition modulo 2^32
+    fn add_word(&mut self, a: &Word, b: &Word) -> Word {
+        let (sum, _) = self.add_bits(a, b, Bit::Const(false));
+        let mut out = [Bit::Const(false); 32];
+        out.copy_from_slice(&sum);
+        out
+    }
+
+    /// Addition modulo 2^31 - 1 of 31-bit words, not both zero
+    fn add_mod_p(&mut self, a: &Word, b: &Word) -> Word {
+        let (sum, carry) = self.add_bits(&a[..31], &b[..31], Bit::Const(false));
+        // the end-around carry never overflows
+        let zero = [Bit::Const(false); 31];
+        let (sum, _) = self.add_bits(&sum, &zero, carry);
+        let mut out = [Bit::Const(false); 32];
+        out[..31].copy_from_slice(&sum);
+        out
+    }
+
+    /// `LFSRWithInitialisationMode` with the 31-bit word `u`, zero in work mode
+    fn lfsr(&mut self, s: &mut [Word; 16], u: &Word) {
+        let mut v = s[0];
+        for (cell, k) in [(0, 8), (4, 20), (10, 21), (13, 17), (15, 15)] {
+            let term = rol31(&s[cell], k);
+            v = self.add_mod_p(&v, &term);
+        }
+        let v = self.add_mod_p(&v, u);
+        s.rotate_left(1);
+        s[15] = v;
+    }
+
+    /// F non-linear function
+    fn f(&mut self, state: &mut State, x: &[Word; 4]) -> Word {
+        let t = self.xor_word(&x[0], &state.r1);
+        let w = self.add_word(&t, &state.r2);
+        let w1 = self.add_word(&state.r1, &x[1]);
+        let w2 = self.xor_word(&state.r2, &x[2]);
+        let u1 = concat_halves(&w1, &w2);
+        let u2 = concat_halves(&w2, &w1);
+        let u1 = self.linear(&u1, &[0, 2, 10, 18, 24]);
+        let u2 = self.linear(&u2, &[0, 8, 14, 22, 30]);
+        state.r1 = self.sbox_word(&u1);
+        state.r2 = self.sbox_word(&u2);
+        w
+    }
+
+    /// XOR of the left rotations of `x`
+    fn linear(&mut self, x: &Word, rotations: &[u32]) -> Word {
+        let mut out = rol(x, rotations[0]);
+        for &n in &rotations[1..] {
+            out = self.xor_word(&out, &rol(x, n));
+        }
+        out
+    }
+
+    /// S box layer `S0, S1, S0, S1` from the most significant byte
+    fn sbox_word(&mut self, x: &Word) -> Word {
+        let mut out = [Bit::Const(false); 32];
+        for byte in 0..4 {
+            // from the least significant byte: S1, S0, S1, S0
+            let sbox = usize::from(byte % 2 == 0);
+            let lo = 8 * byte;
+            let mut inputs = [Bit::Const(false); 8];
+            inputs.copy_from_slice(&x[lo..lo + 8]);
+            let table = [&S0, &S1][sbox];
+            for bit in 0..8 {
+                out[lo + bit] = if let Some(x) = const_byte(&inputs) {
+                    Bit::Const(table[x] >> bit & 1 == 1)
+                } else {
+                    let var = self.push(Node::Sbox { sbox, bit, inputs });
+                    Bit::Lit(Lit { var, neg: false })
+                };
+            }
+        }
+        out
+    }
+
+    /// Loads the key and IV into LFSR words by probing the loader
+    fn load(&mut self, key_len: usize, iv_len: usize, load: &dyn Fn(&[u8], &[u8]) -> Zuc) -> State {
+        let (zero_key, zero_iv) = (vec![0; key_len], vec![0; iv_len]);
+        let base = load(&zero_key, &zero_iv);
+        let mut s = [[Bit::Const(false); 32]; 16];
+        for (cell, word) in base.s.iter().zip(s.iter_mut()) {
+            for (i, bit) in word.iter_mut().enumerate() {
+                *bit = Bit::Const(cell >> i & 1 == 1);
+            }
+        }
+
+        let mut place = |var: usize, probe: &Zuc| {
+            for (i, (cell, base)) in probe.s.iter().zip(base.s.iter()).enumerate() {
+                let diff = cell ^ base;
+                if diff != 0 {
+                    s[i][diff.trailing_zeros() as usize] = input_bit(var);
+                }
+            }
+        };
+        for (i, &var) in self.key.iter().enumerate() {
+            let mut key = zero_key.clone();
+            key[i / 8] = 0x80 >> (i % 8);
+            place(var, &load(&key, &zero_iv));
+        }
+        for (i, &var) in self.iv.iter().enumerate() {
+            let mut iv = zero_iv.clone();
+            iv[i / 8] = 0x80 >> (i % 8);
+            place(var, &load(&zero_key, &iv));
+        }
+
+        State {
+            s,
+            r1: [Bit::Const(false); 32],
+            r2: [Bit::Const(false); 32],
+        }
+    }
+}
+
+/// Symbolic generator state
+struct State {
+    /// LFSR cells
+    s: [Word; 16],
+    /// R1
+    r1: Word,
+    /// R2
+    r2: Word,
+}
+
+/// Bit of an input node
+fn input_bit(var: usize) -> Bit {
+    Bit::Lit(Lit { var, neg: false })
+}
+
+/// Value of `bit` under `values`
+fn value(values: &[bool], bit: Bit) -> bool {
+    match bit {
+        Bit::Const(b) => b,
+        Bit::Lit(l) => values[l.var] ^ l.neg,
+    }
+}
+
+/// Bits of `bytes`, most significant first
+fn bytes_to_bits(bytes: &[u8]) -> impl Iterator<Item = bool> + '_ {
+    bytes
+        .iter()
+        .flat_map(|b| (0..8).rev().map(move |i| b >> i & 1 == 1))
+}
+
+/// DIMACS name of a bit
+fn bit_name(bit: Bit) -> String {
+    match bit {
+        Bit::Const(true) => "T".into(),
+        Bit::Const(false) => "F".into(),
+        Bit::Lit(l) if l.neg => format!("-{}", l.var + 1),
+        Bit::Lit(l) => format!("{}", l.var + 1),
+    }
+}
+
+/// DIMACS clause satisfied when any of the bits has its value,
+/// `None` if a constant already satisfies it
+fn clause(lits: &[(Bit, bool)]) -> Option<Vec<i64>> {
+    let mut clause = Vec::with_capacity(lits.len());
+    for &(bit, value) in lits {
+        match bit {
+            Bit::Const(b) if b == value => return None,
+            Bit::Const(_) => {}
+            Bit::Lit(l) => {
+                let var = i64::try_from(l.var + 1).unwrap_or(i64::MAX);
+                clause.push(if l.neg == value { -var } else { var });
+            }
+        }
+    }
+    Some(clause)
+}
+
+/// Terms of the ANF of an S box output bit, with the input literals and constants substituted
+fn sbox_terms(sbox: &[u8; 256], bit: usize, inputs: &[Bit; 8]) -> Vec<String> {
+    // a negated input flips the corresponding bit of the S box input
+    let neg = inputs.iter().enumerate().fold(0, |m, (j, b)| match b {
+        Bit::Lit(l) if l.neg => m | (1 << j),
+        _ => m,
+    });
+    let table: [u8; 256] = std::array::from_fn(|x| sbox[x ^ neg]);
+
+    let mut coefficients = [false; 256];
+    'monomial: for m in anf(&table)[bit].monomials() {
+        let mut reduced = 0;
+        for (j, b) in inputs.iter().enumerate() {
+            if m >> j & 1 == 0 {
+                continue;
+            }
+            match b {
+                Bit::Const(false) => continue 'monomial,
+                Bit::Const(true) => {}
+                Bit::Lit(_) => reduced |= 1 << j,
+            }
+        }
+        coefficients[reduced] ^= true;
+    }
+
+    let mut terms = Vec::new();
+    for (m, _) in coefficients.iter().enumerate().filter(|(_, &c)| c) {
+        if m == 0 {
+            terms.push("1".into());
+            continue;
+        }
+        let factors: Vec<_> = (0..8)
+            .filter(|j| m >> j & 1 == 1)
+            .filter_map(|j| match inputs[j] {
+                Bit::Lit(l) => Some(format!("x{}", l.var + 1)),
+                Bit::Const(_) => None,
+            })
+            .collect();
+        terms.push(factors.join("*"));
+    }
+    terms
+}
+
+/// Writes ` + term` for each term and ends the line
+fn write_sum(out: &mut String, terms: &[String]) {
+    for t in terms {
+        let _ = write!(out, " + {t}");
+    }
+    out.push('\n');
+}
+
+/// Value of a byte of constant bits
+fn const_byte(bits: &[Bit; 8]) -> Option<usize> {
+    bits.iter().rev().try_fold(0, |x, &b| match b {
+        Bit::Const(v) => Some((x << 1) | usize::from(v)),
+        Bit::Lit(_) => None,
+    })
+}
+
+/// `BitReconstruction` function
+fn bit_reconstruction(s: &[Word; 16]) -> [Word; 4] {
+    let halves = |hi: &Word, lo: &Word| -> Word {
+        // high half: bits 0..16 of `hi`; low half: bits 15..31 of `lo`
+        std::array::from_fn(|i| if i < 16 { lo[i + 15] } else { hi[i - 16] })
+    };
+    let x0: Word = std::array::from_fn(|i| if i < 16 { s[14][i] } else { s[15][i - 1] });
+    [
+        x0,
+        halves(&s[11], &s[9]),
+        halves(&s[7], &s[5]),
+        halves(&s[2], &s[0]),
+    ]
+}
+
+/// Low half of `hi` followed by the high half of `lo`
+fn concat_halves(hi: &Word, lo: &Word) -> Word {
+    std::array::from_fn(|i| if i < 16 { lo[i + 16] } else { hi[i - 16] })
+}
+
+/// Left rotation of a 32-bit word
+fn rol(x: &Word, n: u32) -> Word {
+    let n = n as usize;
+    std::array::from_fn(|i| x[(i + 32 - n) % 32])
+}
+
+/// Left rotation of a 31-bit word, multiplication by 2^n modulo 2^31 - 1
+fn rol31(x: &Word, n: u32) -> Word {
+    let n = n as usize;
+    std::array::from_fn(|i| {
+        if i < 31 {
+            x[(i + 31 - n) % 31]
+        } else {
+            Bit::Const(false)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Zuc128Core, Zuc256Core};
+
+    #[test]
+    fn full_rounds() {
+        let model = Model::zuc128(32, 3);
+        let (key, iv) = ([0x3d; 16], [0x84; 16]);
+        let mut core = Zuc128Core::new(&key, &iv);
+        let expected: Vec<_> = (0..3).map(|_| core.generate()).collect();
+        assert_eq!(model.evaluate(&key, &iv), expected);
+
+        let model = Model::zuc256(32, 2);
+        let (key, iv) = ([0x5a; 32], [0x1f; 23]);
+        let mut core = Zuc256Core::new(&key, &iv);
+        let expected: Vec<_> = (0..2).map(|_| core.generate()).collect();
+        assert_eq!(model.evaluate(&key, &iv), expected);
+    }
+
+    #[test]
+    fn self_check() {
+        for rounds in [0, 1, 3] {
+            assert!(Model::zuc128(rounds, 2).self_check());
+            assert!(Model::zuc256(rounds, 1).self_check());
+        }
+    }
+
+    #[test]
+    fn exports() {
+        let (key, iv) = ([0xff; 16], [0; 16]);
+        let mut model = Model::zuc128(1, 1);
+        let z = model.evaluate(&key, &iv)[0];
+        model.fix_key(&key[..15]);
+        model.fix_iv(&iv);
+        model.fix_keystream_bit(0, z >> 31 == 1);
+        let values = model.assignment(&key, &iv);
+
+        let cnf = model.to_dimacs();
+        let header = cnf.lines().find(|l| l.starts_with("p cnf")).unwrap();
+        let clauses: usize = header.split(' ').nth(3).unwrap().parse().unwrap();
+        assert_eq!(header, format!("p cnf {} {clauses}", model.num_vars()));
+        assert_eq!(cnf.lines().filter(|l| l.ends_with(" 0")).count(), clauses);
+        assert!(cnf.starts_with("c key 1 2 3 "));
+        // units of the fixed bits
+        assert!(cnf.contains("\n1 0\n"));
+        assert!(cnf.contains("\n-129 0\n"));
+        // the evaluation satisfies every clause
+        for line in cnf.lines().filter(|l| !l.starts_with(['c', 'p'])) {
+            let lits: Vec<i64> = line.split(' ').map(|l| l.parse().unwrap()).collect();
+            assert!(lits
+                .iter()
+                .any(|&l| { l != 0 && values[usize::try_from(l.abs() - 1).unwrap()] == (l > 0) }));
+        }
+
+        let anf = model.to_anf();
+        assert!(anf.starts_with("# key 1 2 3 "));
+        assert!(anf.contains("\nx1 + 1\n"));
+        assert!(anf.contains("\nx129\n"));
+        let equations = anf.lines().filter(|l| !l.starts_with('#')).count();
+        assert!(equations > model.num_vars() - 256);
+        // the evaluation solves every equation
+        for line in anf.lines().filter(|l| !l.starts_with('#')) {
+            let sum = line.split(" + ").fold(false, |sum, term| {
+                sum ^ term.split('*').all(|factor| match factor {
+                    "1" => true,
+                    x => values[x[1..].parse::<usize>().unwrap() - 1],
+                })
+            });
+            assert!(!sum, "{line}");
+        }
+    }
+}
@@ -0,0 +1,406 @@
+//! Security algorithm identifiers
+//! ([TS 33.401](https://www.3gpp.org/DynaReport/33401.htm) 5.1.3, [TS 33.501](https://www.3gpp.org/DynaReport/33501.htm) 5.11.1)
+//!
+//! 128-NEA3/128-EEA3 resolve to [`eea3_128_encrypt`],
+//! 128-NIA3/128-EIA3 resolve to [`eia3_128_generate_mac`].
+//! NEA0 and NIA0 are the null algorithms.
+
+use crate::{eea3_128_encrypt, eia3_128_generate_mac};
+
+use std::fmt;
+
+/// Ciphering algorithm (NEA/EEA)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CipheringAlgorithm {
+    /// Null ciphering algorithm
+    Nea0 = 0,
+    /// 128-NEA1 (SNOW 3G based)
+    Nea1 = 1,
+    /// 128-NEA2 (AES based)
+    Nea2 = 2,
+    /// 128-NEA3 (ZUC based)
+    Nea3 = 3,
+}
+
+/// Integrity algorithm (NIA/EIA)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IntegrityAlgorithm {
+    /// Null integrity algorithm
+    Nia0 = 0,
+    /// 128-NIA1 (SNOW 3G based)
+    Nia1 = 1,
+    /// 128-NIA2 (AES based)
+    Nia2 = 2,
+    /// 128-NIA3 (ZUC based)
+    Nia3 = 3,
+}
+
+/// Security algorithm errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlgorithmError {
+    /// The 4-bit algorithm identifier is not defined
+    UnknownCode(u8),
+    /// The ciphering algorithm is not implemented by this crate
+    UnsupportedCiphering(CipheringAlgorithm),
+    /// The integrity algorithm is not implemented by this crate
+    UnsupportedIntegrity(IntegrityAlgorithm),
+    /// The UE security capability IE has an invalid length
+    InvalidLength(usize),
+}
+
+impl fmt::Display for AlgorithmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlgorithmError::UnknownCode(c) => write!(f, "unknown algorithm identifier {c}"),
+            AlgorithmError::UnsupportedCiphering(a) => {
+                write!(f, "unsupported ciphering algorithm {a:?}")
+            }
+            AlgorithmError::UnsupportedIntegrity(a) => {
+                write!(f, "unsupported integrity algorithm {a:?}")
+            }
+            AlgorithmError::InvalidLength(n) => {
+                write!(f, "invalid UE security capability length {n}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AlgorithmError {}
+
+impl CipheringAlgorithm {
+    /// All ciphering algorithms in identifier order
+    pub const ALL: [Self; 4] = [Self::Nea0, Self::Nea1, Self::Nea2, Self::Nea3];
+
+    /// Returns the 4-bit algorithm identifier
+    #[must_use]
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    /// Decodes a 4-bit algorithm identifier
+    ///
+    /// # Errors
+    /// + Returns [`AlgorithmError::UnknownCode`] if `code` is not defined.
+    pub fn from_code(code: u8) -> Result<Self, AlgorithmError> {
+        Self::ALL
+            .get(usize::from(code))
+            .copied()
+            .ok_or(AlgorithmError::UnknownCode(code))
+    }
+
+    /// Encrypts a bit stream, see [`eea3_128_encrypt`]
+    ///
+    /// NEA0 returns the input unchanged.
+    ///
+    /// # Errors
+    /// + Returns [`AlgorithmError::UnsupportedCiphering`] for NEA1 and NEA2.
+    ///
+    /// # Panics
+    /// + Panics if `length` is greater than the length of `ibs` times 8.
+    /// + Panics if `length` is greater than `usize::MAX`.
+    pub fn encrypt(
+        self,
+        count: u32,
+        bearer: u8,
+        direction: u8,
+        key: &[u8; 16],
+        length: u32,
+        ibs: &[u8],
+    ) -> Result<Vec<u8>, AlgorithmError> {
+        match self {
+            Self::Nea0 => {
+                let bitlen =
+                    usize::try_from(length).expect("`length` is greater than `usize::MAX`");
+                assert!(
+                    bitlen <= ibs.len() * 8,
+                    "`length` is greater than the length of `ibs` times 8"
+                );
+                Ok(ibs.to_vec())
+            }
+            Self::Nea3 => Ok(eea3_128_encrypt(count, bearer, direction, key, length, ibs)),
+            Self::Nea1 | Self::Nea2 => Err(AlgorithmError::UnsupportedCiphering(self)),
+        }
+    }
+}
+
+impl IntegrityAlgorithm {
+    /// All integrity algorithms in identifier order
+    pub const ALL: [Self; 4] = [Self::Nia0, Self::Nia1, Self::Nia2, Self::Nia3];
+
+    /// Returns the 4-bit algorithm identifier
+    #[must_use]
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    /// Decodes a 4-bit algorithm identifier
+    ///
+    /// # Errors
+    /// + Returns [`AlgorithmError::UnknownCode`] if `code` is not defined.
+    pub fn from_code(code: u8) -> Result<Self, AlgorithmError> {
+        Self::ALL
+            .get(usize::from(code))
+            .copied()
+            .ok_or(AlgorithmError::UnknownCode(code))
+    }
+
+    /// Generates a 32-bit MAC, see [`eia3_128_generate_mac`]
+    ///
+    /// NIA0 returns a MAC of 32 zero bits.
+    ///
+    /// # Errors
+    /// + Returns [`AlgorithmError::UnsupportedIntegrity`] for NIA1 and NIA2.
+    ///
+    /// # Panics
+    /// + Panics if `length` is greater than the length of `m` times 8.
+    /// + Panics if `length` is greater than `usize::MAX`.
+    pub fn generate_mac(
+        self,
+        count: u32,
+        bearer: u8,
+        direction: u8,
+        key: &[u8; 16],
+        length: u32,
+        m: &[u8],
+    ) -> Result<u32, AlgorithmError> {
+        match self {
+            Self::Nia0 => {
+                let bitlen =
+                    usize::try_from(length).expect("`length` is greater than `usize::MAX`");
+                assert!(
+                    bitlen <= m.len() * 8,
+                    "`length` is greater than the length of `m` times 8"
+                );
+                Ok(0)
+            }
+            Self::Nia3 => Ok(eia3_128_generate_mac(
+                count, bearer, direction, key, length, m,
+            )),
+            Self::Nia1 | Self::Nia2 => Err(AlgorithmError::UnsupportedIntegrity(self)),
+        }
+    }
+}
+
+/// UE security capability
+///
+/// Each bitmap has the algorithm with identifier 0 at the most significant bit,
+/// as in the NAS UE security capability IE.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct UeSecurityCapability {
+    /// 5G-EA0..5G-EA7
+    pub nea: u8,
+    /// 5G-IA0..5G-IA7
+    pub nia: u8,
+    /// EEA0..EEA7, if present
+    pub eea: Option<u8>,
+    /// EIA0..EIA7, if present
+    pub eia: Option<u8>,
+}
+
+/// NAS bitmap bit of an algorithm identifier
+fn nas_bit(code: u8) -> u8 {
+    0x80 >> code
+}
+
+/// 16-bit bitmap bit of an algorithm identifier
+fn bitmap16_bit(code: u8) -> u16 {
+    0x8000 >> (code - 1)
+}
+
+impl UeSecurityCapability {
+    /// Whether the UE supports a 5G ciphering algorithm
+    #[must_use]
+    pub fn supports_nea(&self, alg: CipheringAlgorithm) -> bool {
+        self.nea & nas_bit(alg.code()) != 0
+    }
+
+    /// Whether the UE supports a 5G integrity algorithm
+    #[must_use]
+    pub fn supports_nia(&self, alg: IntegrityAlgorithm) -> bool {
+        self.nia & nas_bit(alg.code()) != 0
+    }
+
+    /// Marks a 5G ciphering algorithm as supported
+    pub fn add_nea(&mut self, alg: CipheringAlgorithm) {
+        self.nea |= nas_bit(alg.code());
+    }
+
+    /// Marks a 5G integrity algorithm as supported
+    pub fn add_nia(&mut self, alg: IntegrityAlgorithm) {
+        self.nia |= nas_bit(alg.code());
+    }
+
+    /// Encodes the value part of the NAS UE security capability IE
+    /// ([TS 24.501](https://www.3gpp.org/DynaReport/24501.htm) 9.11.3.54)
+    ///
+    /// The EIA octet is only encoded together with the EEA octet.
+    #[must_use]
+    pub fn to_nas_bytes(&self) -> Vec<u8> {
+        let mut v = vec![self.nea, self.nia];
+        if let Some(eea) = self.eea {
+            v.push(eea);
+            v.push(self.eia.unwrap_or(0));
+        }
+        v
+    }
+
+    /// Decodes the value part of the NAS UE security capability IE
+    ///
+    /// Spare octets after the EIA octet are ignored.
+    ///
+    /// # Errors
+    /// + Returns [`AlgorithmError::InvalidLength`] if `bytes` is not 2 to 8 bytes long.
+    pub fn from_nas_bytes(bytes: &[u8]) -> Result<Self, AlgorithmError> {
+        if !(2..=8).contains(&bytes.len()) {
+            return Err(AlgorithmError::InvalidLength(bytes.len()));
+        }
+        Ok(Self {
+            nea: bytes[0],
+            nia: bytes[1],
+            eea: bytes.get(2).copied(),
+            eia: bytes.get(3).copied(),
+        })
+    }
+
+    /// Encodes the 16-bit NR encryption and integrity algorithm bitmaps
+    /// used by RRC and NGAP
+    ///
+    /// The first bit is 128-NEA1/128-NIA1, the null algorithms are implicit.
+    #[must_use]
+    pub fn to_bitmap16(&self) -> (u16, u16) {
+        let mut nea = 0;
+        let mut nia = 0;
+        for code in 1..8 {
+            if self.nea & nas_bit(code) != 0 {
+                nea |= bitmap16_bit(code);
+            }
+            if self.nia & nas_bit(code) != 0 {
+                nia |= bitmap16_bit(code);
+            }
+        }
+        (nea, nia)
+    }
+
+    /// Decodes the 16-bit NR encryption and integrity algorithm bitmaps
+    /// used by RRC and NGAP
+    ///
+    /// NEA0 and NIA0 are always marked as supported.
+    #[must_use]
+    pub fn from_bitmap16(nea: u16, nia: u16) -> Self {
+        let mut cap = Self {
+            nea: nas_bit(0),
+            nia: nas_bit(0),
+            eea: None,
+            eia: None,
+        };
+        for code in 1..8 {
+            if nea & bitmap16_bit(code) != 0 {
+                cap.nea |= nas_bit(code);
+            }
+            if nia & bitmap16_bit(code) != 0 {
+                cap.nia |= nas_bit(code);
+            }
+        }
+        cap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use const_str::hex;
+
+    #[test]
+    fn codes() {
+        for code in 0..4 {
+            assert_eq!(CipheringAlgorithm::from_code(code).unwrap().code(), code);
+            assert_eq!(IntegrityAlgorithm::from_code(code).unwrap().code(), code);
+        }
+        assert_eq!(
+            CipheringAlgorithm::from_code(4),
+            Err(AlgorithmError::UnknownCode(4))
+        );
+        assert_eq!(
+            IntegrityAlgorithm::from_code(15),
+            Err(AlgorithmError::UnknownCode(15))
+        );
+    }
+
+    #[test]
+    fn nas_ie() {
+        let mut cap = UeSecurityCapability::default();
+        for alg in CipheringAlgorithm::ALL {
+            cap.add_nea(alg);
+        }
+        cap.add_nia(IntegrityAlgorithm::Nia2);
+        cap.add_nia(IntegrityAlgorithm::Nia3);
+        assert_eq!(cap.to_nas_bytes(), [0xf0, 0x30]);
+        assert!(cap.supports_nea(CipheringAlgorithm::Nea3));
+        assert!(!cap.supports_nia(IntegrityAlgorithm::Nia1));
+
+        cap.eea = Some(0xf0);
+        cap.eia = Some(0x70);
+        assert_eq!(cap.to_nas_bytes(), [0xf0, 0x30, 0xf0, 0x70]);
+
+        let decoded =
+            UeSecurityCapability::from_nas_bytes(&[0xf0, 0x30, 0xf0, 0x70, 0, 0]).unwrap();
+        assert_eq!(decoded, cap);
+        assert_eq!(
+            UeSecurityCapability::from_nas_bytes(&[0xf0]),
+            Err(AlgorithmError::InvalidLength(1))
+        );
+    }
+
+    #[test]
+    fn bitmap16() {
+        let cap = UeSecurityCapability::from_nas_bytes(&[0xb0, 0xf0]).unwrap();
+        assert_eq!(cap.to_bitmap16(), (0x6000, 0xe000));
+        assert_eq!(UeSecurityCapability::from_bitmap16(0x6000, 0xe000), cap);
+    }
+
+    /// Test Set 1
+    /// FROM <https://www.gsma.com/solutions-and-impact/technologies/security/wp-content/uploads/2019/05/eea3eia3testdatav11.pdf>
+    #[test]
+    fn dispatch() {
+        let ck = hex!("17 3d 14 ba 50 03 73 1d 7a 60 04 94 70 f0 0a 29");
+        let ibs = hex!("6cf65340 735552ab 0c9752fa 6f9025fe 0bd675d9 005875b2 00000000");
+        let obs = hex!("a6c85fc6 6afb8533 aafc2518 dfe78494 0ee1e4b0 30238cc8 00000000");
+
+        let alg = CipheringAlgorithm::from_code(3).unwrap();
+        assert_eq!(
+            alg.encrypt(0x6603_5492, 0xf, 0, &ck, 193, &ibs).unwrap(),
+            obs
+        );
+        assert_eq!(
+            CipheringAlgorithm::Nea0
+                .encrypt(0x6603_5492, 0xf, 0, &ck, 193, &ibs)
+                .unwrap(),
+            ibs
+        );
+        assert_eq!(
+            CipheringAlgorithm::Nea2.encrypt(0, 0, 0, &ck, 0, &[]),
+            Err(AlgorithmError::UnsupportedCiphering(
+                CipheringAlgorithm::Nea2
+            ))
+        );
+
+        let alg = IntegrityAlgorithm::from_code(3).unwrap();
+        assert_eq!(
+            alg.generate_mac(0, 0, 0, &[0; 16], 1, &[0; 4]).unwrap(),
+            0xc8a9_595e
+        );
+        assert_eq!(
+            IntegrityAlgorithm::Nia0
+                .generate_mac(0, 0, 0, &[0; 16], 1, &[0; 4])
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            IntegrityAlgorithm::Nia1.generate_mac(0, 0, 0, &[0; 16], 0, &[]),
+            Err(AlgorithmError::UnsupportedIntegrity(
+                IntegrityAlgorithm::Nia1
+            ))
+        );
+    }
+}
@@ -0,0 +1,418 @@
+//! Analysis of the ZUC S boxes and linear transforms
+//!
+//! Computes difference distribution tables, linear approximation tables,
+//! algebraic normal forms and other properties of 8-bit S boxes such as [`S0`] and [`S1`],
+//! and the branch numbers of the linear transforms `L1` and `L2` of the F function.
+//! Tables and reports export as CSV or JSON.
+
+pub use crate::zuc::{S0, S1};
+
+use crate::zuc::{rotate_xor, L1_INV_ROTATIONS, L1_ROTATIONS, L2_INV_ROTATIONS, L2_ROTATIONS};
+
+use std::fmt::{self, Write as _};
+
+/// 256 x 256 table indexed by input and output masks or differences
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    /// rows indexed by the input
+    rows: Vec<[i32; 256]>,
+}
+
+impl Table {
+    /// Returns the entry of input `a` and output `b`
+    #[must_use]
+    pub fn get(&self, a: u8, b: u8) -> i32 {
+        self.rows[usize::from(a)][usize::from(b)]
+    }
+
+    /// Returns the largest absolute value of the entries with a nonzero input
+    #[must_use]
+    pub fn max_nontrivial(&self) -> i32 {
+        self.rows[1..]
+            .iter()
+            .flatten()
+            .map(|v| v.abs())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Renders the table as CSV, one line per input
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        let mut out = String::new();
+        for row in &self.rows {
+            let row: Vec<_> = row.iter().map(i32::to_string).collect();
+            let _ = writeln!(out, "{}", row.join(","));
+        }
+        out
+    }
+
+    /// Renders the table as a JSON array of rows
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        let rows: Vec<_> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let row: Vec<_> = row.iter().map(i32::to_string).collect();
+                format!("[{}]", row.join(","))
+            })
+            .collect();
+        format!("[{}]", rows.join(","))
+    }
+}
+
+/// Computes the difference distribution table
+///
+/// Entry `(a, b)` is the number of inputs `x` with `S(x) ^ S(x ^ a) == b`.
+#[must_use]
+pub fn ddt(sbox: &[u8; 256]) -> Table {
+    let mut rows = vec![[0; 256]; 256];
+    for (a, row) in rows.iter_mut().enumerate() {
+        for x in 0..256 {
+            row[usize::from(sbox[x] ^ sbox[x ^ a])] += 1;
+        }
+    }
+    Table { rows }
+}
+
+/// Computes the linear approximation table
+///
+/// Entry `(a, b)` is the number of inputs `x` with `a·x == b·S(x)`, minus 128.
+#[must_use]
+pub fn lat(sbox: &[u8; 256]) -> Table {
+    let mut rows = vec![[-128; 256]; 256];
+    for (a, row) in (0..=255u8).zip(rows.iter_mut()) {
+        for (b, entry) in (0..=255u8).zip(row.iter_mut()) {
+            for (x, &y) in (0..=255u8).zip(sbox.iter()) {
+                if (a & x).count_ones() % 2 == (b & y).count_ones() % 2 {
+                    *entry += 1;
+                }
+            }
+        }
+    }
+    Table { rows }
+}
+
+/// Algebraic normal form of a Boolean function of 8 variables
+///
+/// Monomial `m` is the product of the variables `x_i` whose bit `i` is set in `m`,
+/// where `x_0` is the least significant input bit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Anf {
+    /// coefficients indexed by monomial
+    coefficients: [bool; 256],
+}
+
+impl Anf {
+    /// Returns the monomials with a nonzero coefficient, in increasing order
+    pub fn monomials(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..=255u8).filter(|&m| self.coefficients[usize::from(m)])
+    }
+
+    /// Returns the algebraic degree, 0 for constant functions
+    #[must_use]
+    pub fn degree(&self) -> u32 {
+        self.monomials().map(u8::count_ones).max().unwrap_or(0)
+    }
+}
+
+impl fmt::Display for Anf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for m in self.monomials() {
+            if !first {
+                write!(f, " + ")?;
+            }
+            first = false;
+            if m == 0 {
+                write!(f, "1")?;
+            }
+            for i in (0..8).filter(|i| m >> i & 1 == 1) {
+                write!(f, "x{i}")?;
+            }
+        }
+        if first {
+            write!(f, "0")?;
+        }
+        Ok(())
+    }
+}
+
+/// Computes the algebraic normal forms of the 8 output bits
+///
+/// Element `i` is the ANF of output bit `i`, the least significant bit being bit 0.
+#[must_use]
+pub fn anf(sbox: &[u8; 256]) -> [Anf; 8] {
+    std::array::from_fn(|bit| {
+        let mut coefficients = [false; 256];
+        for (c, &y) in coefficients.iter_mut().zip(sbox.iter()) {
+            *c = y >> bit & 1 == 1;
+        }
+        // Möbius transform
+        for step in (0..8).map(|i| 1 << i) {
+            for m in 0..256 {
+                if m & step != 0 {
+                    coefficients[m] ^= coefficients[m ^ step];
+                }
+            }
+        }
+        Anf { coefficients }
+    })
+}
+
+/// Returns the algebraic degree, the largest degree of the output bits
+#[must_use]
+pub fn algebraic_degree(sbox: &[u8; 256]) -> u32 {
+    anf(sbox).iter().map(Anf::degree).max().unwrap_or(0)
+}
+
+/// Returns whether the S box is a permutation
+#[must_use]
+pub fn is_bijective(sbox: &[u8; 256]) -> bool {
+    let mut seen = [false; 256];
+    for &y in sbox {
+        seen[usize::from(y)] = true;
+    }
+    seen.iter().all(|&s| s)
+}
+
+/// Returns the inputs `x` with `S(x) == x`
+#[must_use]
+pub fn fixed_points(sbox: &[u8; 256]) -> Vec<u8> {
+    (0..=255u8).filter(|&x| sbox[usize::from(x)] == x).collect()
+}
+
+/// Properties of an 8-bit S box
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SboxReport {
+    /// whether the S box is a permutation
+    pub bijective: bool,
+    /// inputs `x` with `S(x) == x`
+    pub fixed_points: Vec<u8>,
+    /// largest DDT entry with a nonzero input difference
+    pub differential_uniformity: i32,
+    /// largest absolute LAT entry with a nonzero input mask
+    pub linearity: i32,
+    /// nonlinearity, `128 - linearity`
+    pub nonlinearity: i32,
+    /// algebraic degree
+    pub algebraic_degree: u32,
+}
+
+impl SboxReport {
+    /// Analyses an S box
+    #[must_use]
+    pub fn new(sbox: &[u8; 256]) -> Self {
+        let linearity = lat(sbox).max_nontrivial();
+        Self {
+            bijective: is_bijective(sbox),
+            fixed_points: fixed_points(sbox),
+            differential_uniformity: ddt(sbox).max_nontrivial(),
+            linearity,
+            nonlinearity: 128 - linearity,
+            algebraic_degree: algebraic_degree(sbox),
+        }
+    }
+
+    /// Renders the report as CSV, one `property,value` line per property
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        let fixed: Vec<_> = self.fixed_points.iter().map(u8::to_string).collect();
+        format!(
+            "property,value\n\
+             bijective,{}\n\
+             fixed_points,{}\n\
+             differential_uniformity,{}\n\
+             linearity,{}\n\
+             nonlinearity,{}\n\
+             algebraic_degree,{}\n",
+            self.bijective,
+            fixed.join(" "),
+            self.differential_uniformity,
+            self.linearity,
+            self.nonlinearity,
+            self.algebraic_degree,
+        )
+    }
+
+    /// Renders the report as a JSON object
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        let fixed: Vec<_> = self.fixed_points.iter().map(u8::to_string).collect();
+        format!(
+            "{{\"bijective\":{},\"fixed_points\":[{}],\"differential_uniformity\":{},\
+             \"linearity\":{},\"nonlinearity\":{},\"algebraic_degree\":{}}}",
+            self.bijective,
+            fixed.join(","),
+            self.differential_uniformity,
+            self.linearity,
+            self.nonlinearity,
+            self.algebraic_degree,
+        )
+    }
+}
+
+/// Linear transforms of the F function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinearTransform {
+    /// L1
+    L1,
+    /// L2
+    L2,
+}
+
+impl LinearTransform {
+    /// Returns the left rotations of the transform and of its inverse
+    fn rotations(self) -> (&'static [u32], &'static [u32]) {
+        match self {
+            LinearTransform::L1 => (L1_ROTATIONS, L1_INV_ROTATIONS),
+            LinearTransform::L2 => (L2_ROTATIONS, L2_INV_ROTATIONS),
+        }
+    }
+
+    /// Applies the transform
+    #[must_use]
+    pub fn apply(self, x: u32) -> u32 {
+        rotate_xor(x, self.rotations().0)
+    }
+
+    /// Returns the differential branch number over bytes,
+    /// the minimum of `wt(x) + wt(L(x))` for nonzero `x`
+    #[must_use]
+    pub fn differential_branch_number(self) -> u32 {
+        let (forward, inverse) = self.rotations();
+        branch_number(forward, inverse)
+    }
+
+    /// Returns the linear branch number over bytes,
+    /// the minimum of `wt(x) + wt(L^T(x))` for nonzero `x`
+    #[must_use]
+    pub fn linear_branch_number(self) -> u32 {
+        // the transpose of a left rotation is the right rotation
+        let transpose =
+            |rotations: &[u32]| -> Vec<u32> { rotations.iter().map(|&n| (32 - n) % 32).collect() };
+        let (forward, inverse) = self.rotations();
+        branch_number(&transpose(forward), &transpose(inverse))
+    }
+}
+
+/// Number of nonzero bytes
+fn byte_weight(x: u32) -> u32 {
+    x.to_be_bytes().iter().map(|&b| u32::from(b != 0)).sum()
+}
+
+/// Calls `f` with every word with one or two nonzero bytes
+fn for_each_light_word(mut f: impl FnMut(u32)) {
+    for i in 0..4 {
+        for a in 1..=255u32 {
+            let x = a << (8 * i);
+            f(x);
+            for j in i + 1..4 {
+                for b in 1..=255u32 {
+                    f(x | (b << (8 * j)));
+                }
+            }
+        }
+    }
+}
+
+/// Branch number over bytes of an invertible rotate-XOR transform
+///
+/// A pair `(x, L(x))` of total weight below 5 has one side of weight at most 2,
+/// so it is enough to search `x` and `L^-1(y)` for light words `x` and `y`.
+/// Any word of weight 1 bounds the branch number by 5.
+fn branch_number(forward: &[u32], inverse: &[u32]) -> u32 {
+    let mut min = 5;
+    for_each_light_word(|x| {
+        let forward_weight = byte_weight(x) + byte_weight(rotate_xor(x, forward));
+        let inverse_weight = byte_weight(x) + byte_weight(rotate_xor(x, inverse));
+        min = min.min(forward_weight).min(inverse_weight);
+    });
+    min
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sbox_reports() {
+        // values of the ZUC design and evaluation report
+        let s0 = SboxReport::new(&S0);
+        assert!(s0.bijective);
+        assert_eq!(s0.differential_uniformity, 8);
+        assert_eq!(s0.nonlinearity, 96);
+        assert_eq!(s0.algebraic_degree, 5);
+
+        let s1 = SboxReport::new(&S1);
+        assert!(s1.bijective);
+        assert_eq!(s1.differential_uniformity, 4);
+        assert_eq!(s1.nonlinearity, 112);
+        assert_eq!(s1.algebraic_degree, 7);
+
+        for sbox in [&S0, &S1] {
+            let report = SboxReport::new(sbox);
+            assert_eq!(report.fixed_points, fixed_points(sbox));
+            assert!(report
+                .fixed_points
+                .iter()
+                .all(|&x| sbox[usize::from(x)] == x));
+        }
+
+        let mut s = S0;
+        s[0] = s[1];
+        assert!(!is_bijective(&s));
+    }
+
+    #[test]
+    fn tables() {
+        let ddt = ddt(&S1);
+        assert_eq!(ddt.get(0, 0), 256);
+        for a in 0..=255 {
+            let row: i32 = (0..=255).map(|b| ddt.get(a, b)).sum();
+            assert_eq!(row, 256);
+        }
+
+        let lat = lat(&S1);
+        assert_eq!(lat.get(0, 0), 128);
+        assert_eq!(lat.get(0, 1), 0);
+        assert_eq!(lat.max_nontrivial(), 16);
+
+        let csv = ddt.to_csv();
+        assert_eq!(csv.lines().count(), 256);
+        assert!(csv.starts_with("256,0,0,"));
+        let json = lat.to_json();
+        assert!(json.starts_with("[[128,0,"));
+        assert!(json.ends_with("]]"));
+    }
+
+    #[test]
+    fn anf_evaluates() {
+        let anfs = anf(&S0);
+        for x in 0..=255u8 {
+            for (bit, anf) in anfs.iter().enumerate() {
+                let value = anf.monomials().filter(|&m| x & m == m).count() % 2;
+                assert_eq!(value, usize::from(S0[usize::from(x)] >> bit & 1));
+            }
+        }
+
+        let identity: [u8; 256] = std::array::from_fn(|x| u8::try_from(x).unwrap());
+        let anfs = anf(&identity);
+        assert_eq!(anfs[3].to_string(), "x3");
+        assert_eq!(algebraic_degree(&identity), 1);
+        let not: [u8; 256] = identity.map(|x| !x);
+        assert_eq!(anf(&not)[0].to_string(), "1 + x0");
+    }
+
+    #[test]
+    fn branch_numbers() {
+        for l in [LinearTransform::L1, LinearTransform::L2] {
+            assert_eq!(l.differential_branch_number(), 5);
+            assert_eq!(l.linear_branch_number(), 5);
+        }
+        // the identity has branch number 2
+        assert_eq!(branch_number(&[0], &[0]), 2);
+        assert_eq!(LinearTransform::L1.apply(1), 0x0104_0405);
+    }
+}
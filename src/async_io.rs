@@ -0,0 +1,292 @@
+//! Tokio I/O adapters applying ZUC keystreams
+//!
+//! Async counterparts of [`ZucReader`](crate::io::ZucReader) and
+//! [`ZucWriter`](crate::io::ZucWriter). The keystream is applied exactly once to
+//! each byte the underlying stream accepts or returns, so partial reads and writes
+//! keep the keystream position.
+
+use crate::io::StreamMac;
+
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use cipher::StreamCipher;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Buffer size of the writer
+const BUF_SIZE: usize = 8 * 1024;
+
+/// Async reader applying a keystream to the data read from an underlying reader
+///
+/// The MAC covers the data read from the underlying reader, before the keystream is applied.
+pub struct AsyncZucReader<R, C, M = ()> {
+    /// underlying reader
+    inner: R,
+    /// stream cipher
+    cipher: C,
+    /// MAC over the input
+    mac: M,
+}
+
+impl<R: AsyncRead + Unpin, C: StreamCipher> AsyncZucReader<R, C> {
+    /// Creates a reader without MAC
+    pub fn new(inner: R, cipher: C) -> Self {
+        Self::with_mac(inner, cipher, ())
+    }
+}
+
+impl<R: AsyncRead + Unpin, C: StreamCipher, M: StreamMac> AsyncZucReader<R, C, M> {
+    /// Creates a reader computing a MAC over its input
+    pub fn with_mac(inner: R, cipher: C, mac: M) -> Self {
+        Self { inner, cipher, mac }
+    }
+
+    /// Returns a reference to the underlying reader
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns the underlying reader and the MAC of all data read from it
+    pub fn finish(self) -> (R, M::Tag) {
+        (self.inner, self.mac.finish())
+    }
+}
+
+impl<R, C, M> AsyncRead for AsyncZucReader<R, C, M>
+where
+    R: AsyncRead + Unpin,
+    C: StreamCipher + Unpin,
+    M: StreamMac + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let start = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        let data = &mut buf.filled_mut()[start..];
+        this.mac.update(data);
+        this.cipher.apply_keystream(data);
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Async writer applying a keystream to the data written to an underlying writer
+///
+/// The MAC covers the data written to the underlying writer, after the keystream is applied.
+/// It is finalized by [`AsyncWrite::poll_shutdown`], after which writes fail.
+/// Processed data is buffered until the buffer is full, flushed or shut down.
+pub struct AsyncZucWriter<W, C, M: StreamMac = ()> {
+    /// underlying writer
+    inner: W,
+    /// stream cipher
+    cipher: C,
+    /// MAC over the output, `None` after shutdown
+    mac: Option<M>,
+    /// MAC of the output, set on shutdown
+    tag: Option<M::Tag>,
+    /// processed data not yet written
+    buf: Vec<u8>,
+    /// number of bytes of `buf` already written
+    written: usize,
+}
+
+impl<W: AsyncWrite + Unpin, C: StreamCipher> AsyncZucWriter<W, C> {
+    /// Creates a writer without MAC
+    pub fn new(inner: W, cipher: C) -> Self {
+        Self::with_mac(inner, cipher, ())
+    }
+}
+
+impl<W: AsyncWrite + Unpin, C: StreamCipher, M: StreamMac> AsyncZucWriter<W, C, M> {
+    /// Creates a writer computing a MAC over its output
+    pub fn with_mac(inner: W, cipher: C, mac: M) -> Self {
+        Self {
+            inner,
+            cipher,
+            mac: Some(mac),
+            tag: None,
+            buf: Vec::with_capacity(BUF_SIZE),
+            written: 0,
+        }
+    }
+
+    /// Returns a reference to the underlying writer
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns the MAC of the output, available after shutdown
+    pub fn tag(&self) -> Option<&M::Tag> {
+        self.tag.as_ref()
+    }
+
+    /// Returns the underlying writer and the MAC of the output if the writer has been shut down
+    pub fn into_inner(self) -> (W, Option<M::Tag>) {
+        (self.inner, self.tag)
+    }
+
+    /// Writes the buffered data to the underlying writer
+    fn poll_flush_buf(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.written < self.buf.len() {
+            let n = ready!(Pin::new(&mut self.inner).poll_write(cx, &self.buf[self.written..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.written += n;
+        }
+        self.buf.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W, C, M> AsyncWrite for AsyncZucWriter<W, C, M>
+where
+    W: AsyncWrite + Unpin,
+    C: StreamCipher + Unpin,
+    M: StreamMac + Unpin,
+    M::Tag: Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        data: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.buf.len() == BUF_SIZE {
+            ready!(this.poll_flush_buf(cx))?;
+        }
+        let Some(mac) = this.mac.as_mut() else {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "write after shutdown",
+            )));
+        };
+        let start = this.buf.len();
+        let n = data.len().min(BUF_SIZE - start);
+        this.buf.extend_from_slice(&data[..n]);
+        this.cipher.apply_keystream(&mut this.buf[start..]);
+        mac.update(&this.buf[start..]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_flush_buf(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_flush_buf(cx))?;
+        if let Some(mac) = this.mac.take() {
+            this.tag = Some(mac.finish());
+        }
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{eia3_128_generate_mac, Zuc128, Zuc128Mac, Zuc256, Zuc256Mac};
+
+    use cipher::KeyIvInit;
+    use rand::Rng;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    static K128: [u8; 16] = [0x17; 16];
+    static IV128: [u8; 16] = [0x66; 16];
+    static K256: [u8; 32] = [0x3d; 32];
+    static IV256: [u8; 23] = [0x84; 23];
+
+    fn random_data(len: usize) -> Vec<u8> {
+        (0..len).map(|_| rand::random::<u8>()).collect()
+    }
+
+    async fn write_chunks(w: &mut (impl AsyncWrite + Unpin), data: &[u8]) {
+        let mut rest = data;
+        while !rest.is_empty() {
+            let n = rand::thread_rng().gen_range(1..=rest.len().min(3000));
+            let n = w.write(&rest[..n]).await.unwrap();
+            rest = &rest[n..];
+        }
+        w.shutdown().await.unwrap();
+    }
+
+    async fn read_chunks(r: &mut (impl AsyncRead + Unpin)) -> Vec<u8> {
+        let mut out = Vec::new();
+        loop {
+            let mut buf = vec![0; rand::thread_rng().gen_range(1..3000)];
+            let n = r.read(&mut buf).await.unwrap();
+            if n == 0 {
+                return out;
+            }
+            out.extend_from_slice(&buf[..n]);
+        }
+    }
+
+    #[tokio::test]
+    async fn roundtrip_zuc128() {
+        let plain = random_data(50_000);
+        let (a, b) = tokio::io::duplex(100);
+
+        let writer = async {
+            let cipher = Zuc128::new(&K128.into(), &IV128.into());
+            let mac = Zuc128Mac::eia3(7, 3, 0, &K128);
+            let mut w = AsyncZucWriter::with_mac(a, cipher, mac);
+            write_chunks(&mut w, &plain).await;
+            w.into_inner().1.unwrap()
+        };
+        let reader = async {
+            let cipher = Zuc128::new(&K128.into(), &IV128.into());
+            let mac = Zuc128Mac::eia3(7, 3, 0, &K128);
+            let mut r = AsyncZucReader::with_mac(b, cipher, mac);
+            let out = read_chunks(&mut r).await;
+            (out, r.finish().1)
+        };
+        let (sent_tag, (out, received_tag)) = tokio::join!(writer, reader);
+        assert_eq!(out, plain);
+        assert_eq!(sent_tag, received_tag);
+
+        let mut ciphertext = plain.clone();
+        Zuc128::new(&K128.into(), &IV128.into()).apply_keystream(&mut ciphertext);
+        let bitlen = u32::try_from(ciphertext.len() * 8).unwrap();
+        assert_eq!(
+            sent_tag,
+            eia3_128_generate_mac(7, 3, 0, &K128, bitlen, &ciphertext)
+        );
+    }
+
+    #[tokio::test]
+    async fn roundtrip_zuc256() {
+        for _ in 0..5 {
+            let len = rand::thread_rng().gen_range(0..20_000);
+            let plain = random_data(len);
+            let (a, b) = tokio::io::duplex(rand::thread_rng().gen_range(1..500));
+
+            let writer = async {
+                let cipher = Zuc256::new(&K256.into(), &IV256.into());
+                let mac = Zuc256Mac::<u128>::new(&K256, &IV256);
+                let mut w = AsyncZucWriter::with_mac(a, cipher, mac);
+                write_chunks(&mut w, &plain).await;
+                assert!(w.write(b"x").await.is_err());
+                *w.tag().unwrap()
+            };
+            let reader = async {
+                let cipher = Zuc256::new(&K256.into(), &IV256.into());
+                let mac = Zuc256Mac::<u128>::new(&K256, &IV256);
+                let mut r = AsyncZucReader::with_mac(b, cipher, mac);
+                let out = read_chunks(&mut r).await;
+                (out, r.finish().1)
+            };
+            let (sent_tag, (out, received_tag)) = tokio::join!(writer, reader);
+            assert_eq!(out, plain);
+            assert_eq!(sent_tag, received_tag);
+        }
+    }
+}
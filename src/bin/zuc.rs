@@ -0,0 +1,517 @@
+//! `zuc` command-line tool
+
+#![deny(unsafe_code, missing_docs)]
+#![deny(clippy::all, clippy::pedantic, clippy::missing_docs_in_private_items)]
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+/// ZUC stream cipher tool
+#[derive(Parser)]
+#[command(name = "zuc", version, about)]
+struct Cli {
+    /// operation
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Subcommands
+#[derive(Subcommand)]
+enum Command {
+    /// 128-EEA3 encryption or decryption
+    Eea3 {
+        /// 128bit confidentiality key
+        #[arg(long, value_parser = parse_hex_bytes::<16>)]
+        key: [u8; 16],
+        /// link parameters
+        #[command(flatten)]
+        link: LinkArgs,
+        /// payload
+        #[command(flatten)]
+        io: IoArgs,
+    },
+    /// 128-EIA3 MAC generation
+    Eia3 {
+        /// 128bit integrity key
+        #[arg(long, value_parser = parse_hex_bytes::<16>)]
+        key: [u8; 16],
+        /// link parameters
+        #[command(flatten)]
+        link: LinkArgs,
+        /// payload
+        #[command(flatten)]
+        io: IoArgs,
+    },
+    /// ZUC128 keystream
+    Zuc128Keystream {
+        /// 128bit key
+        #[arg(long, value_parser = parse_hex_bytes::<16>)]
+        key: [u8; 16],
+        /// 128bit initial vector
+        #[arg(long, value_parser = parse_hex_bytes::<16>)]
+        iv: [u8; 16],
+        /// keystream
+        #[command(flatten)]
+        ks: KeystreamArgs,
+    },
+    /// ZUC256 keystream
+    Zuc256Keystream {
+        /// 256bit key
+        #[arg(long, value_parser = parse_hex_bytes::<32>)]
+        key: [u8; 32],
+        /// 184bit initial vector
+        #[arg(long, value_parser = parse_hex_bytes::<23>)]
+        iv: [u8; 23],
+        /// keystream
+        #[command(flatten)]
+        ks: KeystreamArgs,
+    },
+    /// ZUC256 MAC generation
+    Zuc256Mac {
+        /// 256bit integrity key
+        #[arg(long, value_parser = parse_hex_bytes::<32>)]
+        key: [u8; 32],
+        /// 184bit initial vector
+        #[arg(long, value_parser = parse_hex_bytes::<23>)]
+        iv: [u8; 23],
+        /// tag size in bits
+        #[arg(long, default_value_t = 32, value_parser = parse_tag_bits)]
+        tag_bits: u32,
+        /// payload
+        #[command(flatten)]
+        io: IoArgs,
+    },
+    /// Decrypts NAS and PDCP messages in a pcap or pcapng capture
+    PcapDecrypt {
+        /// input capture
+        #[arg(long, short)]
+        input: PathBuf,
+        /// decrypted pcap capture
+        #[arg(long, short)]
+        output: PathBuf,
+        /// report file, writes stdout if absent or `-`
+        #[arg(long)]
+        report: Option<PathBuf>,
+        /// keys
+        #[command(flatten)]
+        keys: KeyArgs,
+    },
+    /// Runs the NIST SP 800-22 statistical tests on keystreams of random keys and IVs
+    Stats {
+        /// keystream generator
+        #[arg(long, value_enum, default_value_t = Alg::Zuc128)]
+        alg: Alg,
+        /// number of (key, IV) pairs
+        #[arg(long, default_value_t = 100, value_parser = parse_usize)]
+        sequences: usize,
+        /// keystream bits of each pair
+        #[arg(long, default_value_t = 1_000_000, value_parser = parse_usize)]
+        bits: usize,
+        /// seed of the keys and IVs
+        #[arg(long, default_value_t = 0, value_parser = parse_int)]
+        seed: u64,
+        /// significance level
+        #[arg(long, default_value_t = 0.01, value_parser = parse_alpha)]
+        alpha: f64,
+        /// report file, writes stdout if absent or `-`
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+/// Keystream generator
+#[derive(Clone, Copy, ValueEnum)]
+enum Alg {
+    /// ZUC-128
+    Zuc128,
+    /// ZUC-256
+    Zuc256,
+}
+
+/// Capture decryption keys, missing ciphering keys mean NEA0
+#[derive(Args)]
+struct KeyArgs {
+    /// `K_NASenc`
+    #[arg(long = "nas-enc-key", value_parser = parse_hex_bytes::<16>)]
+    nas_enc: Option<[u8; 16]>,
+    /// `K_NASint`
+    #[arg(long = "nas-int-key", value_parser = parse_hex_bytes::<16>)]
+    nas_int: Option<[u8; 16]>,
+    /// `K_RRCenc`
+    #[arg(long = "rrc-enc-key", value_parser = parse_hex_bytes::<16>)]
+    rrc_enc: Option<[u8; 16]>,
+    /// `K_RRCint`
+    #[arg(long = "rrc-int-key", value_parser = parse_hex_bytes::<16>)]
+    rrc_int: Option<[u8; 16]>,
+    /// `K_UPenc`
+    #[arg(long = "up-enc-key", value_parser = parse_hex_bytes::<16>)]
+    up_enc: Option<[u8; 16]>,
+    /// `K_UPint`
+    #[arg(long = "up-int-key", value_parser = parse_hex_bytes::<16>)]
+    up_int: Option<[u8; 16]>,
+    /// check PDCP `MAC-I` against NIA0 when the RRC or UP integrity key is absent
+    #[arg(long)]
+    nia0: bool,
+}
+
+/// EEA3/EIA3 link parameters
+#[derive(Args)]
+struct LinkArgs {
+    /// 32bit counter
+    #[arg(long, value_parser = parse_u32)]
+    count: u32,
+    /// 5bit bearer identity
+    #[arg(long, value_parser = parse_bearer)]
+    bearer: u8,
+    /// 1bit direction
+    #[arg(long, value_parser = parse_direction)]
+    direction: u8,
+}
+
+/// Payload input and output
+#[derive(Args)]
+struct IoArgs {
+    /// input file, reads stdin if absent or `-`
+    #[arg(long, short)]
+    input: Option<PathBuf>,
+    /// input format
+    #[arg(long, value_enum, default_value_t = Format::Raw)]
+    input_format: Format,
+    /// bit length of the payload, defaults to the whole input
+    #[arg(long, value_parser = parse_u32)]
+    length: Option<u32>,
+    /// output
+    #[command(flatten)]
+    out: OutArgs,
+}
+
+/// Keystream length and output
+#[derive(Args)]
+struct KeystreamArgs {
+    /// number of 32bit keystream words
+    #[arg(long, value_parser = parse_usize)]
+    words: usize,
+    /// output
+    #[command(flatten)]
+    out: OutArgs,
+}
+
+/// Output destination
+#[derive(Args)]
+struct OutArgs {
+    /// output file, writes stdout if absent or `-`
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+    /// output format
+    #[arg(long, value_enum, default_value_t = Format::Hex)]
+    output_format: Format,
+}
+
+/// Payload encoding
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// binary
+    Raw,
+    /// hexadecimal, whitespace is ignored on input
+    Hex,
+    /// standard base64, whitespace is ignored on input
+    Base64,
+}
+
+/// Parses an integer as `0x`-prefixed hex or decimal
+fn parse_int(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let res = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(h) => u64::from_str_radix(h, 16),
+        None => s.parse(),
+    };
+    res.map_err(|e| format!("invalid integer `{s}`: {e}"))
+}
+
+/// Parses a 32bit integer
+fn parse_u32(s: &str) -> Result<u32, String> {
+    u32::try_from(parse_int(s)?).map_err(|_| format!("`{s}` does not fit in 32 bits"))
+}
+
+/// Parses a count of keystream words
+fn parse_usize(s: &str) -> Result<usize, String> {
+    usize::try_from(parse_int(s)?).map_err(|_| format!("`{s}` is too large"))
+}
+
+/// Parses a significance level in `(0, 1)`
+fn parse_alpha(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(a) if a > 0.0 && a < 1.0 => Ok(a),
+        _ => Err(format!("invalid significance level: {s}")),
+    }
+}
+
+/// Parses a 5bit bearer identity
+fn parse_bearer(s: &str) -> Result<u8, String> {
+    match u8::try_from(parse_int(s)?) {
+        Ok(x @ 0..=0x1f) => Ok(x),
+        _ => Err(format!("bearer `{s}` does not fit in 5 bits")),
+    }
+}
+
+/// Parses a 1bit direction
+fn parse_direction(s: &str) -> Result<u8, String> {
+    match u8::try_from(parse_int(s)?) {
+        Ok(x @ 0..=1) => Ok(x),
+        _ => Err(format!("direction `{s}` must be 0 or 1")),
+    }
+}
+
+/// Parses a ZUC256 MAC tag size
+fn parse_tag_bits(s: &str) -> Result<u32, String> {
+    match parse_u32(s)? {
+        x @ (32 | 64 | 128) => Ok(x),
+        _ => Err(format!("tag size `{s}` must be 32, 64 or 128")),
+    }
+}
+
+/// Decodes hex, ignoring whitespace and an optional `0x` prefix
+fn decode_hex(s: &str) -> Result<Vec<u8>, String> {
+    let s = s.trim();
+    let s = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    let digits: Vec<u8> = s.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if digits.len() % 2 == 1 {
+        return Err("odd number of hex digits".into());
+    }
+    digits
+        .chunks_exact(2)
+        .map(|pair| {
+            let text = String::from_utf8_lossy(pair);
+            // `from_str_radix` also accepts a leading sign
+            if !pair.iter().all(u8::is_ascii_hexdigit) {
+                return Err(format!("invalid hex digits `{text}`"));
+            }
+            u8::from_str_radix(&text, 16).map_err(|e| e.to_string())
+        })
+        .collect()
+}
+
+/// Parses a fixed-size hex byte string
+fn parse_hex_bytes<const N: usize>(s: &str) -> Result<[u8; N], String> {
+    let bytes = decode_hex(s)?;
+    <[u8; N]>::try_from(bytes.as_slice())
+        .map_err(|_| format!("expected {N} bytes, got {}", bytes.len()))
+}
+
+/// Encodes bytes as lowercase hex
+fn encode_hex(bytes: &[u8]) -> String {
+    use std::fmt::Write as _;
+    let mut s = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        let _ = write!(s, "{b:02x}");
+    }
+    s
+}
+
+/// Reads the payload and its bit length
+fn read_input(io: &IoArgs) -> Result<(Vec<u8>, u32), String> {
+    let mut raw = Vec::new();
+    match &io.input {
+        Some(path) if path.as_os_str() != "-" => {
+            raw = fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        }
+        _ => {
+            io::stdin()
+                .read_to_end(&mut raw)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    let data = match io.input_format {
+        Format::Raw => raw,
+        Format::Hex => decode_hex(&String::from_utf8_lossy(&raw))?,
+        Format::Base64 => {
+            let text: Vec<u8> = raw
+                .into_iter()
+                .filter(|b| !b.is_ascii_whitespace())
+                .collect();
+            BASE64.decode(text).map_err(|e| e.to_string())?
+        }
+    };
+
+    let max = data
+        .len()
+        .checked_mul(8)
+        .and_then(|n| u32::try_from(n).ok());
+    let length = match (io.length, max) {
+        (Some(length), Some(max)) if length > max => {
+            return Err(format!(
+                "bit length {length} exceeds the input length {max}"
+            ))
+        }
+        (Some(length), _) => length,
+        (None, Some(max)) => max,
+        (None, None) => return Err("input is too long".into()),
+    };
+    Ok((data, length))
+}
+
+/// Writes the result
+fn write_output(out: &OutArgs, data: &[u8]) -> Result<(), String> {
+    let encoded = match out.output_format {
+        Format::Raw => data.to_vec(),
+        Format::Hex => (encode_hex(data) + "\n").into_bytes(),
+        Format::Base64 => (BASE64.encode(data) + "\n").into_bytes(),
+    };
+    match &out.output {
+        Some(path) if path.as_os_str() != "-" => {
+            fs::write(path, encoded).map_err(|e| format!("{}: {e}", path.display()))
+        }
+        _ => {
+            let mut stdout = io::stdout().lock();
+            stdout
+                .write_all(&encoded)
+                .and_then(|()| stdout.flush())
+                .map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// Collects `words` keystream words as big-endian bytes
+fn keystream(words: usize, mut generate: impl FnMut() -> u32) -> Vec<u8> {
+    (0..words).flat_map(|_| generate().to_be_bytes()).collect()
+}
+
+/// Runs the statistical test suite
+fn run_stats(
+    alg: Alg,
+    sequences: usize,
+    bits: usize,
+    seed: u64,
+    alpha: f64,
+    output: Option<PathBuf>,
+) -> Result<(), String> {
+    if sequences == 0 || bits == 0 {
+        return Err("empty suite".to_string());
+    }
+    if sequences < zuc::stats::MIN_UNIFORMITY_SEQUENCES {
+        eprintln!(
+            "warning: the uniformity of the p-values needs at least {} sequences, not assessed",
+            zuc::stats::MIN_UNIFORMITY_SEQUENCES
+        );
+    }
+    let algorithm = match alg {
+        Alg::Zuc128 => zuc::stats::Algorithm::Zuc128,
+        Alg::Zuc256 => zuc::stats::Algorithm::Zuc256,
+    };
+    let report = zuc::stats::run_suite(algorithm, sequences, bits, seed, alpha);
+    let out = OutArgs {
+        output,
+        output_format: Format::Raw,
+    };
+    write_output(&out, report.to_string().as_bytes())?;
+    if report.is_pass() {
+        Ok(())
+    } else {
+        Err("statistical tests failed".to_string())
+    }
+}
+
+/// Runs a subcommand
+fn run(cli: Cli) -> Result<(), String> {
+    match cli.command {
+        Command::Eea3 { key, link, io } => {
+            let (data, length) = read_input(&io)?;
+            let obs =
+                zuc::eea3_128_encrypt(link.count, link.bearer, link.direction, &key, length, &data);
+            write_output(&io.out, &obs)
+        }
+        Command::Eia3 { key, link, io } => {
+            let (data, length) = read_input(&io)?;
+            let mac = zuc::eia3_128_generate_mac(
+                link.count,
+                link.bearer,
+                link.direction,
+                &key,
+                length,
+                &data,
+            );
+            write_output(&io.out, &mac.to_be_bytes())
+        }
+        Command::Zuc128Keystream { key, iv, ks } => {
+            let mut zuc = zuc::Zuc128Core::new(&key, &iv);
+            write_output(&ks.out, &keystream(ks.words, || zuc.generate()))
+        }
+        Command::Zuc256Keystream { key, iv, ks } => {
+            let mut zuc = zuc::Zuc256Core::new(&key, &iv);
+            write_output(&ks.out, &keystream(ks.words, || zuc.generate()))
+        }
+        Command::Zuc256Mac {
+            key,
+            iv,
+            tag_bits,
+            io,
+        } => {
+            let (data, length) = read_input(&io)?;
+            let tag = match tag_bits {
+                32 => zuc::zuc256_generate_mac::<u32>(&key, &iv, length, &data)
+                    .to_be_bytes()
+                    .to_vec(),
+                64 => zuc::zuc256_generate_mac::<u64>(&key, &iv, length, &data)
+                    .to_be_bytes()
+                    .to_vec(),
+                _ => zuc::zuc256_generate_mac::<u128>(&key, &iv, length, &data)
+                    .to_be_bytes()
+                    .to_vec(),
+            };
+            write_output(&io.out, &tag)
+        }
+        Command::PcapDecrypt {
+            input,
+            output,
+            report,
+            keys,
+        } => {
+            let capture = fs::read(&input).map_err(|e| format!("{}: {e}", input.display()))?;
+            let keys = zuc::pcap::Keys {
+                nas_enc: keys.nas_enc,
+                nas_int: keys.nas_int,
+                rrc_enc: keys.rrc_enc,
+                rrc_int: keys.rrc_int,
+                up_enc: keys.up_enc,
+                up_int: keys.up_int,
+                nia0: keys.nia0,
+            };
+            let (decrypted, summary) =
+                zuc::pcap::decrypt_pcap(&capture, keys).map_err(|e| e.to_string())?;
+            fs::write(&output, decrypted).map_err(|e| format!("{}: {e}", output.display()))?;
+            let out = OutArgs {
+                output: report,
+                output_format: Format::Raw,
+            };
+            write_output(&out, summary.to_string().as_bytes())
+        }
+        Command::Stats {
+            alg,
+            sequences,
+            bits,
+            seed,
+            alpha,
+            output,
+        } => run_stats(alg, sequences, bits, seed, alpha, output),
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,657 @@
+//! Encrypted file container
+//!
+//! A `.zuc` container is a header followed by a sequence of chunks.
+//! Chunks are encrypted with ZUC-256 and authenticated with the 128-bit ZUC-256 MAC.
+//!
+//! # Format (version 1)
+//!
+//! All integers are big-endian.
+//!
+//! | field        | bytes | value                                         |
+//! |--------------|-------|-----------------------------------------------|
+//! | magic        | 4     | `ZUCF`                                        |
+//! | version      | 1     | 1                                             |
+//! | algorithm    | 1     | 1, ZUC-256 with 128-bit MAC                   |
+//! | chunk size   | 4     | plaintext bytes per chunk, 1 to 2^24          |
+//! | salt         | 16    | KDF salt                                      |
+//! | base IV      | 23    | ZUC-256 IV                                    |
+//!
+//! The header is followed by chunks of `ciphertext || tag`,
+//! where `tag` is 16 bytes. Every chunk but the last carries exactly
+//! `chunk size` bytes of ciphertext, the last chunk carries fewer,
+//! possibly none, and is always present.
+//!
+//! # Keys
+//!
+//! The encryption key and the MAC key are derived from a 256-bit master key
+//! with the [3GPP KDF](crate::kdf::kdf), `FC = 0xff`,
+//! `P0 = "enc"` or `P0 = "mac"`, and `P1` the 49 header bytes,
+//! so the keys are unique to the salt and bound to the whole header.
+//!
+//! # Chunks
+//!
+//! Chunk `i`, counted from zero, uses the IV obtained by XOR-ing the 64-bit
+//! index into the first 8 bytes of the base IV. Its ciphertext is the plaintext
+//! XOR-ed with the ZUC-256 keystream, and its tag is
+//! `zuc256_generate_mac::<u128>` under the MAC key and the chunk IV over
+//! `i (8 bytes) || final (1 byte) || ciphertext`, where `final` is 1 on the last
+//! chunk and 0 otherwise. Reordered chunks fail the index check, a container
+//! truncated on a chunk boundary lacks its final chunk, and a container
+//! truncated within a chunk or extended with data fails the final flag
+//! or the tag check.
+
+use crate::kdf::kdf;
+use crate::zuc256::{indexed_iv, Zuc256};
+use crate::zuc256_mac::zuc256_generate_mac;
+
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use cipher::{KeyIvInit, StreamCipher};
+
+/// Container magic
+pub const MAGIC: [u8; 4] = *b"ZUCF";
+
+/// Container format version
+pub const VERSION: u8 = 1;
+
+/// Algorithm identifier of ZUC-256 with 128-bit MAC
+pub const ALGORITHM_ZUC256: u8 = 1;
+
+/// Header length in bytes
+pub const HEADER_LEN: usize = 49;
+
+/// Tag length in bytes
+pub const TAG_LEN: usize = 16;
+
+/// Default chunk size
+pub const DEFAULT_CHUNK_SIZE: u32 = 64 * 1024;
+
+/// Maximum chunk size
+pub const MAX_CHUNK_SIZE: u32 = 1 << 24;
+
+/// KDF FC value of the container keys
+const FC: u8 = 0xff;
+
+/// Container error
+#[derive(Debug)]
+pub enum ContainerError {
+    /// I/O error of the underlying reader or writer
+    Io(io::Error),
+    /// The magic does not match
+    BadMagic,
+    /// The version is not supported
+    UnsupportedVersion(u8),
+    /// The algorithm is not supported
+    UnsupportedAlgorithm(u8),
+    /// The chunk size is zero or greater than [`MAX_CHUNK_SIZE`]
+    InvalidChunkSize(u32),
+    /// The container ends before its final chunk
+    Truncated,
+    /// Chunk authentication failed
+    Authentication {
+        /// chunk index
+        chunk: u64,
+    },
+}
+
+impl fmt::Display for ContainerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContainerError::Io(e) => write!(f, "I/O error: {e}"),
+            ContainerError::BadMagic => write!(f, "not a zuc container"),
+            ContainerError::UnsupportedVersion(v) => write!(f, "unsupported container version {v}"),
+            ContainerError::UnsupportedAlgorithm(a) => write!(f, "unsupported algorithm {a}"),
+            ContainerError::InvalidChunkSize(n) => write!(f, "invalid chunk size {n}"),
+            ContainerError::Truncated => write!(f, "truncated container"),
+            ContainerError::Authentication { chunk } => {
+                write!(f, "authentication failed (chunk {chunk})")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ContainerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ContainerError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ContainerError {
+    fn from(e: io::Error) -> Self {
+        ContainerError::Io(e)
+    }
+}
+
+impl From<ContainerError> for io::Error {
+    fn from(e: ContainerError) -> Self {
+        match e {
+            ContainerError::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
+
+/// Container header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    /// plaintext bytes per chunk
+    pub chunk_size: u32,
+    /// KDF salt, must be unique per container under the same master key
+    pub salt: [u8; 16],
+    /// base IV
+    pub iv: [u8; 23],
+}
+
+impl Header {
+    /// Creates a header with [`DEFAULT_CHUNK_SIZE`]
+    #[must_use]
+    pub fn new(salt: [u8; 16], iv: [u8; 23]) -> Self {
+        Self {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            salt,
+            iv,
+        }
+    }
+
+    /// Encodes the header
+    #[must_use]
+    pub fn encode(&self) -> [u8; HEADER_LEN] {
+        let mut buf = [0; HEADER_LEN];
+        buf[..4].copy_from_slice(&MAGIC);
+        buf[4] = VERSION;
+        buf[5] = ALGORITHM_ZUC256;
+        buf[6..10].copy_from_slice(&self.chunk_size.to_be_bytes());
+        buf[10..26].copy_from_slice(&self.salt);
+        buf[26..].copy_from_slice(&self.iv);
+        buf
+    }
+
+    /// Decodes a header
+    ///
+    /// # Errors
+    /// + Returns [`ContainerError::BadMagic`] if the magic does not match.
+    /// + Returns [`ContainerError::UnsupportedVersion`] if the version is not 1.
+    /// + Returns [`ContainerError::UnsupportedAlgorithm`] if the algorithm is not ZUC-256.
+    /// + Returns [`ContainerError::InvalidChunkSize`] if the chunk size is out of range.
+    pub fn decode(buf: &[u8; HEADER_LEN]) -> Result<Self, ContainerError> {
+        if buf[..4] != MAGIC {
+            return Err(ContainerError::BadMagic);
+        }
+        if buf[4] != VERSION {
+            return Err(ContainerError::UnsupportedVersion(buf[4]));
+        }
+        if buf[5] != ALGORITHM_ZUC256 {
+            return Err(ContainerError::UnsupportedAlgorithm(buf[5]));
+        }
+        let mut header = Header::new([0; 16], [0; 23]);
+        header.chunk_size = u32::from_be_bytes([buf[6], buf[7], buf[8], buf[9]]);
+        header.salt.copy_from_slice(&buf[10..26]);
+        header.iv.copy_from_slice(&buf[26..]);
+        header.validate()?;
+        Ok(header)
+    }
+
+    /// Checks the chunk size
+    fn validate(&self) -> Result<(), ContainerError> {
+        if self.chunk_size == 0 || self.chunk_size > MAX_CHUNK_SIZE {
+            return Err(ContainerError::InvalidChunkSize(self.chunk_size));
+        }
+        Ok(())
+    }
+}
+
+/// Chunk keys and IV
+struct ChunkCipher {
+    /// encryption key
+    ek: [u8; 32],
+    /// MAC key
+    ik: [u8; 32],
+    /// base IV
+    iv: [u8; 23],
+}
+
+impl ChunkCipher {
+    /// Derives the chunk keys of a container
+    fn new(key: &[u8; 32], header: &Header) -> Self {
+        let encoded = header.encode();
+        Self {
+            ek: kdf(key, FC, &[b"enc", &encoded]),
+            ik: kdf(key, FC, &[b"mac", &encoded]),
+            iv: header.iv,
+        }
+    }
+
+    /// Applies the keystream of chunk `index`
+    fn apply_keystream(&self, index: u64, data: &mut [u8]) {
+        let mut zuc = Zuc256::new(&self.ek.into(), &indexed_iv(&self.iv, index).into());
+        zuc.apply_keystream(data);
+    }
+
+    /// Computes the tag of chunk `index`
+    fn tag(&self, index: u64, last: bool, ciphertext: &[u8]) -> [u8; TAG_LEN] {
+        let mut m = Vec::with_capacity(9 + ciphertext.len());
+        m.extend_from_slice(&index.to_be_bytes());
+        m.push(u8::from(last));
+        m.extend_from_slice(ciphertext);
+        let bitlen = u32::try_from(m.len() * 8).expect("chunk size is bounded");
+        zuc256_generate_mac::<u128>(&self.ik, &indexed_iv(&self.iv, index), bitlen, &m)
+            .to_be_bytes()
+    }
+}
+
+/// Writes an encrypted container
+///
+/// [`ContainerWriter::finish`] must be called to write the final chunk,
+/// a dropped writer leaves a truncated container.
+///
+/// A chunk that fails to be written may have been partially written:
+/// the writer then fails every later call.
+pub struct ContainerWriter<W: Write> {
+    /// underlying writer
+    inner: W,
+    /// chunk cipher
+    cipher: ChunkCipher,
+    /// chunk size
+    chunk_size: usize,
+    /// pending plaintext, shorter than a chunk between calls
+    buf: Vec<u8>,
+    /// ciphertext of the chunk being written
+    scratch: Vec<u8>,
+    /// index of the next chunk
+    index: u64,
+    /// whether a chunk write failed
+    poisoned: bool,
+}
+
+impl<W: Write> ContainerWriter<W> {
+    /// Writes the header and creates a container writer
+    ///
+    /// # Errors
+    /// + Returns [`ContainerError::InvalidChunkSize`] if the chunk size is out of range.
+    /// + Returns [`ContainerError::Io`] if the header cannot be written.
+    ///
+    /// # Panics
+    /// + Panics if the chunk size is greater than `usize::MAX`.
+    pub fn new(mut inner: W, key: &[u8; 32], header: Header) -> Result<Self, ContainerError> {
+        header.validate()?;
+        inner.write_all(&header.encode())?;
+        let chunk_size = usize::try_from(header.chunk_size).expect("chunk size is bounded");
+        Ok(Self {
+            inner,
+            cipher: ChunkCipher::new(key, &header),
+            chunk_size,
+            buf: Vec::with_capacity(chunk_size),
+            scratch: Vec::with_capacity(chunk_size),
+            index: 0,
+            poisoned: false,
+        })
+    }
+
+    /// Fails if a chunk write failed before
+    fn check_poisoned(&self) -> io::Result<()> {
+        if self.poisoned {
+            return Err(io::Error::other(
+                "container writer failed to write a chunk before",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Encrypts and writes the pending chunk
+    ///
+    /// The plaintext is encrypted into a separate buffer and the writer is
+    /// poisoned on failure, so a retry cannot write a chunk twice or in plaintext.
+    fn write_chunk(&mut self, last: bool) -> io::Result<()> {
+        self.check_poisoned()?;
+        self.scratch.clear();
+        self.scratch.extend_from_slice(&self.buf);
+        self.cipher.apply_keystream(self.index, &mut self.scratch);
+        let tag = self.cipher.tag(self.index, last, &self.scratch);
+        let written = self
+            .inner
+            .write_all(&self.scratch)
+            .and_then(|()| self.inner.write_all(&tag));
+        if let Err(e) = written {
+            self.poisoned = true;
+            return Err(e);
+        }
+        self.buf.clear();
+        self.index += 1;
+        Ok(())
+    }
+
+    /// Writes the final chunk and returns the underlying writer
+    ///
+    /// # Errors
+    /// + Returns an I/O error if the final chunk cannot be written, or a chunk write failed before.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_chunk(true)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for ContainerWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.check_poisoned()?;
+        let n = data.len().min(self.chunk_size - self.buf.len());
+        self.buf.extend_from_slice(&data[..n]);
+        if self.buf.len() == self.chunk_size {
+            self.write_chunk(false)?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reads and verifies an encrypted container
+///
+/// Plaintext is returned only after its chunk has been verified.
+/// Errors are returned as [`io::ErrorKind::InvalidData`] wrapping a [`ContainerError`].
+pub struct ContainerReader<R: Read> {
+    /// underlying reader
+    inner: R,
+    /// container header
+    header: Header,
+    /// chunk cipher
+    cipher: ChunkCipher,
+    /// chunk size
+    chunk_size: usize,
+    /// verified plaintext of the current chunk
+    buf: Vec<u8>,
+    /// read position in `buf`
+    pos: usize,
+    /// index of the next chunk
+    index: u64,
+    /// whether the final chunk has been read
+    done: bool,
+}
+
+impl<R: Read> ContainerReader<R> {
+    /// Reads the header and creates a container reader
+    ///
+    /// # Errors
+    /// + Returns [`ContainerError::Truncated`] if the header is incomplete.
+    /// + Returns the errors of [`Header::decode`].
+    ///
+    /// # Panics
+    /// + Panics if the chunk size is greater than `usize::MAX`.
+    pub fn new(mut inner: R, key: &[u8; 32]) -> Result<Self, ContainerError> {
+        let mut encoded = [0; HEADER_LEN];
+        if read_full(&mut inner, &mut encoded)? < HEADER_LEN {
+            return Err(ContainerError::Truncated);
+        }
+        let header = Header::decode(&encoded)?;
+        let chunk_size = usize::try_from(header.chunk_size).expect("chunk size is bounded");
+        Ok(Self {
+            inner,
+            header,
+            cipher: ChunkCipher::new(key, &header),
+            chunk_size,
+            buf: Vec::with_capacity(chunk_size + TAG_LEN),
+            pos: 0,
+            index: 0,
+            done: false,
+        })
+    }
+
+    /// Returns the container header
+    #[must_use]
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Returns the underlying reader
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Reads, verifies and decrypts the next chunk
+    fn read_chunk(&mut self) -> Result<(), ContainerError> {
+        self.buf.resize(self.chunk_size + TAG_LEN, 0);
+        let n = read_full(&mut self.inner, &mut self.buf)?;
+        if n < TAG_LEN {
+            return Err(ContainerError::Truncated);
+        }
+        let last = n < self.buf.len();
+        let len = n - TAG_LEN;
+        let tag = self.cipher.tag(self.index, last, &self.buf[..len]);
+        if tag[..] != self.buf[len..n] {
+            return Err(ContainerError::Authentication { chunk: self.index });
+        }
+        self.buf.truncate(len);
+        self.cipher.apply_keystream(self.index, &mut self.buf);
+        self.pos = 0;
+        self.index += 1;
+        self.done = last;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for ContainerReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.buf.len() {
+            if self.done || out.is_empty() {
+                return Ok(0);
+            }
+            if let Err(e) = self.read_chunk() {
+                self.buf.clear();
+                self.pos = 0;
+                return Err(e.into());
+            }
+        }
+        let n = out.len().min(self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Reads until `buf` is full or the reader is exhausted, returns the number of bytes read
+fn read_full(r: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match r.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(k) => n += k,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static KEY: [u8; 32] = [0x42; 32];
+
+    fn header(chunk_size: u32) -> Header {
+        Header {
+            chunk_size,
+            salt: [0x5a; 16],
+            iv: [0xa5; 23],
+        }
+    }
+
+    fn seal(data: &[u8], chunk_size: u32) -> Vec<u8> {
+        let mut w = ContainerWriter::new(Vec::new(), &KEY, header(chunk_size)).unwrap();
+        w.write_all(data).unwrap();
+        w.finish().unwrap()
+    }
+
+    fn open(container: &[u8]) -> Result<Vec<u8>, ContainerError> {
+        let mut r = ContainerReader::new(container, &KEY)?;
+        let mut out = Vec::new();
+        r.read_to_end(&mut out).map_err(|e| {
+            *e.into_inner()
+                .expect("container error")
+                .downcast::<ContainerError>()
+                .expect("container error")
+        })?;
+        Ok(out)
+    }
+
+    fn chunk_range(i: usize, chunk_size: usize) -> std::ops::Range<usize> {
+        let start = HEADER_LEN + i * (chunk_size + TAG_LEN);
+        start..start + chunk_size + TAG_LEN
+    }
+
+    #[test]
+    fn roundtrip() {
+        let data: Vec<u8> = (0..=u8::MAX).cycle().take(1000).collect();
+        for (len, chunk_size) in [
+            (0, 16),
+            (1, 16),
+            (16, 16),
+            (17, 16),
+            (1000, 64),
+            (1000, 1 << 16),
+        ] {
+            let container = seal(&data[..len], chunk_size);
+            let chunks = len / chunk_size as usize + 1;
+            assert_eq!(container.len(), HEADER_LEN + len + chunks * TAG_LEN);
+            if len > 0 {
+                assert_ne!(&container[HEADER_LEN..HEADER_LEN + len], &data[..len]);
+            }
+            assert_eq!(open(&container).unwrap(), &data[..len]);
+        }
+
+        let empty = seal(b"", 16);
+        let reader = ContainerReader::new(&empty[..], &KEY).unwrap();
+        assert_eq!(*reader.header(), header(16));
+    }
+
+    #[test]
+    fn corruption() {
+        let data = [0x33; 100];
+        let container = seal(&data, 32);
+        // chunks: 32, 32, 32, 4 (final)
+
+        // flipped ciphertext and tag bits
+        for pos in [HEADER_LEN, HEADER_LEN + 40, container.len() - 1] {
+            let mut c = container.clone();
+            c[pos] ^= 1;
+            assert!(matches!(
+                open(&c),
+                Err(ContainerError::Authentication { .. })
+            ));
+        }
+
+        // header
+        let mut c = container.clone();
+        c[0] = b'X';
+        assert!(matches!(open(&c), Err(ContainerError::BadMagic)));
+        let mut c = container.clone();
+        c[4] = 2;
+        assert!(matches!(
+            open(&c),
+            Err(ContainerError::UnsupportedVersion(2))
+        ));
+        let mut c = container.clone();
+        c[5] = 0;
+        assert!(matches!(
+            open(&c),
+            Err(ContainerError::UnsupportedAlgorithm(0))
+        ));
+        let mut c = container.clone();
+        c[6..10].copy_from_slice(&0u32.to_be_bytes());
+        assert!(matches!(open(&c), Err(ContainerError::InvalidChunkSize(0))));
+        let mut c = container.clone();
+        c[20] ^= 1;
+        assert!(matches!(
+            open(&c),
+            Err(ContainerError::Authentication { chunk: 0 })
+        ));
+        assert!(matches!(
+            open(&container[..10]),
+            Err(ContainerError::Truncated)
+        ));
+
+        // reordered chunks
+        let mut c = container.clone();
+        let first = c[chunk_range(0, 32)].to_vec();
+        c.copy_within(chunk_range(1, 32), chunk_range(0, 32).start);
+        c[chunk_range(1, 32)].copy_from_slice(&first);
+        assert!(matches!(
+            open(&c),
+            Err(ContainerError::Authentication { chunk: 0 })
+        ));
+
+        // truncated on a chunk boundary and within a chunk
+        let end = chunk_range(2, 32).end;
+        assert!(matches!(
+            open(&container[..end]),
+            Err(ContainerError::Truncated)
+        ));
+        assert!(matches!(
+            open(&container[..end - 5]),
+            Err(ContainerError::Authentication { chunk: 2 })
+        ));
+        assert!(matches!(
+            open(&container[..container.len() - 1]),
+            Err(ContainerError::Authentication { chunk: 3 })
+        ));
+
+        // appended data
+        for extra in [1, 48, 100] {
+            let mut c = container.clone();
+            c.resize(c.len() + extra, 0);
+            assert!(matches!(
+                open(&c),
+                Err(ContainerError::Authentication { chunk: 3 })
+            ));
+        }
+
+        // wrong key
+        let mut reader = ContainerReader::new(&container[..], &[0; 32]).unwrap();
+        let mut out = Vec::new();
+        let err = reader.read_to_end(&mut out).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn failed_write() {
+        /// Fails the first write of a chunk body with `WouldBlock`
+        struct Flaky {
+            out: Vec<u8>,
+            failed: bool,
+        }
+
+        impl Write for Flaky {
+            fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+                if !self.failed && self.out.len() > HEADER_LEN - 1 {
+                    self.failed = true;
+                    return Err(io::ErrorKind::WouldBlock.into());
+                }
+                self.out.extend_from_slice(data);
+                Ok(data.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let flaky = Flaky {
+            out: Vec::new(),
+            failed: false,
+        };
+        let data = b"SECRET-PLAINTEXT";
+        let mut w = ContainerWriter::new(flaky, &KEY, header(16)).unwrap();
+        let err = w.write(data).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(w.write(data).unwrap_err().kind(), io::ErrorKind::Other);
+        assert_eq!(w.inner.out.len(), HEADER_LEN);
+        assert!(w.finish().is_err());
+    }
+}
@@ -0,0 +1,27 @@
+//! Transmission direction
+
+/// Transmission direction, the 1-bit `DIRECTION` input of 128-EEA3 and 128-EIA3
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// From the UE to the network
+    Uplink = 0,
+    /// From the network to the UE
+    Downlink = 1,
+}
+
+impl Direction {
+    /// Returns the `DIRECTION` bit
+    #[must_use]
+    pub fn bit(self) -> u8 {
+        self as u8
+    }
+
+    /// Returns the opposite direction
+    #[must_use]
+    pub fn reverse(self) -> Self {
+        match self {
+            Direction::Uplink => Direction::Downlink,
+            Direction::Downlink => Direction::Uplink,
+        }
+    }
+}
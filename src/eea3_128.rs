@@ -24,9 +24,10 @@ pub fn zuc128_xor_encrypt(ck: &[u8; 16], iv: &[u8; 16], length: u32, ibs: &[u8])
 
     let mut zuc = Zuc128Core::new(ck, iv);
     let mut res = ibs.to_vec();
+    let mut k: [u8; 4];
 
     for i in 0..ibs.len() / 4 {
-        let k = zuc.generate().to_be_bytes();
+        k = zuc.generate().to_be_bytes();
         for j in 0..4 {
             res[i * 4 + j] ^= k[j];
         }
@@ -34,12 +35,15 @@ pub fn zuc128_xor_encrypt(ck: &[u8; 16], iv: &[u8; 16], length: u32, ibs: &[u8])
 
     {
         let i = ibs.len() / 4 * 4;
-        let k = zuc.generate().to_be_bytes();
+        k = zuc.generate().to_be_bytes();
         for j in 0..ibs.len() % 4 {
             res[i + j] ^= k[j];
         }
     }
 
+    #[cfg(feature = "zeroize")]
+    zeroize::Zeroize::zeroize(&mut k);
+
     if bitlen % 8 != 0 {
         res[bitlen / 8] &= 0xFF << (8 - bitlen % 8);
     }
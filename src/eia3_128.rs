@@ -67,13 +67,11 @@ pub fn zuc128_generate_mac(ik: &[u8; 16], iv: &[u8; 16], length: u32, m: &[u8])
     } else {
         let i = bitlen / 32 * 4;
 
-        let mut bits = match (bitlen % 32) / 8 {
-            0 => u32::from_be_bytes([m[i], 0, 0, 0]),
-            1 => u32::from_be_bytes([m[i], m[i + 1], 0, 0]),
-            2 => u32::from_be_bytes([m[i], m[i + 1], m[i + 2], 0]),
-            3 => u32::from_be_bytes([m[i], m[i + 1], m[i + 2], m[i + 3]]),
-            _ => unreachable!(),
-        };
+        let n = (bitlen % 32).div_ceil(8);
+
+        let mut buf = [0_u8; 4];
+        buf[..n].copy_from_slice(&m[i..i + n]);
+        let mut bits = u32::from_be_bytes(buf);
 
         for _ in 0..(bitlen % 32) {
             eia3_xor_t(&mut bits, &mut key, &mut t);
@@ -83,6 +81,9 @@ pub fn zuc128_generate_mac(ik: &[u8; 16], iv: &[u8; 16], length: u32, m: &[u8])
         t ^= zuc.generate();
     }
 
+    #[cfg(feature = "zeroize")]
+    zeroize::Zeroize::zeroize(&mut key);
+
     t
 }
 
@@ -112,6 +113,12 @@ pub fn eia3_128_generate_mac(
     length: u32,
     m: &[u8],
 ) -> u32 {
+    let iv = eia3_iv(count, bearer, direction);
+    zuc128_generate_mac(ik, &iv, length, m)
+}
+
+/// Builds the 128-EIA3 initial vector
+fn eia3_iv(count: u32, bearer: u8, direction: u8) -> [u8; 16] {
     let mut iv: [u8; 16] = [0; 16];
     let count: [u8; 4] = count.to_be_bytes();
     let bearer = bearer & 0x1f;
@@ -127,9 +134,141 @@ pub fn eia3_128_generate_mac(
     iv[11] = iv[3];
     iv[12] = iv[4];
     iv[14] = iv[6] ^ (direction << 7);
-    zuc128_generate_mac(ik, &iv, length, m)
+    iv
+}
+
+/// Incremental ZUC128 MAC generation
+///
+/// Computes the same MAC as [`zuc128_generate_mac`] over a byte-aligned message
+/// fed in pieces.
+#[derive(Clone)]
+pub struct Zuc128Mac {
+    /// keystream generator
+    zuc: Zuc128Core,
+    /// accumulated tag
+    t: u32,
+    /// current key window
+    key: u64,
+    /// pending bytes of the current word
+    buf: [u8; 4],
+    /// number of pending bytes
+    buf_len: usize,
+}
+
+impl Zuc128Mac {
+    /// Creates an incremental MAC generator
+    ///
+    /// Input:
+    /// - `ik`:         128bit  integrity key
+    /// - `iv`:         128bit  initial vector
+    #[must_use]
+    pub fn new(ik: &[u8; 16], iv: &[u8; 16]) -> Self {
+        let mut zuc = Zuc128Core::new(ik, iv);
+        let key = {
+            let k0 = zuc.generate();
+            let k1 = zuc.generate();
+            (u64::from(k0) << 32) | u64::from(k1)
+        };
+        Self {
+            zuc,
+            t: 0,
+            key,
+            buf: [0; 4],
+            buf_len: 0,
+        }
+    }
+
+    /// Creates an incremental 128-EIA3 MAC generator
+    ///
+    /// Input:
+    /// - `count`:        32bit   counter
+    /// - `bearer`:       5bit    carrier layer identification
+    /// - `direction`:    1bit    transmission direction identification
+    /// - `ik`:           128bit  integrity key
+    #[must_use]
+    pub fn eia3(count: u32, bearer: u8, direction: u8, ik: &[u8; 16]) -> Self {
+        Self::new(ik, &eia3_iv(count, bearer, direction))
+    }
+
+    /// Processes one full word
+    fn process_word(&mut self, word: [u8; 4]) {
+        let mut bits = u32::from_be_bytes(word);
+        for _ in 0..32 {
+            eia3_xor_t(&mut bits, &mut self.key, &mut self.t);
+        }
+        self.key |= u64::from(self.zuc.generate());
+    }
+
+    /// Feeds message bytes
+    pub fn update(&mut self, mut data: &[u8]) {
+        if self.buf_len > 0 {
+            let n = data.len().min(4 - self.buf_len);
+            self.buf[self.buf_len..self.buf_len + n].copy_from_slice(&data[..n]);
+            self.buf_len += n;
+            data = &data[n..];
+            if self.buf_len < 4 {
+                return;
+            }
+            self.process_word(self.buf);
+            self.buf_len = 0;
+        }
+        let (words, rest) = data.as_chunks_::<4>();
+        for word in words {
+            self.process_word(*word);
+        }
+        self.buf[..rest.len()].copy_from_slice(rest);
+        self.buf_len = rest.len();
+    }
+
+    /// Returns the MAC of the bytes fed so far
+    #[allow(clippy::cast_possible_truncation)]
+    #[must_use]
+    pub fn finish(mut self) -> u32 {
+        if self.buf_len == 0 {
+            self.t ^= (self.key >> 32) as u32;
+            self.t ^= self.key as u32;
+        } else {
+            self.buf[self.buf_len..].fill(0);
+            let mut bits = u32::from_be_bytes(self.buf);
+            for _ in 0..self.buf_len * 8 {
+                eia3_xor_t(&mut bits, &mut self.key, &mut self.t);
+            }
+            self.t ^= (self.key >> 32) as u32;
+            self.t ^= self.zuc.generate();
+        }
+        self.t
+    }
+
+    /// Keystream generator, for fault injection
+    #[cfg(test)]
+    pub(crate) fn zuc_mut(&mut self) -> &mut Zuc128Core {
+        &mut self.zuc
+    }
+}
+
+impl core::fmt::Debug for Zuc128Mac {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("Zuc128Mac { ... }")
+    }
 }
 
+/// Wipes the keystream window and the pending bytes
+#[cfg(feature = "zeroize")]
+impl Drop for Zuc128Mac {
+    fn drop(&mut self) {
+        use zeroize::Zeroize;
+
+        self.t.zeroize();
+        self.key.zeroize();
+        self.buf.zeroize();
+    }
+}
+
+/// The state is wiped on drop
+#[cfg(feature = "zeroize")]
+#[cfg_attr(docsrs, doc(cfg(feature = "zeroize")))]
+impl zeroize::ZeroizeOnDrop for Zuc128Mac {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -284,6 +423,18 @@ mod tests {
         assert_eq!(mac, 0x2592_99ab); // generated from GmSSL
     }
 
+    #[test]
+    fn byte_aligned_tail() {
+        let x = &EXAMPLE3;
+        for len in [1_usize, 2, 3, 5, 6, 7] {
+            let length = u32::try_from(len * 8).unwrap();
+            let padded = eia3_128_generate_mac(x.count, x.bearer, x.direction, &x.ik, length, x.m);
+            let exact =
+                eia3_128_generate_mac(x.count, x.bearer, x.direction, &x.ik, length, &x.m[..len]);
+            assert_eq!(exact, padded);
+        }
+    }
+
     #[test]
     fn zero_bitlen() {
         let x = &EXAMPLE5;
@@ -291,4 +442,19 @@ mod tests {
         let mac = eia3_128_generate_mac(x.count, x.bearer, x.direction, &x.ik, length, x.m);
         assert_eq!(mac, 0x0787_bab1); // generated from GmSSL
     }
+
+    #[test]
+    fn incremental() {
+        let x = &EXAMPLE5;
+        for len in [0, 1, 3, 4, 5, 8, 100, x.m.len()] {
+            let m = &x.m[..len];
+            let length = u32::try_from(len * 8).unwrap();
+            let expected = eia3_128_generate_mac(x.count, x.bearer, x.direction, &x.ik, length, m);
+            for step in [1, 2, 3, 4, 7, 64] {
+                let mut mac = Zuc128Mac::eia3(x.count, x.bearer, x.direction, &x.ik);
+                m.chunks(step).for_each(|piece| mac.update(piece));
+                assert_eq!(mac.finish(), expected);
+            }
+        }
+    }
 }
@@ -0,0 +1,325 @@
+//! Fault-attack countermeasure by redundant computation
+//!
+//! [`FaultResistant`] runs two instances set up independently from the same key and IV
+//! and compares their outputs word by word. A fault injected into one instance,
+//! in its LFSR or in the registers of the F function, makes the outputs differ:
+//! the wrapper then returns [`FaultDetected`] instead of keystream or a tag,
+//! and keeps failing afterwards.
+//!
+//! The countermeasure doubles the cost and does not detect a fault hitting
+//! both instances the same way.
+
+use crate::io::StreamMac;
+use crate::zuc256_mac::private::MacWord;
+use crate::{Zuc128Core, Zuc128Mac, Zuc256Core, Zuc256Mac};
+
+use std::fmt;
+
+/// The redundant computations disagreed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FaultDetected;
+
+impl fmt::Display for FaultDetected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "fault detected: redundant computations disagree")
+    }
+}
+
+impl std::error::Error for FaultDetected {}
+
+/// Two independent instances whose outputs are compared
+#[derive(Debug, Clone)]
+pub struct FaultResistant<C> {
+    /// instance whose output is returned
+    primary: C,
+    /// instance checking the primary one
+    shadow: C,
+    /// whether a fault has been detected
+    faulted: bool,
+    /// last keystream word of [`FaultResistant::apply_keystream`]
+    block: [u8; 4],
+    /// number of bytes of `block` already used
+    block_pos: usize,
+}
+
+impl<C> FaultResistant<C> {
+    /// Wraps two instances
+    fn with(primary: C, shadow: C) -> Self {
+        Self {
+            primary,
+            shadow,
+            faulted: false,
+            block: [0; 4],
+            block_pos: 4,
+        }
+    }
+
+    /// Returns whether a fault has been detected
+    #[must_use]
+    pub fn is_faulted(&self) -> bool {
+        self.faulted
+    }
+}
+
+impl FaultResistant<Zuc128Core> {
+    /// Creates a fault-resistant ZUC128 keystream generator
+    #[must_use]
+    pub fn new(key: &[u8; 16], iv: &[u8; 16]) -> Self {
+        Self::with(Zuc128Core::new(key, iv), Zuc128Core::new(key, iv))
+    }
+}
+
+impl FaultResistant<Zuc256Core> {
+    /// Creates a fault-resistant ZUC256 keystream generator
+    #[must_use]
+    pub fn new(k: &[u8; 32], iv: &[u8; 23]) -> Self {
+        Self::with(Zuc256Core::new(k, iv), Zuc256Core::new(k, iv))
+    }
+}
+
+impl<C: Iterator<Item = u32>> FaultResistant<C> {
+    /// Generates the next 32-bit word in the keystream
+    ///
+    /// The unused bytes of the last word of [`FaultResistant::apply_keystream`] are discarded.
+    ///
+    /// # Errors
+    /// + Returns [`FaultDetected`] if the instances disagree, or disagreed before.
+    pub fn generate(&mut self) -> Result<u32, FaultDetected> {
+        self.block_pos = 4;
+        let primary = self.primary.next();
+        let shadow = self.shadow.next();
+        if self.faulted || primary != shadow {
+            self.faulted = true;
+            return Err(FaultDetected);
+        }
+        primary.ok_or(FaultDetected)
+    }
+
+    /// XORs `data` with the next keystream bytes
+    ///
+    /// The unused bytes of the last word are kept for the next call,
+    /// so the output matches [`Zuc128`](crate::Zuc128) and [`Zuc256`](crate::Zuc256)
+    /// however the data is split.
+    /// `data` is left unchanged if a fault is detected.
+    ///
+    /// # Errors
+    /// + Returns [`FaultDetected`] if the instances disagree, or disagreed before.
+    pub fn apply_keystream(&mut self, data: &mut [u8]) -> Result<(), FaultDetected> {
+        if self.faulted {
+            return Err(FaultDetected);
+        }
+        let (block, block_pos) = (self.block, self.block_pos);
+        let buffered = data.len().min(4 - block_pos);
+        let keystream = (0..(data.len() - buffered).div_ceil(4))
+            .map(|_| self.generate().map(u32::to_be_bytes))
+            .collect::<Result<Vec<_>, _>>()?;
+        let bytes = block[block_pos..block_pos + buffered]
+            .iter()
+            .chain(keystream.iter().flatten());
+        for (byte, k) in data.iter_mut().zip(bytes) {
+            *byte ^= k;
+        }
+        match keystream.last() {
+            Some(&last) => {
+                self.block = last;
+                self.block_pos = 4 - (keystream.len() * 4 + buffered - data.len());
+            }
+            None => self.block_pos = block_pos + buffered,
+        }
+        Ok(())
+    }
+}
+
+impl FaultResistant<Zuc128Mac> {
+    /// Creates a fault-resistant incremental MAC generator
+    ///
+    /// Input:
+    /// - `ik`:         128bit  integrity key
+    /// - `iv`:         128bit  initial vector
+    #[must_use]
+    pub fn new(ik: &[u8; 16], iv: &[u8; 16]) -> Self {
+        Self::with(Zuc128Mac::new(ik, iv), Zuc128Mac::new(ik, iv))
+    }
+
+    /// Creates a fault-resistant incremental 128-EIA3 MAC generator
+    ///
+    /// Input:
+    /// - `count`:        32bit   counter
+    /// - `bearer`:       5bit    carrier layer identification
+    /// - `direction`:    1bit    transmission direction identification
+    /// - `ik`:           128bit  integrity key
+    #[must_use]
+    pub fn eia3(count: u32, bearer: u8, direction: u8, ik: &[u8; 16]) -> Self {
+        Self::with(
+            Zuc128Mac::eia3(count, bearer, direction, ik),
+            Zuc128Mac::eia3(count, bearer, direction, ik),
+        )
+    }
+}
+
+impl<T: MacWord> FaultResistant<Zuc256Mac<T>> {
+    /// Creates a fault-resistant incremental MAC generator
+    ///
+    /// Input:
+    /// - `ik`:         256bit          integrity key
+    /// - `iv`:         184bit          initial vector
+    #[must_use]
+    pub fn new(ik: &[u8; 32], iv: &[u8; 23]) -> Self {
+        Self::with(Zuc256Mac::new(ik, iv), Zuc256Mac::new(ik, iv))
+    }
+}
+
+impl<M> FaultResistant<M>
+where
+    M: StreamMac,
+    M::Tag: PartialEq,
+{
+    /// Feeds message bytes
+    pub fn update(&mut self, data: &[u8]) {
+        self.primary.update(data);
+        self.shadow.update(data);
+    }
+
+    /// Returns the MAC of the bytes fed so far
+    ///
+    /// # Errors
+    /// + Returns [`FaultDetected`] if the instances disagree.
+    pub fn finish(self) -> Result<M::Tag, FaultDetected> {
+        let primary = self.primary.finish();
+        let shadow = self.shadow.finish();
+        if self.faulted || primary != shadow {
+            return Err(FaultDetected);
+        }
+        Ok(primary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Generates words until a fault is detected, returning how many were produced
+    fn words_until_fault<C: Iterator<Item = u32>>(fr: &mut FaultResistant<C>) -> usize {
+        let n = (0..64).take_while(|_| fr.generate().is_ok()).count();
+        assert!(n < 64, "fault not detected");
+        assert!(fr.is_faulted());
+        assert_eq!(fr.generate(), Err(FaultDetected));
+        n
+    }
+
+    #[test]
+    fn no_fault() {
+        let (k, iv) = ([0x3d; 16], [0x84; 16]);
+        let mut fr = FaultResistant::<Zuc128Core>::new(&k, &iv);
+        let mut zuc = Zuc128Core::new(&k, &iv);
+        for _ in 0..100 {
+            assert_eq!(fr.generate(), Ok(zuc.generate()));
+        }
+
+        let (k, iv) = ([0x3d; 32], [0x84; 23]);
+        let mut fr = FaultResistant::<Zuc256Core>::new(&k, &iv);
+        let mut data = [0u8; 31];
+        fr.apply_keystream(&mut data).unwrap();
+        let mut zuc = Zuc256Core::new(&k, &iv);
+        let expected: Vec<u8> = (0..8).flat_map(|_| zuc.generate().to_be_bytes()).collect();
+        assert_eq!(data[..], expected[..31]);
+        assert!(!fr.is_faulted());
+    }
+
+    #[test]
+    fn split_keystream() {
+        use cipher::{KeyIvInit, StreamCipher};
+
+        let (k, iv) = ([0x3d; 16], [0x84; 16]);
+        let mut expected = [0u8; 100];
+        crate::Zuc128::new(&k.into(), &iv.into()).apply_keystream(&mut expected);
+
+        let mut fr = FaultResistant::<Zuc128Core>::new(&k, &iv);
+        let mut whole = [0u8; 100];
+        fr.apply_keystream(&mut whole).unwrap();
+        assert_eq!(whole, expected);
+
+        let mut fr = FaultResistant::<Zuc128Core>::new(&k, &iv);
+        let mut split = [0u8; 100];
+        let mut start = 0;
+        for len in [1, 0, 2, 5, 3, 3, 4, 13, 69] {
+            fr.apply_keystream(&mut split[start..start + len]).unwrap();
+            start += len;
+        }
+        assert_eq!(start, 100);
+        assert_eq!(split, expected);
+    }
+
+    #[test]
+    fn state_faults() {
+        for cell in 0..16 {
+            for bit in [0, 13, 30] {
+                let mut fr = FaultResistant::<Zuc128Core>::new(&[1; 16], &[2; 16]);
+                let _ = fr.generate();
+                fr.shadow.core_mut().s[cell] ^= 1 << bit;
+                words_until_fault(&mut fr);
+
+                let mut fr = FaultResistant::<Zuc256Core>::new(&[1; 32], &[2; 23]);
+                fr.primary.core_mut().s[cell] ^= 1 << bit;
+                words_until_fault(&mut fr);
+            }
+        }
+
+        for bit in [0, 17, 31] {
+            let mut fr = FaultResistant::<Zuc128Core>::new(&[1; 16], &[2; 16]);
+            fr.primary.core_mut().r1 ^= 1 << bit;
+            assert_eq!(words_until_fault(&mut fr), 0);
+
+            let mut fr = FaultResistant::<Zuc256Core>::new(&[1; 32], &[2; 23]);
+            fr.shadow.core_mut().r2 ^= 1 << bit;
+            assert_eq!(words_until_fault(&mut fr), 0);
+        }
+    }
+
+    #[test]
+    fn keystream_withheld() {
+        let mut fr = FaultResistant::<Zuc128Core>::new(&[1; 16], &[2; 16]);
+        fr.shadow.core_mut().r2 ^= 1;
+        let mut data = [0x55; 10];
+        assert_eq!(fr.apply_keystream(&mut data), Err(FaultDetected));
+        assert_eq!(data, [0x55; 10]);
+
+        // a fault detected while keystream bytes are still buffered
+        let mut fr = FaultResistant::<Zuc128Core>::new(&[1; 16], &[2; 16]);
+        let mut data = [0x55; 1];
+        fr.apply_keystream(&mut data).unwrap();
+        fr.shadow.core_mut().r2 ^= 1;
+        fr.faulted = true;
+        let mut data = [0x55; 1];
+        assert_eq!(fr.apply_keystream(&mut data), Err(FaultDetected));
+        assert_eq!(data, [0x55; 1]);
+        assert_eq!(fr.apply_keystream(&mut []), Err(FaultDetected));
+    }
+
+    #[test]
+    fn mac_faults() {
+        let (ik, m) = ([7; 16], [0x11; 100]);
+        let mac = FaultResistant::<Zuc128Mac>::eia3(5, 3, 1, &ik);
+        let mut clean = mac.clone();
+        clean.update(&m);
+        let expected = crate::eia3_128_generate_mac(5, 3, 1, &ik, 800, &m);
+        assert_eq!(clean.finish(), Ok(expected));
+
+        let mut faulty = mac;
+        faulty.update(&m[..40]);
+        faulty.shadow.zuc_mut().core_mut().s[9] ^= 1 << 20;
+        faulty.update(&m[40..]);
+        assert_eq!(faulty.finish(), Err(FaultDetected));
+
+        let (ik, iv) = ([7; 32], [9; 23]);
+        let mut clean = FaultResistant::<Zuc256Mac<u64>>::new(&ik, &iv);
+        clean.update(&m);
+        let expected = crate::zuc256_generate_mac::<u64>(&ik, &iv, 800, &m);
+        assert_eq!(clean.finish(), Ok(expected));
+
+        let mut faulty = FaultResistant::<Zuc256Mac<u128>>::new(&ik, &iv);
+        faulty.primary.zuc_mut().core_mut().r1 ^= 1;
+        faulty.update(&m);
+        assert_eq!(faulty.finish(), Err(FaultDetected));
+    }
+}
@@ -0,0 +1,316 @@
+//! I/O adapters applying ZUC keystreams
+//!
+//! [`ZucReader`] and [`ZucWriter`] XOR the data passing through them with the
+//! keystream of any [`StreamCipher`], such as [`Zuc128`](crate::Zuc128) or
+//! [`Zuc256`](crate::Zuc256). Both can feed the ciphertext to a [`StreamMac`],
+//! whose tag is returned by `finish`.
+
+use crate::eia3_128::Zuc128Mac;
+use crate::zuc256_mac::private::MacWord;
+use crate::zuc256_mac::Zuc256Mac;
+
+use std::io::{self, BufRead, Read, Write};
+
+use cipher::StreamCipher;
+
+/// Buffer size of the adapters
+const BUF_SIZE: usize = 8 * 1024;
+
+/// Incremental MAC over a byte stream
+pub trait StreamMac {
+    /// MAC type
+    type Tag;
+
+    /// Feeds message bytes
+    fn update(&mut self, data: &[u8]);
+
+    /// Returns the MAC of the bytes fed so far
+    fn finish(self) -> Self::Tag;
+}
+
+/// No MAC
+impl StreamMac for () {
+    type Tag = ();
+
+    fn update(&mut self, _: &[u8]) {}
+
+    fn finish(self) {}
+}
+
+impl StreamMac for Zuc128Mac {
+    type Tag = u32;
+
+    fn update(&mut self, data: &[u8]) {
+        Zuc128Mac::update(self, data);
+    }
+
+    fn finish(self) -> u32 {
+        Zuc128Mac::finish(self)
+    }
+}
+
+impl<T: MacWord> StreamMac for Zuc256Mac<T> {
+    type Tag = T;
+
+    fn update(&mut self, data: &[u8]) {
+        Zuc256Mac::update(self, data);
+    }
+
+    fn finish(self) -> T {
+        Zuc256Mac::finish(self)
+    }
+}
+
+/// Reader applying a keystream to the data read from an underlying reader
+///
+/// The MAC covers the data read from the underlying reader, before the keystream is applied.
+pub struct ZucReader<R, C, M = ()> {
+    /// underlying reader
+    inner: R,
+    /// stream cipher
+    cipher: C,
+    /// MAC over the input
+    mac: M,
+    /// processed data
+    buf: Box<[u8]>,
+    /// read position in `buf`
+    pos: usize,
+    /// end of the processed data in `buf`
+    filled: usize,
+}
+
+impl<R: Read, C: StreamCipher> ZucReader<R, C> {
+    /// Creates a reader without MAC
+    pub fn new(inner: R, cipher: C) -> Self {
+        Self::with_mac(inner, cipher, ())
+    }
+}
+
+impl<R: Read, C: StreamCipher, M: StreamMac> ZucReader<R, C, M> {
+    /// Creates a reader computing a MAC over its input
+    pub fn with_mac(inner: R, cipher: C, mac: M) -> Self {
+        Self {
+            inner,
+            cipher,
+            mac,
+            buf: vec![0; BUF_SIZE].into_boxed_slice(),
+            pos: 0,
+            filled: 0,
+        }
+    }
+
+    /// Returns a reference to the underlying reader
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns the underlying reader and the MAC of all data read from it
+    ///
+    /// Data buffered but not yet consumed is covered by the MAC.
+    pub fn finish(self) -> (R, M::Tag) {
+        (self.inner, self.mac.finish())
+    }
+
+    /// Reads from the underlying reader and processes the data in place
+    fn read_inner(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(out)?;
+        self.mac.update(&out[..n]);
+        self.cipher.apply_keystream(&mut out[..n]);
+        Ok(n)
+    }
+}
+
+impl<R: Read, C: StreamCipher, M: StreamMac> Read for ZucReader<R, C, M> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.filled && out.len() >= self.buf.len() {
+            return self.read_inner(out);
+        }
+        let available = self.fill_buf()?;
+        let n = available.len().min(out.len());
+        out[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: Read, C: StreamCipher, M: StreamMac> BufRead for ZucReader<R, C, M> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.filled {
+            let mut buf = std::mem::take(&mut self.buf);
+            let ret = self.read_inner(&mut buf);
+            self.buf = buf;
+            self.filled = ret?;
+            self.pos = 0;
+        }
+        Ok(&self.buf[self.pos..self.filled])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.filled);
+    }
+}
+
+/// Writer applying a keystream to the data written to an underlying writer
+///
+/// The MAC covers the data written to the underlying writer, after the keystream is applied.
+/// Processed data is buffered, [`ZucWriter::finish`] or [`Write::flush`] must be called
+/// before the writer is dropped.
+pub struct ZucWriter<W, C, M = ()> {
+    /// underlying writer
+    inner: W,
+    /// stream cipher
+    cipher: C,
+    /// MAC over the output
+    mac: M,
+    /// processed data not yet written
+    buf: Vec<u8>,
+}
+
+impl<W: Write, C: StreamCipher> ZucWriter<W, C> {
+    /// Creates a writer without MAC
+    pub fn new(inner: W, cipher: C) -> Self {
+        Self::with_mac(inner, cipher, ())
+    }
+}
+
+impl<W: Write, C: StreamCipher, M: StreamMac> ZucWriter<W, C, M> {
+    /// Creates a writer computing a MAC over its output
+    pub fn with_mac(inner: W, cipher: C, mac: M) -> Self {
+        Self {
+            inner,
+            cipher,
+            mac,
+            buf: Vec::with_capacity(BUF_SIZE),
+        }
+    }
+
+    /// Returns a reference to the underlying writer
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Writes the buffered data and returns the underlying writer and the MAC of all data written
+    ///
+    /// # Errors
+    /// + Returns the I/O errors of the underlying writer.
+    pub fn finish(mut self) -> io::Result<(W, M::Tag)> {
+        self.flush()?;
+        Ok((self.inner, self.mac.finish()))
+    }
+
+    /// Writes the buffered data to the underlying writer
+    fn flush_buf(&mut self) -> io::Result<()> {
+        let mut written = 0;
+        let ret = loop {
+            if written == self.buf.len() {
+                break Ok(());
+            }
+            match self.inner.write(&self.buf[written..]) {
+                Ok(0) => break Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => written += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => break Err(e),
+            }
+        };
+        self.buf.drain(..written);
+        ret
+    }
+}
+
+impl<W: Write, C: StreamCipher, M: StreamMac> Write for ZucWriter<W, C, M> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.buf.len() == BUF_SIZE {
+            self.flush_buf()?;
+        }
+        let start = self.buf.len();
+        let n = data.len().min(BUF_SIZE - start);
+        self.buf.extend_from_slice(&data[..n]);
+        self.cipher.apply_keystream(&mut self.buf[start..]);
+        self.mac.update(&self.buf[start..]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_buf()?;
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{eia3_128_generate_mac, zuc256_generate_mac, Zuc128, Zuc256};
+
+    use cipher::KeyIvInit;
+
+    static K128: [u8; 16] = [0x17; 16];
+    static IV128: [u8; 16] = [0x66; 16];
+    static K256: [u8; 32] = [0x3d; 32];
+    static IV256: [u8; 23] = [0x84; 23];
+
+    fn plain() -> Vec<u8> {
+        let mut v = Vec::new();
+        for i in 0..2000 {
+            v.extend_from_slice(format!("line {i}\n").as_bytes());
+        }
+        v
+    }
+
+    #[test]
+    fn writer() {
+        let plain = plain();
+        let mut expected = plain.clone();
+        Zuc128::new(&K128.into(), &IV128.into()).apply_keystream(&mut expected);
+        let bitlen = u32::try_from(expected.len() * 8).unwrap();
+        let expected_mac = eia3_128_generate_mac(0x1234, 5, 1, &K128, bitlen, &expected);
+
+        for step in [1, 7, 4096, 100_000] {
+            let cipher = Zuc128::new(&K128.into(), &IV128.into());
+            let mac = Zuc128Mac::eia3(0x1234, 5, 1, &K128);
+            let mut w = ZucWriter::with_mac(Vec::new(), cipher, mac);
+            for piece in plain.chunks(step) {
+                w.write_all(piece).unwrap();
+            }
+            let (out, tag) = w.finish().unwrap();
+            assert_eq!(out, expected);
+            assert_eq!(tag, expected_mac);
+        }
+
+        let mut w = ZucWriter::new(Vec::new(), Zuc128::new(&K128.into(), &IV128.into()));
+        w.write_all(&plain).unwrap();
+        w.flush().unwrap();
+        assert_eq!(w.get_ref(), &expected);
+    }
+
+    #[test]
+    fn reader() {
+        let plain = plain();
+        let mut ciphertext = plain.clone();
+        Zuc256::new(&K256.into(), &IV256.into()).apply_keystream(&mut ciphertext);
+        let bitlen = u32::try_from(ciphertext.len() * 8).unwrap();
+        let expected_mac = zuc256_generate_mac::<u64>(&K256, &IV256, bitlen, &ciphertext);
+
+        let cipher = Zuc256::new(&K256.into(), &IV256.into());
+        let mac = Zuc256Mac::<u64>::new(&K256, &IV256);
+        let mut r = ZucReader::with_mac(&ciphertext[..], cipher, mac);
+        let mut line = String::new();
+        r.read_line(&mut line).unwrap();
+        assert_eq!(line, "line 0\n");
+        let mut rest = Vec::new();
+        r.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, plain[7..]);
+        let (_, tag) = r.finish();
+        assert_eq!(tag, expected_mac);
+
+        // large reads bypass the buffer
+        let cipher = Zuc256::new(&K256.into(), &IV256.into());
+        let mut r = ZucReader::new(&ciphertext[..], cipher);
+        let mut head = [0; 3];
+        r.read_exact(&mut head).unwrap();
+        let mut rest = vec![0; plain.len() - 3];
+        r.read_exact(&mut rest).unwrap();
+        assert_eq!(head, plain[..3]);
+        assert_eq!(rest, plain[3..]);
+        assert_eq!(r.read(&mut head).unwrap(), 0);
+    }
+}
@@ -0,0 +1,189 @@
+//! 3GPP key derivation
+//! ([TS 33.220](https://www.3gpp.org/DynaReport/33220.htm) Annex B,
+//! [TS 33.401](https://www.3gpp.org/DynaReport/33401.htm) Annex A.7,
+//! [TS 33.501](https://www.3gpp.org/DynaReport/33501.htm) Annex A.8)
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Algorithm identity of 128-EEA3/128-EIA3 (128-NEA3/128-NIA3)
+pub const ZUC_ALGORITHM_IDENTITY: u8 = 3;
+
+/// Generic 3GPP key derivation function
+///
+/// Computes `HMAC-SHA-256(key, S)` where `S = FC || P0 || L0 || P1 || L1 || ...`
+/// and each `Li` is the 16-bit big-endian length of `Pi`.
+///
+/// # Panics
+/// + Panics if any parameter is longer than 65535 bytes.
+#[must_use]
+pub fn kdf(key: &[u8], fc: u8, params: &[&[u8]]) -> [u8; 32] {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(&[fc]);
+    for p in params {
+        let len = u16::try_from(p.len()).expect("KDF parameter is too long");
+        mac.update(p);
+        mac.update(&len.to_be_bytes());
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/// Key hierarchy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyHierarchy {
+    /// EPS, from `K_ASME` or `K_eNB` (FC = 0x15)
+    Eps,
+    /// 5GS, from `K_AMF` or `K_gNB` (FC = 0x69)
+    FiveGs,
+}
+
+impl KeyHierarchy {
+    /// FC value of the algorithm key derivation
+    #[must_use]
+    pub fn fc(self) -> u8 {
+        match self {
+            KeyHierarchy::Eps => 0x15,
+            KeyHierarchy::FiveGs => 0x69,
+        }
+    }
+}
+
+/// Algorithm type distinguisher
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlgorithmType {
+    /// `K_NASenc`
+    NasEnc = 0x01,
+    /// `K_NASint`
+    NasInt = 0x02,
+    /// `K_RRCenc`
+    RrcEnc = 0x03,
+    /// `K_RRCint`
+    RrcInt = 0x04,
+    /// `K_UPenc`
+    UpEnc = 0x05,
+    /// `K_UPint`
+    UpInt = 0x06,
+}
+
+/// Derives a 256-bit algorithm key
+///
+/// Input:
+/// - `hierarchy`:            key hierarchy
+/// - `key`:                  256bit  `K_ASME`/`K_AMF` for NAS keys, `K_eNB`/`K_gNB` for RRC and UP keys
+/// - `algorithm_type`:       algorithm type distinguisher
+/// - `algorithm_identity`:   algorithm identity
+///
+/// Output:
+/// - `[u8; 32]`:  the full KDF output
+#[must_use]
+pub fn derive_algorithm_key(
+    hierarchy: KeyHierarchy,
+    key: &[u8; 32],
+    algorithm_type: AlgorithmType,
+    algorithm_identity: u8,
+) -> [u8; 32] {
+    let p0 = [algorithm_type as u8];
+    let p1 = [algorithm_identity];
+    kdf(key, hierarchy.fc(), &[&p0, &p1])
+}
+
+/// Derives a 128-bit key for 128-EEA3/128-EIA3
+///
+/// The key is the 128 least significant bits of the KDF output.
+#[must_use]
+pub fn derive_zuc128_key(
+    hierarchy: KeyHierarchy,
+    key: &[u8; 32],
+    algorithm_type: AlgorithmType,
+) -> [u8; 16] {
+    let out = derive_algorithm_key(hierarchy, key, algorithm_type, ZUC_ALGORITHM_IDENTITY);
+    let mut k = [0; 16];
+    k.copy_from_slice(&out[16..]);
+    k
+}
+
+/// Derives a 256-bit key for ZUC-256
+///
+/// The key is the full KDF output.
+#[must_use]
+pub fn derive_zuc256_key(
+    hierarchy: KeyHierarchy,
+    key: &[u8; 32],
+    algorithm_type: AlgorithmType,
+    algorithm_identity: u8,
+) -> [u8; 32] {
+    derive_algorithm_key(hierarchy, key, algorithm_type, algorithm_identity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use const_str::hex;
+
+    // TS 33.220, TS 33.401 and TS 33.501 publish no test data for the KDF.
+    // The expected values are cross-checked against OpenSSL's HMAC, e.g. for `K_NASenc` in 5GS:
+    // printf '\x69\x01\x00\x01\x03\x00\x01' | openssl dgst -sha256 -mac HMAC -macopt hexkey:0001..1f
+    static KEY: [u8; 32] = hex!("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f");
+
+    #[test]
+    fn algorithm_keys() {
+        let algorithm_types = [
+            AlgorithmType::NasEnc,
+            AlgorithmType::NasInt,
+            AlgorithmType::RrcEnc,
+            AlgorithmType::RrcInt,
+            AlgorithmType::UpEnc,
+            AlgorithmType::UpInt,
+        ];
+
+        let eps: [[u8; 16]; 6] = [
+            hex!("7e8169d5bb7ccb9a3cefdf90fb3b95e0"),
+            hex!("be50533a8dd35a6f85bfa1175f45f46f"),
+            hex!("0c6b657d79f7717f606cc8e7f97f8f9b"),
+            hex!("5ef5ffb23515e1f511f59ca7d3b1cbaa"),
+            hex!("1a38888147fc5b8ffe68ce23039fcd48"),
+            hex!("3025f040f09033a4fcfcd104e38ea358"),
+        ];
+
+        let five_gs: [[u8; 16]; 6] = [
+            hex!("b39df53dc99a6a3abf39840e24331957"),
+            hex!("787a8cdec75ea15c0e055b864f944e07"),
+            hex!("fdf500f2fbde35387c2e8a661e60f5ec"),
+            hex!("8619c0258893195d07f364ad4a168502"),
+            hex!("b614bf48aa005b25f26a6961ad88d18a"),
+            hex!("2934f9affd76bdb0251e53ea10a87d36"),
+        ];
+
+        for (hierarchy, expected) in [(KeyHierarchy::Eps, eps), (KeyHierarchy::FiveGs, five_gs)] {
+            for (algorithm_type, expected) in algorithm_types.into_iter().zip(expected) {
+                assert_eq!(derive_zuc128_key(hierarchy, &KEY, algorithm_type), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn zuc256_key() {
+        let key = derive_zuc256_key(KeyHierarchy::FiveGs, &[0xff; 32], AlgorithmType::UpEnc, 3);
+        let expected = hex!("c40b03fa85566b8d1113eea93ad7f9a242c10cfeab6c2b824e5033d44b56056d");
+        assert_eq!(key, expected);
+        assert_eq!(
+            derive_zuc128_key(KeyHierarchy::FiveGs, &[0xff; 32], AlgorithmType::UpEnc),
+            key[16..]
+        );
+    }
+
+    #[test]
+    fn generic() {
+        // K_gNB* style derivation (FC = 0x70), PCI = 300, ARFCN-DL = 640000
+        let out = kdf(&KEY, 0x70, &[&[0x01, 0x2c], &[0x09, 0xc3, 0x50]]);
+        let expected = hex!("839d564b70e7d20817fde9f00dfa3351e5e14d6e6e391024ee5c9efe7156d728");
+        assert_eq!(out, expected);
+
+        let out = kdf(&KEY, 0x69, &[&[0x01], &[0x03]]);
+        assert_eq!(
+            out,
+            derive_algorithm_key(KeyHierarchy::FiveGs, &KEY, AlgorithmType::NasEnc, 3)
+        );
+    }
+}
@@ -23,6 +23,10 @@
 
 mod u256;
 mod zuc;
+pub use self::zuc::{StateError, STATE_LEN};
+
+mod direction;
+pub use self::direction::Direction;
 
 mod zuc128;
 pub use self::zuc128::{Zuc128, Zuc128Core};
@@ -31,12 +35,79 @@ mod eea3_128;
 pub use eea3_128::{eea3_128_encrypt, zuc128_xor_encrypt};
 
 mod eia3_128;
-pub use eia3_128::{eia3_128_generate_mac, zuc128_generate_mac};
+pub use eia3_128::{eia3_128_generate_mac, zuc128_generate_mac, Zuc128Mac};
 
 mod zuc256;
 pub use self::zuc256::{Zuc256, Zuc256Core};
 
 mod zuc256_mac;
-pub use self::zuc256_mac::zuc256_generate_mac;
+pub use self::zuc256_mac::{zuc256_generate_mac, Zuc256Mac};
+
+mod self_test;
+pub use self::self_test::{self_test, SelfTestError};
+
+pub mod algorithms;
+pub mod fault;
+pub mod io;
+pub mod nas;
+pub mod pdcp;
+pub mod record;
+
+#[cfg(feature = "kdf")]
+#[cfg_attr(docsrs, doc(cfg(feature = "kdf")))]
+pub mod kdf;
+
+#[cfg(feature = "pcap")]
+#[cfg_attr(docsrs, doc(cfg(feature = "pcap")))]
+pub mod pcap;
+
+#[cfg(feature = "container")]
+#[cfg_attr(docsrs, doc(cfg(feature = "container")))]
+pub mod container;
+
+#[cfg(feature = "par")]
+#[cfg_attr(docsrs, doc(cfg(feature = "par")))]
+pub mod par;
+
+#[cfg(feature = "tokio")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+pub mod async_io;
+
+#[cfg(feature = "trace")]
+#[cfg_attr(docsrs, doc(cfg(feature = "trace")))]
+pub mod trace;
+
+#[cfg(feature = "research")]
+#[cfg_attr(docsrs, doc(cfg(feature = "research")))]
+pub mod research;
+
+#[cfg(feature = "research")]
+#[cfg_attr(docsrs, doc(cfg(feature = "research")))]
+pub mod analysis;
+
+#[cfg(feature = "research")]
+#[cfg_attr(docsrs, doc(cfg(feature = "research")))]
+pub mod algebraic;
+
+#[cfg(feature = "stats")]
+#[cfg_attr(docsrs, doc(cfg(feature = "stats")))]
+pub mod stats;
+
+#[cfg(feature = "masked")]
+#[cfg_attr(docsrs, doc(cfg(feature = "masked")))]
+pub mod masked;
+
+#[cfg(feature = "rand_core")]
+#[cfg_attr(docsrs, doc(cfg(feature = "rand_core")))]
+mod rng;
+#[cfg(feature = "rand_core")]
+#[cfg_attr(docsrs, doc(cfg(feature = "rand_core")))]
+pub use self::rng::{ZucRng, ZucRngSeed};
+
+#[cfg(feature = "sm3")]
+mod sm3;
+#[cfg(feature = "sm3")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sm3")))]
+pub use self::sm3::{sm3_kdf, sm3_kdf_zuc128, sm3_kdf_zuc256};
 
 pub use cipher;
@@ -0,0 +1,482 @@
+//! First-order masked ZUC keystream generator
+//!
+//! [`MaskedZucCore`] splits every secret intermediate value into two shares
+//! refreshed with randomness from a caller-provided RNG:
+//!
+//! + LFSR cells are shared arithmetically modulo 2^31 - 1, `s = a ⊞ b`, so the
+//!   feedback, a sum of rotations modulo 2^31 - 1, is computed on each share separately.
+//!   Each share keeps the representation `1..=2^31 - 1` of the cells.
+//! + The F function works on Boolean shares, `x = x0 ^ x1`. The S boxes are looked up
+//!   in tables recomputed under fresh input and output masks.
+//! + The additions modulo 2^32 in F convert their Boolean operands to arithmetic shares
+//!   (Goubin's conversion), add them share-wise, and convert the sum back.
+//! + Conversions to Boolean shares and from Boolean shares modulo 2^31 - 1 use a
+//!   Boolean-masked adder whose AND gates are ISW multiplications.
+//!
+//! The keystream equals the one of [`Zuc128Core`](crate::Zuc128Core) and
+//! [`Zuc256Core`](crate::Zuc256Core). Each cell also keeps Boolean shares, converted
+//! once when it enters the LFSR, for the bit reconstruction.
+//!
+//! The key and IV are loaded unmasked: only the initialization and working stages
+//! are protected. Masking does not protect against higher-order attacks,
+//! and the table lookups are not constant-time.
+
+use crate::zuc::{rotate_xor, L1_ROTATIONS, L2_ROTATIONS, S0, S1};
+use crate::{zuc128, zuc256};
+
+use rand_core::RngCore;
+
+/// 2^31 - 1
+const P: u32 = (1 << 31) - 1;
+
+/// Two shares of a 32-bit value
+type Shares = [u32; 2];
+
+/// (a + b) mod (2^31 - 1) for `a`, `b` in `1..=P` or `0..=P`, in the representation `0..=P`
+fn add_p(a: u32, b: u32) -> u32 {
+    let c = a + b;
+    (c & P) + (c >> 31)
+}
+
+/// 2^k * x mod (2^31 - 1) for a 31-bit `x`
+fn rot31(x: u32, k: u32) -> u32 {
+    ((x << k) | (x >> (31 - k))) & P
+}
+
+/// Uniform value in `1..=P`
+fn random_cell(rng: &mut impl RngCore) -> u32 {
+    loop {
+        let x = rng.next_u32() & P;
+        if x != 0 {
+            return x;
+        }
+    }
+}
+
+/// Boolean shares of `x`
+fn share_bool(x: u32, rng: &mut impl RngCore) -> Shares {
+    let m = rng.next_u32();
+    [x ^ m, m]
+}
+
+/// Arithmetic shares modulo 2^31 - 1 of the cell `x`
+fn share_cell(x: u32, rng: &mut impl RngCore) -> Shares {
+    let a = random_cell(rng);
+    // b = x - a, in 1..=P
+    [a, add_p(x, P - a)]
+}
+
+/// Refreshes Boolean shares
+fn refresh(x: Shares, rng: &mut impl RngCore) -> Shares {
+    let m = rng.next_u32();
+    [x[0] ^ m, x[1] ^ m]
+}
+
+/// Applies `f`, linear over XOR, to each share
+fn map(x: Shares, f: impl Fn(u32) -> u32) -> Shares {
+    [f(x[0]), f(x[1])]
+}
+
+/// XOR of Boolean shares
+fn xor(x: Shares, y: Shares) -> Shares {
+    [x[0] ^ y[0], x[1] ^ y[1]]
+}
+
+/// AND of Boolean shares (ISW multiplication, second operand refreshed)
+fn sec_and(x: Shares, y: Shares, rng: &mut impl RngCore) -> Shares {
+    let y = refresh(y, rng);
+    let r = rng.next_u32();
+    let z0 = (x[0] & y[0]) ^ r;
+    let z1 = (x[1] & y[1]) ^ ((r ^ (x[0] & y[1])) ^ (x[1] & y[0]));
+    [z0, z1]
+}
+
+/// Sum modulo 2^32 of Boolean shares, by a masked ripple-carry adder
+#[allow(clippy::many_single_char_names)]
+fn sec_add(x: Shares, y: Shares, rng: &mut impl RngCore) -> Shares {
+    let p = xor(x, y);
+    let g = sec_and(x, y, rng);
+    let mut c = [0, 0];
+    for _ in 0..31 {
+        c = map(xor(g, sec_and(p, c, rng)), |c| c << 1);
+    }
+    xor(p, c)
+}
+
+/// Boolean to arithmetic shares modulo 2^32 (Goubin's conversion)
+///
+/// Returns `[a, x1]` with `x0 ^ x1 = a + x1`.
+#[allow(clippy::many_single_char_names)]
+fn b2a(x: Shares, rng: &mut impl RngCore) -> Shares {
+    let [x, r] = x;
+    let g = rng.next_u32();
+    let t = (x ^ g).wrapping_sub(g) ^ x;
+    let g = g ^ r;
+    let a = (x ^ g).wrapping_sub(g) ^ t;
+    [a, r]
+}
+
+/// Arithmetic shares modulo 2^32 to Boolean shares
+fn a2b(a: Shares, rng: &mut impl RngCore) -> Shares {
+    let x = share_bool(a[0], rng);
+    let y = share_bool(a[1], rng);
+    sec_add(x, y, rng)
+}
+
+/// Sum modulo 2^32 of Boolean shares through arithmetic shares
+fn add(x: Shares, y: Shares, rng: &mut impl RngCore) -> Shares {
+    let a = b2a(x, rng);
+    let b = b2a(y, rng);
+    a2b([a[0].wrapping_add(b[0]), a[1].wrapping_add(b[1])], rng)
+}
+
+/// Reduces Boolean shares of `x < 2^32` modulo 2^31 - 1 as `(x & P) + (x >> 31)`
+fn fold_p(x: Shares, rng: &mut impl RngCore) -> Shares {
+    let low = map(x, |x| x & P);
+    let high = refresh(map(x, |x| x >> 31), rng);
+    sec_add(low, high, rng)
+}
+
+/// Arithmetic shares modulo 2^31 - 1 of a cell to Boolean shares
+///
+/// For shares in `1..=P` the result is the cell in `1..=P`.
+fn cell_a2b(a: Shares, rng: &mut impl RngCore) -> Shares {
+    let x = share_bool(a[0], rng);
+    let y = share_bool(a[1], rng);
+    fold_p(sec_add(x, y, rng), rng)
+}
+
+/// Boolean shares of `u <= P` to arithmetic shares modulo 2^31 - 1 in `1..=P`
+///
+/// The second share `u - a` is unmasked: it is uniform and independent of `u`
+/// for a uniform first share `a`.
+fn cell_b2a(u: Shares, rng: &mut impl RngCore) -> Shares {
+    let a = random_cell(rng);
+    let neg_a = share_bool(P - a, rng);
+    let b = fold_p(sec_add(u, neg_a, rng), rng);
+    let b = b[0] ^ b[1];
+    [a, if b == 0 { P } else { b }]
+}
+
+/// S box layer on Boolean shares, each byte looked up in a table recomputed under fresh masks
+fn sbox(x: Shares, rng: &mut impl RngCore) -> Shares {
+    let (x0, x1) = (x[0].to_be_bytes(), x[1].to_be_bytes());
+    let mut y0 = [0; 4];
+    let mut y1 = [0; 4];
+    let mut table = [0; 256];
+    for i in 0..4 {
+        let s = if i % 2 == 0 { &S0 } else { &S1 };
+        let masks = rng.next_u32().to_be_bytes();
+        let (m_in, m_out) = (masks[0], masks[1]);
+        for (j, t) in (0..=u8::MAX).zip(&mut table) {
+            *t = s[usize::from(j ^ m_in)] ^ m_out;
+        }
+        // x0 ^ (x1 ^ m_in) = x ^ m_in
+        y0[i] = table[usize::from(x0[i] ^ (x1[i] ^ m_in))];
+        y1[i] = m_out;
+    }
+    [u32::from_be_bytes(y0), u32::from_be_bytes(y1)]
+}
+
+/// Masked ZUC keystream generator
+///
+/// The output is the keystream of the unmasked generators.
+///
+/// The generator is not [`Clone`]: a copy sharing the RNG state would draw
+/// the same masks, use [`MaskedZucCore::clone_with_rng`] instead.
+pub struct MaskedZucCore<R> {
+    /// LFSR cells, arithmetic shares modulo 2^31 - 1
+    s: [Shares; 16],
+    /// LFSR cells, Boolean shares
+    s_bool: [Shares; 16],
+    /// R1, Boolean shares
+    r1: Shares,
+    /// R2, Boolean shares
+    r2: Shares,
+    /// source of the masks
+    rng: R,
+}
+
+impl<R: RngCore> MaskedZucCore<R> {
+    /// Creates a masked ZUC128 keystream generator
+    ///
+    /// # Panics
+    /// + Panics with the `auto-self-test` feature if the power-on self-tests failed.
+    pub fn zuc128(key: &[u8; 16], iv: &[u8; 16], rng: R) -> Self {
+        Self::with_cells(zuc128::load(key, iv, &zuc128::D).s, rng)
+    }
+
+    /// Creates a masked ZUC256 keystream generator
+    ///
+    /// # Panics
+    /// + Panics with the `auto-self-test` feature if the power-on self-tests failed.
+    pub fn zuc256(key: &[u8; 32], iv: &[u8; 23], rng: R) -> Self {
+        Self::with_cells(zuc256::load(key, iv, &zuc256::D).s, rng)
+    }
+
+    /// Shares the loaded LFSR and runs the initialization stage
+    fn with_cells(cells: [u32; 16], mut rng: R) -> Self {
+        #[cfg(feature = "auto-self-test")]
+        crate::self_test::ensure();
+        let mut zuc = Self {
+            s: cells.map(|x| share_cell(x, &mut rng)),
+            s_bool: cells.map(|x| share_bool(x, &mut rng)),
+            r1: share_bool(0, &mut rng),
+            r2: share_bool(0, &mut rng),
+            rng,
+        };
+        for _ in 0..32 {
+            zuc.init_round();
+        }
+        let x = zuc.bit_reconstruction();
+        zuc.f(x);
+        zuc
+    }
+
+    /// Copies the generator, drawing the masks of the copy from `rng`
+    ///
+    /// The shares of the copy are refreshed, so they differ from the ones of `self`.
+    #[must_use]
+    pub fn clone_with_rng(&self, mut rng: R) -> Self {
+        let s = self.s.map(|[a, b]| {
+            let m = random_cell(&mut rng);
+            [add_p(a, m), add_p(b, P - m)]
+        });
+        let s_bool = self.s_bool.map(|x| refresh(x, &mut rng));
+        let r1 = refresh(self.r1, &mut rng);
+        let r2 = refresh(self.r2, &mut rng);
+        Self {
+            s,
+            s_bool,
+            r1,
+            r2,
+            rng,
+        }
+    }
+
+    /// Runs one round of the initialization stage
+    fn init_round(&mut self) {
+        let x = self.bit_reconstruction();
+        let w = self.f(x);
+        let u = cell_b2a(map(w, |w| w >> 1), &mut self.rng);
+        self.lfsr(Some(u));
+    }
+
+    /// `BitReconstruction` function on Boolean shares
+    fn bit_reconstruction(&self) -> [Shares; 4] {
+        let s = &self.s_bool;
+        let mut x = [[0; 2]; 4];
+        for k in 0..2 {
+            // the top bits of the shares cancel out, the cells being 31-bit words
+            x[0][k] = ((s[15][k] & 0x7FFF_8000) << 1) ^ (s[14][k] & 0xFFFF);
+            x[1][k] = ((s[11][k] & 0xFFFF) << 16) ^ ((s[9][k] & P) >> 15);
+            x[2][k] = ((s[7][k] & 0xFFFF) << 16) ^ ((s[5][k] & P) >> 15);
+            x[3][k] = ((s[2][k] & 0xFFFF) << 16) ^ ((s[0][k] & P) >> 15);
+        }
+        x
+    }
+
+    /// F non-linear function on Boolean shares
+    fn f(&mut self, x: [Shares; 4]) -> Shares {
+        let rng = &mut self.rng;
+        let w = add(xor(x[0], self.r1), self.r2, rng);
+        let w1 = add(self.r1, x[1], rng);
+        let w2 = xor(self.r2, x[2]);
+        let u = map(xor(map(w1, |w| w << 16), map(w2, |w| w >> 16)), |u| {
+            rotate_xor(u, L1_ROTATIONS)
+        });
+        let v = map(xor(map(w2, |w| w << 16), map(w1, |w| w >> 16)), |v| {
+            rotate_xor(v, L2_ROTATIONS)
+        });
+        self.r1 = sbox(u, rng);
+        self.r2 = sbox(v, rng);
+        w
+    }
+
+    /// LFSR update, adding the arithmetic shares `u` in the initialization mode
+    fn lfsr(&mut self, u: Option<Shares>) {
+        let s = &self.s;
+        let mut s16 = [0; 2];
+        for k in 0..2 {
+            let terms = [
+                rot31(s[15][k], 15),
+                rot31(s[13][k], 17),
+                rot31(s[10][k], 21),
+                rot31(s[4][k], 20),
+                rot31(s[0][k], 8),
+                s[0][k],
+            ];
+            let v = terms.into_iter().reduce(add_p).unwrap_or(P);
+            s16[k] = u.map_or(v, |u| add_p(v, u[k]));
+        }
+        let s16_bool = cell_a2b(s16, &mut self.rng);
+        self.s.copy_within(1.., 0);
+        self.s[15] = s16;
+        self.s_bool.copy_within(1.., 0);
+        self.s_bool[15] = s16_bool;
+    }
+
+    /// Generates the next 32-bit word in the keystream
+    pub fn generate(&mut self) -> u32 {
+        self.lfsr(None);
+        let x = self.bit_reconstruction();
+        let z = xor(self.f(x), x[3]);
+        z[0] ^ z[1]
+    }
+}
+
+impl<R> core::fmt::Debug for MaskedZucCore<R> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("MaskedZucCore { ... }")
+    }
+}
+
+impl<R: RngCore> Iterator for MaskedZucCore<R> {
+    type Item = u32;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.generate())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zuc::Zuc;
+    use crate::{Zuc128Core, Zuc256Core};
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    impl<R> MaskedZucCore<R> {
+        /// Unmasked state, checking that both sharings of each cell agree
+        fn unmask(&self) -> Zuc {
+            let mut zuc = Zuc::zeroed();
+            for i in 0..16 {
+                let [a, b] = self.s[i];
+                assert!((1..=P).contains(&a) && (1..=P).contains(&b));
+                zuc.s[i] = add_p(a, b);
+                assert_eq!(zuc.s[i], self.s_bool[i][0] ^ self.s_bool[i][1]);
+            }
+            zuc.r1 = self.r1[0] ^ self.r1[1];
+            zuc.r2 = self.r2[0] ^ self.r2[1];
+            zuc
+        }
+    }
+
+    #[test]
+    #[allow(clippy::many_single_char_names)]
+    fn gadgets() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..1000 {
+            let (x, y): (u32, u32) = rng.gen();
+            let xs = share_bool(x, &mut rng);
+            let ys = share_bool(y, &mut rng);
+
+            let z = sec_and(xs, ys, &mut rng);
+            assert_eq!(z[0] ^ z[1], x & y);
+            let z = sec_add(xs, ys, &mut rng);
+            assert_eq!(z[0] ^ z[1], x.wrapping_add(y));
+            let z = add(xs, ys, &mut rng);
+            assert_eq!(z[0] ^ z[1], x.wrapping_add(y));
+
+            let a = b2a(xs, &mut rng);
+            assert_eq!(a[0].wrapping_add(a[1]), x);
+            let b = a2b(a, &mut rng);
+            assert_eq!(b[0] ^ b[1], x);
+
+            let cell = (x & P).max(1);
+            let a = share_cell(cell, &mut rng);
+            assert_eq!(add_p(a[0], a[1]), cell);
+            let b = cell_a2b(a, &mut rng);
+            assert_eq!(b[0] ^ b[1], cell);
+
+            let u = y >> 1;
+            let a = cell_b2a(share_bool(u, &mut rng), &mut rng);
+            assert_eq!(add_p(a[0], a[1]) % P, u % P);
+
+            let s = sbox(xs, &mut rng);
+            let b = x.to_be_bytes();
+            let expected = [S0[usize::from(b[0])], S1[usize::from(b[1])]];
+            assert_eq!((s[0] ^ s[1]) >> 16, u32::from(u16::from_be_bytes(expected)));
+        }
+
+        // shares of the cell 2^31 - 1, which is 0 modulo 2^31 - 1
+        let a = share_cell(P, &mut rng);
+        let b = cell_a2b(a, &mut rng);
+        assert_eq!(b[0] ^ b[1], P);
+        for u in [0, P] {
+            let a = cell_b2a(share_bool(u, &mut rng), &mut rng);
+            assert_eq!(add_p(a[0], a[1]), P);
+        }
+    }
+
+    #[test]
+    fn keystream() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..3 {
+            let k: [u8; 16] = rng.gen();
+            let iv: [u8; 16] = rng.gen();
+            let masked = MaskedZucCore::zuc128(&k, &iv, StdRng::seed_from_u64(rng.gen()));
+            let zuc = Zuc128Core::new(&k, &iv);
+            assert!(masked.zip(zuc).take(50).all(|(a, b)| a == b));
+
+            let k: [u8; 32] = rng.gen();
+            let iv: [u8; 23] = rng.gen();
+            let masked = MaskedZucCore::zuc256(&k, &iv, StdRng::seed_from_u64(rng.gen()));
+            let zuc = Zuc256Core::new(&k, &iv);
+            assert!(masked.zip(zuc).take(50).all(|(a, b)| a == b));
+        }
+
+        let masked = MaskedZucCore::zuc128(&[0; 16], &[0; 16], StdRng::seed_from_u64(3));
+        let z: Vec<u32> = masked.take(2).collect();
+        assert_eq!(z, [0x27be_de74, 0x0180_82da]);
+    }
+
+    #[test]
+    fn intermediate_values() {
+        let (k, iv) = ([0x3d; 16], [0x84; 16]);
+        let mut plain = zuc128::load(&k, &iv, &zuc128::D);
+        let mut masked = MaskedZucCore {
+            s: plain
+                .s
+                .map(|x| share_cell(x, &mut StdRng::seed_from_u64(x.into()))),
+            s_bool: plain
+                .s
+                .map(|x| share_bool(x, &mut StdRng::seed_from_u64(x.into()))),
+            r1: [0, 0],
+            r2: [0, 0],
+            rng: StdRng::seed_from_u64(4),
+        };
+        let mut other = masked.clone_with_rng(StdRng::seed_from_u64(5));
+        assert_ne!(masked.s, other.s);
+        assert_eq!(other.unmask().s, plain.s);
+
+        for _ in 0..32 {
+            let x = masked.bit_reconstruction();
+            assert_eq!(x.map(|x| x[0] ^ x[1]), plain.bit_reconstruction());
+            masked.init_round();
+            other.init_round();
+            plain.init_round_with(&S0, &S1);
+            let state = masked.unmask();
+            assert_eq!(state.s, plain.s);
+            assert_eq!((state.r1, state.r2), (plain.r1, plain.r2));
+            // fresh randomness gives other shares of the same state
+            assert_ne!(masked.s, other.s);
+            assert_ne!(masked.r1, other.r1);
+            assert_eq!(other.unmask().s, plain.s);
+        }
+
+        let x = masked.bit_reconstruction();
+        let w = masked.f(x);
+        let x = plain.bit_reconstruction();
+        assert_eq!(w[0] ^ w[1], plain.f_with(x, &S0, &S1));
+        for _ in 0..20 {
+            assert_eq!(masked.generate(), plain.generate());
+            let state = masked.unmask();
+            assert_eq!(state.s, plain.s);
+            assert_eq!((state.r1, state.r2), (plain.r1, plain.r2));
+        }
+    }
+}
@@ -0,0 +1,612 @@
+//! NAS security context
+//! ([TS 24.301](https://www.3gpp.org/DynaReport/24301.htm), [TS 24.501](https://www.3gpp.org/DynaReport/24501.htm))
+//!
+//! A security protected NAS message is `header || MAC || SQN || message`.
+//! The message is ciphered with 128-EEA3,
+//! then `MAC` is computed with 128-EIA3 over `SQN || message`.
+//!
+//! NAS COUNT is the 16-bit overflow counter followed by the 8-bit SQN,
+//! padded to 32 bits with zeros.
+
+use crate::{eea3_128_encrypt, eia3_128_generate_mac, Direction};
+
+use std::fmt;
+
+/// Maximum NAS COUNT plus one
+const COUNT_LIMIT: u32 = 1 << 24;
+
+/// EPS mobility management protocol discriminator
+const EMM_PD: u8 = 0x07;
+
+/// 5GS mobility management extended protocol discriminator
+const FIVEGMM_EPD: u8 = 0x7e;
+
+/// 5GS session management extended protocol discriminator
+const FIVEGSM_EPD: u8 = 0x2e;
+
+/// Converts a byte length to a bit length
+///
+/// # Panics
+/// + Panics if the bit length is greater than `u32::MAX`.
+fn bit_length(len: usize) -> u32 {
+    len.checked_mul(8)
+        .and_then(|n| u32::try_from(n).ok())
+        .expect("NAS message bit length overflow")
+}
+
+/// NAS message format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NasSystem {
+    /// EPS NAS (TS 24.301), 6-byte security header
+    Eps,
+    /// 5GS NAS (TS 24.501), 7-byte security header
+    FiveGs,
+}
+
+impl NasSystem {
+    /// Length of the security protected NAS message header, including MAC and SQN
+    #[must_use]
+    pub fn header_len(self) -> usize {
+        match self {
+            NasSystem::Eps => 6,
+            NasSystem::FiveGs => 7,
+        }
+    }
+}
+
+/// Security header type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SecurityHeaderType {
+    /// Plain NAS message, not security protected
+    Plain = 0,
+    /// Integrity protected
+    IntegrityProtected = 1,
+    /// Integrity protected and ciphered
+    IntegrityProtectedAndCiphered = 2,
+    /// Integrity protected with new security context
+    IntegrityProtectedWithNewContext = 3,
+    /// Integrity protected and ciphered with new security context
+    IntegrityProtectedAndCipheredWithNewContext = 4,
+}
+
+impl SecurityHeaderType {
+    /// Decodes a 4-bit security header type
+    #[must_use]
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Plain),
+            1 => Some(Self::IntegrityProtected),
+            2 => Some(Self::IntegrityProtectedAndCiphered),
+            3 => Some(Self::IntegrityProtectedWithNewContext),
+            4 => Some(Self::IntegrityProtectedAndCipheredWithNewContext),
+            _ => None,
+        }
+    }
+
+    /// Reads the security header type of a NAS message
+    ///
+    /// # Errors
+    /// + Returns [`NasError::InvalidHeader`] if the header is malformed.
+    /// + Returns [`NasError::UnsupportedHeaderType`] if the security header type is unknown.
+    pub fn of_message(system: NasSystem, pdu: &[u8]) -> Result<Self, NasError> {
+        let raw_type = match (system, pdu) {
+            (NasSystem::Eps, [first, ..]) if first & 0x0f == EMM_PD => first >> 4,
+            (NasSystem::Eps, [_, ..]) | (NasSystem::FiveGs, [FIVEGSM_EPD, ..]) => 0,
+            (NasSystem::FiveGs, [FIVEGMM_EPD, t, ..]) => t & 0x0f,
+            _ => return Err(NasError::InvalidHeader),
+        };
+        Self::from_u8(raw_type).ok_or(NasError::UnsupportedHeaderType(raw_type))
+    }
+
+    /// Whether the message is ciphered
+    #[must_use]
+    pub fn is_ciphered(self) -> bool {
+        matches!(
+            self,
+            Self::IntegrityProtectedAndCiphered | Self::IntegrityProtectedAndCipheredWithNewContext
+        )
+    }
+
+    /// Whether the message takes a new security context into use
+    #[must_use]
+    pub fn is_new_context(self) -> bool {
+        matches!(
+            self,
+            Self::IntegrityProtectedWithNewContext
+                | Self::IntegrityProtectedAndCipheredWithNewContext
+        )
+    }
+}
+
+/// NAS security configuration
+#[derive(Debug, Clone)]
+pub struct NasConfig {
+    /// Message format
+    pub system: NasSystem,
+    /// Transmitting direction, [`Direction::Uplink`] for the UE and [`Direction::Downlink`] for the network
+    pub direction: Direction,
+    /// 5-bit `BEARER` input, 0 for EPS and 3GPP access, 1 for non-3GPP access
+    pub bearer: u8,
+    /// `K_NASenc`, `None` for EEA0/NEA0
+    pub ciphering_key: Option<[u8; 16]>,
+    /// `K_NASint`
+    pub integrity_key: [u8; 16],
+}
+
+/// NAS security errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NasError {
+    /// The message is too short or its header is malformed
+    InvalidHeader,
+    /// The security header type is not supported
+    UnsupportedHeaderType(u8),
+    /// A new security context can only be taken into use by the first message in each direction
+    ContextInUse,
+    /// NAS COUNT would wrap around, the keys must be refreshed
+    CountWrap,
+    /// The message has already been accepted
+    Replay {
+        /// NAS COUNT of the replayed message
+        count: u32,
+    },
+    /// MAC verification failed
+    IntegrityFailure {
+        /// Estimated NAS COUNT of the failed message
+        count: u32,
+    },
+}
+
+impl fmt::Display for NasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NasError::InvalidHeader => write!(f, "invalid security header"),
+            NasError::UnsupportedHeaderType(t) => write!(f, "unsupported security header type {t}"),
+            NasError::ContextInUse => write!(f, "security context already in use"),
+            NasError::CountWrap => write!(f, "NAS COUNT wrap around"),
+            NasError::Replay { count } => write!(f, "replayed message (NAS COUNT = {count:#08x})"),
+            NasError::IntegrityFailure { count } => {
+                write!(f, "integrity check failed (NAS COUNT = {count:#08x})")
+            }
+        }
+    }
+}
+
+impl std::error::Error for NasError {}
+
+/// NAS security context
+///
+/// Keeps independent uplink and downlink NAS COUNTs.
+#[derive(Debug, Clone)]
+pub struct NasSecurityContext {
+    /// security configuration
+    config: NasConfig,
+    /// NAS COUNT of the next message to transmit (up to 2^24)
+    tx_count: u32,
+    /// NAS COUNT of the last accepted message
+    rx_count: Option<u32>,
+}
+
+impl NasSecurityContext {
+    /// Creates a NAS security context with both NAS COUNTs set to zero
+    #[must_use]
+    pub fn new(config: NasConfig) -> Self {
+        Self {
+            config,
+            tx_count: 0,
+            rx_count: None,
+        }
+    }
+
+    /// Returns the NAS COUNT of the next message to transmit
+    #[must_use]
+    pub fn tx_count(&self) -> u32 {
+        self.tx_count
+    }
+
+    /// Returns the NAS COUNT of the last accepted message
+    #[must_use]
+    pub fn rx_count(&self) -> Option<u32> {
+        self.rx_count
+    }
+
+    /// Computes the NAS MAC over `SQN || message`
+    fn mac(&self, direction: Direction, count: u32, body: &[u8]) -> [u8; 4] {
+        let NasConfig {
+            bearer,
+            ref integrity_key,
+            ..
+        } = self.config;
+        let length = bit_length(body.len());
+        eia3_128_generate_mac(count, bearer, direction.bit(), integrity_key, length, body)
+            .to_be_bytes()
+    }
+
+    /// Applies the NAS ciphering keystream
+    fn cipher(&self, direction: Direction, count: u32, message: &[u8]) -> Vec<u8> {
+        match &self.config.ciphering_key {
+            Some(ck) => {
+                let length = bit_length(message.len());
+                eea3_128_encrypt(
+                    count,
+                    self.config.bearer,
+                    direction.bit(),
+                    ck,
+                    length,
+                    message,
+                )
+            }
+            None => message.to_vec(),
+        }
+    }
+
+    /// Protects a plain NAS message
+    ///
+    /// Input:
+    /// - `header_type`:    security header type
+    /// - `message`:        plain NAS message
+    ///
+    /// Output:
+    /// - [`Vec<u8>`]:      security protected NAS message
+    ///
+    /// # Errors
+    /// + Returns [`NasError::UnsupportedHeaderType`] if `header_type` is [`SecurityHeaderType::Plain`].
+    /// + Returns [`NasError::ContextInUse`] if a new context header type is used after the first message.
+    /// + Returns [`NasError::CountWrap`] if NAS COUNT is exhausted.
+    ///
+    /// # Panics
+    /// + Panics if the message bit length is greater than `u32::MAX`.
+    pub fn protect(
+        &mut self,
+        header_type: SecurityHeaderType,
+        message: &[u8],
+    ) -> Result<Vec<u8>, NasError> {
+        if header_type == SecurityHeaderType::Plain {
+            return Err(NasError::UnsupportedHeaderType(0));
+        }
+        if header_type.is_new_context() && self.tx_count != 0 {
+            return Err(NasError::ContextInUse);
+        }
+        if self.tx_count >= COUNT_LIMIT {
+            return Err(NasError::CountWrap);
+        }
+
+        let count = self.tx_count;
+        let direction = self.config.direction;
+
+        let mut body = Vec::with_capacity(1 + message.len());
+        body.push(count.to_be_bytes()[3]);
+        if header_type.is_ciphered() {
+            body.extend_from_slice(&self.cipher(direction, count, message));
+        } else {
+            body.extend_from_slice(message);
+        }
+        let mac = self.mac(direction, count, &body);
+
+        let mut pdu = Vec::with_capacity(self.config.system.header_len() - 1 + body.len());
+        match self.config.system {
+            NasSystem::Eps => pdu.push(((header_type as u8) << 4) | EMM_PD),
+            NasSystem::FiveGs => pdu.extend_from_slice(&[FIVEGMM_EPD, header_type as u8]),
+        }
+        pdu.extend_from_slice(&mac);
+        pdu.extend_from_slice(&body);
+
+        self.tx_count += 1;
+        Ok(pdu)
+    }
+
+    /// Unprotects a NAS message
+    ///
+    /// Plain NAS messages are returned unchanged with [`SecurityHeaderType::Plain`],
+    /// the caller decides whether to accept them.
+    ///
+    /// Input:
+    /// - `pdu`:            NAS message
+    ///
+    /// Output:
+    /// - [`SecurityHeaderType`]:   security header type
+    /// - [`Vec<u8>`]:              plain NAS message
+    ///
+    /// # Errors
+    /// + Returns [`NasError::InvalidHeader`] if the header is malformed.
+    /// + Returns [`NasError::UnsupportedHeaderType`] if the security header type is unknown.
+    /// + Returns [`NasError::ContextInUse`] if a new context header type is received after the first message.
+    /// + Returns [`NasError::CountWrap`] if NAS COUNT is exhausted.
+    /// + Returns [`NasError::Replay`] if the message has already been accepted.
+    /// + Returns [`NasError::IntegrityFailure`] if MAC verification fails.
+    ///
+    /// # Panics
+    /// + Panics if the message bit length is greater than `u32::MAX`.
+    pub fn unprotect(&mut self, pdu: &[u8]) -> Result<(SecurityHeaderType, Vec<u8>), NasError> {
+        let system = self.config.system;
+        let header_type = SecurityHeaderType::of_message(system, pdu)?;
+
+        if header_type == SecurityHeaderType::Plain {
+            return Ok((header_type, pdu.to_vec()));
+        }
+        if pdu.len() < system.header_len() {
+            return Err(NasError::InvalidHeader);
+        }
+
+        let mac_offset = system.header_len() - 5;
+        let mac = &pdu[mac_offset..mac_offset + 4];
+        let body = &pdu[mac_offset + 4..];
+        let sqn = u32::from(body[0]);
+
+        let direction = self.config.direction.reverse();
+
+        let count = match (header_type.is_new_context(), self.rx_count) {
+            (true, Some(_)) => {
+                return Err(if self.mac(direction, sqn, body) == mac {
+                    NasError::Replay { count: sqn }
+                } else {
+                    NasError::ContextInUse
+                });
+            }
+            (_, None) => sqn,
+            (false, Some(last)) => {
+                let count = (last & !0xff) | sqn;
+                if count > last {
+                    count
+                } else {
+                    count + 0x100
+                }
+            }
+        };
+        if count >= COUNT_LIMIT {
+            return Err(NasError::CountWrap);
+        }
+
+        if self.mac(direction, count, body) != mac {
+            if count >= 0x100 && self.mac(direction, count - 0x100, body) == mac {
+                return Err(NasError::Replay {
+                    count: count - 0x100,
+                });
+            }
+            return Err(NasError::IntegrityFailure { count });
+        }
+        self.rx_count = Some(count);
+
+        let message = if header_type.is_ciphered() {
+            self.cipher(direction, count, &body[1..])
+        } else {
+            body[1..].to_vec()
+        };
+        Ok((header_type, message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use const_str::hex;
+
+    const K_NAS_ENC: [u8; 16] = hex!("d4 55 2a 8f d6 e6 1c c8 1a 20 09 14 1a 29 c1 0b");
+    const K_NAS_INT: [u8; 16] = hex!("6b 8b 08 ee 79 e0 b5 98 2d 6d 12 8e a9 f2 20 cb");
+
+    fn pair(system: NasSystem) -> (NasSecurityContext, NasSecurityContext) {
+        let config = NasConfig {
+            system,
+            direction: Direction::Uplink,
+            bearer: 0,
+            ciphering_key: Some(K_NAS_ENC),
+            integrity_key: K_NAS_INT,
+        };
+        let ue = NasSecurityContext::new(config.clone());
+        let amf = NasSecurityContext::new(NasConfig {
+            direction: Direction::Downlink,
+            ..config
+        });
+        (ue, amf)
+    }
+
+    /// Registration request (plain 5GMM message)
+    static REGISTRATION_REQUEST: &[u8] =
+        &hex!("7e 00 41 79 00 0d 01 09 f1 07 00 00 00 00 00 00 00 00 10");
+
+    #[test]
+    fn layout_5gs() {
+        let (mut ue, _) = pair(NasSystem::FiveGs);
+        let msg = REGISTRATION_REQUEST;
+
+        let ciphered = eea3_128_encrypt(0, 0, 0, &K_NAS_ENC, bit_length(msg.len()), msg);
+        let mut body = vec![0x00];
+        body.extend_from_slice(&ciphered);
+        let mac = eia3_128_generate_mac(0, 0, 0, &K_NAS_INT, bit_length(body.len()), &body);
+
+        let mut expected = vec![0x7e, 0x04];
+        expected.extend_from_slice(&mac.to_be_bytes());
+        expected.extend_from_slice(&body);
+
+        let header_type = SecurityHeaderType::IntegrityProtectedAndCipheredWithNewContext;
+        assert_eq!(ue.protect(header_type, msg).unwrap(), expected);
+        assert_eq!(ue.tx_count(), 1);
+    }
+
+    #[test]
+    fn layout_eps() {
+        let (_, mut mme) = pair(NasSystem::Eps);
+        let msg = &hex!("07 5d 11 00 02 e0 e0");
+
+        let mut body = vec![0x00];
+        body.extend_from_slice(msg);
+        let mac = eia3_128_generate_mac(0, 0, 1, &K_NAS_INT, bit_length(body.len()), &body);
+
+        let mut expected = vec![0x37];
+        expected.extend_from_slice(&mac.to_be_bytes());
+        expected.extend_from_slice(&body);
+
+        let header_type = SecurityHeaderType::IntegrityProtectedWithNewContext;
+        assert_eq!(mme.protect(header_type, msg).unwrap(), expected);
+    }
+
+    #[test]
+    fn round_trip() {
+        for system in [NasSystem::Eps, NasSystem::FiveGs] {
+            let (mut ue, mut amf) = pair(system);
+
+            let smc = amf
+                .protect(SecurityHeaderType::IntegrityProtectedWithNewContext, b"smc")
+                .unwrap();
+            let (t, msg) = ue.unprotect(&smc).unwrap();
+            assert_eq!(t, SecurityHeaderType::IntegrityProtectedWithNewContext);
+            assert_eq!(msg, b"smc");
+
+            let complete = ue
+                .protect(
+                    SecurityHeaderType::IntegrityProtectedAndCipheredWithNewContext,
+                    b"smc complete",
+                )
+                .unwrap();
+            assert_eq!(amf.unprotect(&complete).unwrap().1, b"smc complete");
+
+            for i in 1..600_u32 {
+                let dl = amf
+                    .protect(
+                        SecurityHeaderType::IntegrityProtectedAndCiphered,
+                        &i.to_be_bytes(),
+                    )
+                    .unwrap();
+                assert_eq!(ue.unprotect(&dl).unwrap().1, i.to_be_bytes());
+            }
+            assert_eq!(amf.tx_count(), 600);
+            assert_eq!(ue.rx_count(), Some(599));
+            assert_eq!(ue.tx_count(), 1);
+            assert_eq!(amf.rx_count(), Some(0));
+
+            let plain = &[0x07, 0x41, 0x01];
+            let plain = if system == NasSystem::FiveGs {
+                REGISTRATION_REQUEST
+            } else {
+                plain
+            };
+            assert_eq!(
+                amf.unprotect(plain).unwrap(),
+                (SecurityHeaderType::Plain, plain.to_vec())
+            );
+        }
+    }
+
+    #[test]
+    fn lost_messages() {
+        let (mut ue, mut amf) = pair(NasSystem::FiveGs);
+        let header_type = SecurityHeaderType::IntegrityProtectedAndCiphered;
+
+        let first = amf.protect(header_type, b"0").unwrap();
+        assert!(ue.unprotect(&first).is_ok());
+
+        // a whole SQN cycle is lost except the last message
+        for _ in 1..300 {
+            amf.protect(header_type, b"lost").unwrap();
+        }
+        let dl = amf.protect(header_type, b"300").unwrap();
+        assert_eq!(
+            ue.unprotect(&dl),
+            Err(NasError::IntegrityFailure { count: 300 - 256 })
+        );
+
+        // gaps within one SQN cycle are recovered
+        let dl = amf.protect(header_type, b"301").unwrap();
+        let (mut ue, _) = pair(NasSystem::FiveGs);
+        assert!(ue.unprotect(&first).is_ok());
+        let mut ue2 = ue.clone();
+        assert_eq!(
+            ue2.unprotect(&dl),
+            Err(NasError::IntegrityFailure { count: 301 - 256 })
+        );
+        ue.rx_count = Some(290);
+        assert_eq!(ue.unprotect(&dl).unwrap().1, b"301");
+        assert_eq!(ue.rx_count(), Some(301));
+    }
+
+    #[test]
+    fn replay() {
+        let (mut ue, mut amf) = pair(NasSystem::Eps);
+
+        let smc = amf
+            .protect(SecurityHeaderType::IntegrityProtectedWithNewContext, b"smc")
+            .unwrap();
+        assert!(ue.unprotect(&smc).is_ok());
+        assert_eq!(ue.unprotect(&smc), Err(NasError::Replay { count: 0 }));
+
+        let dl = amf
+            .protect(SecurityHeaderType::IntegrityProtected, b"dl")
+            .unwrap();
+        assert!(ue.unprotect(&dl).is_ok());
+        assert_eq!(ue.unprotect(&dl), Err(NasError::Replay { count: 1 }));
+
+        assert_eq!(
+            amf.protect(SecurityHeaderType::IntegrityProtectedWithNewContext, b"smc"),
+            Err(NasError::ContextInUse)
+        );
+    }
+
+    #[test]
+    fn tampering() {
+        let (mut ue, mut amf) = pair(NasSystem::FiveGs);
+
+        let mut dl = amf
+            .protect(SecurityHeaderType::IntegrityProtectedAndCiphered, b"hello")
+            .unwrap();
+        dl[8] ^= 0x80;
+        assert_eq!(
+            ue.unprotect(&dl),
+            Err(NasError::IntegrityFailure { count: 0 })
+        );
+        assert_eq!(ue.rx_count(), None);
+
+        assert_eq!(ue.unprotect(&dl[..6]), Err(NasError::InvalidHeader));
+        assert_eq!(ue.unprotect(&[0x11, 0x02]), Err(NasError::InvalidHeader));
+        assert_eq!(
+            ue.unprotect(&[0x2e, 0x01, 0x01, 0xc1]).unwrap(),
+            (SecurityHeaderType::Plain, vec![0x2e, 0x01, 0x01, 0xc1])
+        );
+        assert_eq!(
+            ue.unprotect(&[0x7e, 0x0c]),
+            Err(NasError::UnsupportedHeaderType(12))
+        );
+        assert_eq!(
+            ue.protect(SecurityHeaderType::Plain, b""),
+            Err(NasError::UnsupportedHeaderType(0))
+        );
+    }
+
+    #[test]
+    fn count_wrap() {
+        let (mut ue, mut amf) = pair(NasSystem::Eps);
+        amf.tx_count = COUNT_LIMIT - 1;
+        ue.rx_count = Some(COUNT_LIMIT - 2);
+
+        let dl = amf
+            .protect(SecurityHeaderType::IntegrityProtected, b"last")
+            .unwrap();
+        assert_eq!(ue.unprotect(&dl).unwrap().1, b"last");
+        assert_eq!(
+            amf.protect(SecurityHeaderType::IntegrityProtected, b"next"),
+            Err(NasError::CountWrap)
+        );
+
+        let mut amf = amf.clone();
+        amf.tx_count = 0xff;
+        let dl = amf
+            .protect(SecurityHeaderType::IntegrityProtected, b"wrap")
+            .unwrap();
+        assert_eq!(ue.unprotect(&dl), Err(NasError::CountWrap));
+    }
+
+    #[test]
+    fn null_ciphering() {
+        let (mut ue, mut amf) = pair(NasSystem::FiveGs);
+        ue.config.ciphering_key = None;
+        amf.config.ciphering_key = None;
+
+        let ul = ue
+            .protect(
+                SecurityHeaderType::IntegrityProtectedAndCiphered,
+                b"plain text",
+            )
+            .unwrap();
+        assert_eq!(&ul[6..], b"\x00plain text");
+        assert_eq!(amf.unprotect(&ul).unwrap().1, b"plain text");
+    }
+}
@@ -0,0 +1,276 @@
+//! Parallel chunked encryption and MAC generation
+//!
+//! A ZUC keystream is sequential, so a long message is split into fixed-size chunks
+//! which are processed independently on the rayon thread pool.
+//! Chunk `i`, counted from zero, uses the ZUC-256 IV made of the 120-bit caller nonce
+//! followed by the 64-bit big-endian index, with a fresh keystream.
+//! The chunking is fixed by `chunk_size` alone, so the output does not depend on
+//! the number of threads.
+//!
+//! The last chunk may be shorter than `chunk_size`.
+//! Nonces must not be reused under the same key. Since the index has its own IV
+//! bytes, distinct nonces never share chunk IVs.
+
+use crate::zuc256::Zuc256;
+use crate::zuc256_mac::private::MacWord;
+use crate::zuc256_mac::zuc256_generate_mac;
+
+use std::io::{self, Read, Write};
+
+use cipher::{KeyIvInit, StreamCipher};
+use rayon::prelude::*;
+
+/// Number of chunks per thread read at once by [`par_encrypt_stream`]
+const CHUNKS_PER_THREAD: usize = 4;
+
+/// Maximum number of bytes read at once by [`par_encrypt_stream`], unless a chunk is larger
+const BATCH_BYTES: usize = 64 * 1024 * 1024;
+
+/// IV of chunk `index`: `nonce` followed by the 64-bit big-endian index
+fn chunk_iv(nonce: &[u8; 15], index: u64) -> [u8; 23] {
+    let mut iv = [0; 23];
+    iv[..15].copy_from_slice(nonce);
+    iv[15..].copy_from_slice(&index.to_be_bytes());
+    iv
+}
+
+/// Applies the chunked ZUC-256 keystream to `data` in parallel
+///
+/// Encryption and decryption are the same operation.
+///
+/// Input:
+/// - `key`:          256bit  encryption key
+/// - `nonce`:        120bit  nonce
+/// - `chunk_size`:   bytes per chunk
+/// - `data`:         the message, encrypted in place
+///
+/// # Panics
+/// + Panics if `chunk_size` is zero.
+pub fn par_apply_keystream(key: &[u8; 32], nonce: &[u8; 15], chunk_size: usize, data: &mut [u8]) {
+    par_apply_keystream_from(key, nonce, chunk_size, 0, data);
+}
+
+/// Applies the keystream of the chunks starting at chunk `first`
+fn par_apply_keystream_from(
+    key: &[u8; 32],
+    nonce: &[u8; 15],
+    chunk_size: usize,
+    first: u64,
+    data: &mut [u8],
+) {
+    assert!(chunk_size > 0, "`chunk_size` is zero");
+    data.par_chunks_mut(chunk_size)
+        .enumerate()
+        .for_each(|(i, chunk)| {
+            let index = first + i as u64;
+            let mut zuc = Zuc256::new(key.into(), &chunk_iv(nonce, index).into());
+            zuc.apply_keystream(chunk);
+        });
+}
+
+/// Generates the ZUC-256 MAC of each chunk of `data` in parallel
+///
+/// Input:
+/// - `<T>`:          u32/u64/u128    MAC type
+/// - `ik`:           256bit  integrity key
+/// - `nonce`:        120bit  nonce
+/// - `chunk_size`:   bytes per chunk
+/// - `data`:         the message
+///
+/// Output:
+/// - `Vec<T>`:   the MAC of each chunk, in order
+///
+/// # Panics
+/// + Panics if `chunk_size` is zero.
+/// + Panics if the bit length of a chunk is greater than `u32::MAX`.
+#[must_use]
+pub fn par_generate_macs<T>(
+    ik: &[u8; 32],
+    nonce: &[u8; 15],
+    chunk_size: usize,
+    data: &[u8],
+) -> Vec<T>
+where
+    T: MacWord + Send,
+{
+    assert!(chunk_size > 0, "`chunk_size` is zero");
+    data.par_chunks(chunk_size)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let bitlen = u32::try_from(chunk.len() * 8).expect("chunk is too long");
+            zuc256_generate_mac::<T>(ik, &chunk_iv(nonce, i as u64), bitlen, chunk)
+        })
+        .collect()
+}
+
+/// Encrypts a stream with the chunked ZUC-256 keystream
+///
+/// Reads a batch of chunks for every thread at a time, up to 64 MiB or a single chunk
+/// if it is larger, so large files are processed in bounded memory.
+/// The buffer grows with the data read, not with `chunk_size`.
+/// The output is the same as [`par_apply_keystream`] over the whole input.
+///
+/// Input:
+/// - `key`:          256bit  encryption key
+/// - `nonce`:        120bit  nonce
+/// - `chunk_size`:   bytes per chunk
+/// - `reader`:       the input
+/// - `writer`:       the output
+///
+/// Output:
+/// - `u64`:  the number of bytes processed
+///
+/// # Errors
+/// + Returns the I/O errors of `reader` and `writer`.
+///
+/// # Panics
+/// + Panics if `chunk_size` is zero.
+pub fn par_encrypt_stream<R: Read, W: Write>(
+    key: &[u8; 32],
+    nonce: &[u8; 15],
+    chunk_size: usize,
+    mut reader: R,
+    mut writer: W,
+) -> io::Result<u64> {
+    assert!(chunk_size > 0, "`chunk_size` is zero");
+    let batch_chunks = rayon::current_num_threads()
+        .saturating_mul(CHUNKS_PER_THREAD)
+        .min(BATCH_BYTES / chunk_size)
+        .max(1);
+    let batch_len = chunk_size
+        .checked_mul(batch_chunks)
+        .expect("batch is at most one chunk or `BATCH_BYTES`");
+    let mut buf = Vec::new();
+    let mut first = 0;
+    let mut total = 0;
+    loop {
+        buf.clear();
+        let n = reader
+            .by_ref()
+            .take(batch_len as u64)
+            .read_to_end(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        par_apply_keystream_from(key, nonce, chunk_size, first, &mut buf);
+        writer.write_all(&buf)?;
+        total += n as u64;
+        if n < batch_len {
+            break;
+        }
+        first += batch_chunks as u64;
+    }
+    writer.flush()?;
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static KEY: [u8; 32] = [0x11; 32];
+    static NONCE: [u8; 15] = [0x22; 15];
+
+    fn data(len: usize) -> Vec<u8> {
+        (0..=u8::MAX).cycle().take(len).collect()
+    }
+
+    fn with_threads<T: Send>(n: usize, f: impl FnOnce() -> T + Send) -> T {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(n)
+            .build()
+            .unwrap();
+        pool.install(f)
+    }
+
+    #[test]
+    fn keystream() {
+        let plain = data(10_000);
+        for chunk_size in [1, 7, 64, 4096, 20_000] {
+            let mut expected = plain.clone();
+            for (index, chunk) in (0..).zip(expected.chunks_mut(chunk_size)) {
+                let mut zuc = Zuc256::new(&KEY.into(), &chunk_iv(&NONCE, index).into());
+                zuc.apply_keystream(chunk);
+            }
+
+            for threads in [1, 2, 5] {
+                let mut buf = plain.clone();
+                with_threads(threads, || {
+                    par_apply_keystream(&KEY, &NONCE, chunk_size, &mut buf);
+                });
+                assert_eq!(buf, expected);
+
+                let mut out = Vec::new();
+                let n = with_threads(threads, || {
+                    par_encrypt_stream(&KEY, &NONCE, chunk_size, &plain[..], &mut out).unwrap()
+                });
+                assert_eq!(n, 10_000);
+                assert_eq!(out, expected);
+            }
+
+            par_apply_keystream(&KEY, &NONCE, chunk_size, &mut expected);
+            assert_eq!(expected, plain);
+        }
+    }
+
+    #[test]
+    fn distinct_nonces() {
+        // nonce || 64-bit big-endian index
+        let iv = chunk_iv(&NONCE, 0x0102_0304_0506_0708);
+        assert_eq!(iv[..15], NONCE);
+        assert_eq!(iv[15..], [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(chunk_iv(&NONCE, u64::MAX)[..15], NONCE);
+
+        // nonces differing only next to the index never share chunk IVs
+        let mut other = NONCE;
+        other[14] ^= 1;
+        for i in [0, 1, 0x100, u64::MAX] {
+            for j in [0, 1, 0x100, u64::MAX] {
+                assert_ne!(chunk_iv(&NONCE, i), chunk_iv(&other, j));
+            }
+        }
+        let mut a = vec![0; 128];
+        let mut b = vec![0; 128];
+        par_apply_keystream(&KEY, &NONCE, 64, &mut a);
+        par_apply_keystream(&KEY, &other, 64, &mut b);
+        assert_ne!(a[64..], b[..64]);
+        assert_ne!(a[..64], b[64..]);
+    }
+
+    #[test]
+    fn huge_chunks() {
+        let plain = data(1000);
+        let mut expected = plain.clone();
+        par_apply_keystream(&KEY, &NONCE, 1000, &mut expected);
+
+        for chunk_size in [usize::MAX, usize::MAX / 3, BATCH_BYTES / 3 + 1] {
+            let mut out = Vec::new();
+            let n = with_threads(64, || {
+                par_encrypt_stream(&KEY, &NONCE, chunk_size, &plain[..], &mut out).unwrap()
+            });
+            assert_eq!(n, 1000);
+            assert_eq!(out, expected);
+        }
+    }
+
+    #[test]
+    fn macs() {
+        let plain = data(1000);
+        let expected: Vec<u64> = (0..)
+            .zip(plain.chunks(300))
+            .map(|(index, chunk)| {
+                let bitlen = u32::try_from(chunk.len() * 8).unwrap();
+                zuc256_generate_mac::<u64>(&KEY, &chunk_iv(&NONCE, index), bitlen, chunk)
+            })
+            .collect();
+        assert_eq!(expected.len(), 4);
+        for threads in [1, 3] {
+            let macs = with_threads(threads, || {
+                par_generate_macs::<u64>(&KEY, &NONCE, 300, &plain)
+            });
+            assert_eq!(macs, expected);
+        }
+        let macs: Vec<u128> = par_generate_macs(&KEY, &NONCE, 300, &plain);
+        assert_eq!(macs.len(), 4);
+    }
+}
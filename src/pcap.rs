@@ -0,0 +1,1457 @@
+//! Offline decryption of NAS and PDCP captures
+//!
+//! Reads a pcap or pcapng capture, finds NAS and PDCP messages carried over UDP,
+//! removes 128-EEA3 ciphering, verifies 128-EIA3 MACs and writes a pcap capture
+//! with the plain messages.
+//!
+//! # Framing
+//!
+//! The framing is specific to this crate. It is modelled on the tag-based UDP
+//! framing of Wireshark's PDCP and NAS heuristic dissectors, but is not checked
+//! against them: captures framed by other tools may not be recognised.
+//!
+//! Messages are recognised by a signature at the start of the UDP payload,
+//! on any port. The signature is followed by fixed fields and tags,
+//! up to the payload tag, after which the rest of the datagram is the message.
+//!
+//! PDCP uses this layout:
+//!
+//! | field             | bytes | value                                     |
+//! |-------------------|-------|-------------------------------------------|
+//! | signature         | 7     | `pdcp-nr`                                 |
+//! | plane             | 1     | 1 signalling (SRB), 2 user (DRB)          |
+//! | tag `0x02`        | 1     | SN length, 12 or 18 (12 for SRBs)         |
+//! | tag `0x03`        | 1     | direction, 0 uplink, 1 downlink           |
+//! | tag `0x04`        | 1     | bearer type, ignored                      |
+//! | tag `0x05`        | 1     | radio bearer identity, 1 to 32            |
+//! | tag `0x06`        | 2     | UE identifier, big-endian                 |
+//! | tag `0x0f`        | 0     | `MAC-I` present on a DRB                  |
+//! | tag `0x01`        | 0     | payload follows                           |
+//!
+//! NAS uses the same layout with the signatures `nas-eps` and `nas-5gs`,
+//! no fixed fields, and the tags `0x03` (direction), `0x06` (UE identifier)
+//! and `0x01` (payload).
+//!
+//! Unknown tags stop the parsing and the datagram is copied unchanged.
+//! Missing direction tags default to uplink, missing identifiers to zero.
+//!
+//! # Security contexts
+//!
+//! One [`NasSecurityContext`] is kept per UE, NAS system and direction,
+//! one [`PdcpSecurity`] per UE, plane, radio bearer and direction,
+//! so COUNTs are tracked per bearer as messages are seen.
+//! The HFN of a bearer starts at zero with the first PDU in the capture.
+//! SRBs use the RRC keys and always carry `MAC-I`,
+//! DRBs use the UP keys and carry `MAC-I` only when tagged.
+//! PDUs carrying `MAC-I` need the integrity key of their plane, unless NIA0
+//! is requested with [`Keys::nia0`].
+//! Plain NAS messages are copied unchanged and need no keys.
+//!
+//! # Output
+//!
+//! The output is a classic pcap capture with microsecond timestamps
+//! and the link type of the input.
+//! Decrypted messages replace the protected ones in their datagrams:
+//! NAS messages become the plain inner message,
+//! PDCP PDUs keep their header and carry their `MAC-I` in the clear.
+//! IP and UDP lengths are updated, the IPv4 UDP checksum is cleared.
+//!
+//! Packets that fail verification, that lack keys, and packets without framing
+//! are copied unchanged.
+//!
+//! Supported link types are Ethernet (with VLAN tags), raw IP, Linux cooked
+//! capture v1 and v2, and BSD loopback. Fragmented IPv4 and IPv6 extension
+//! headers are not supported.
+
+use crate::nas::{NasConfig, NasError, NasSecurityContext, NasSystem, SecurityHeaderType};
+use crate::pdcp::{bearer_from_rb_identity, PdcpConfig, PdcpError, PdcpSecurity, SnLength};
+use crate::{eia3_128_generate_mac, Direction};
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// PDCP framing signature
+const PDCP_NR_SIGNATURE: &[u8] = b"pdcp-nr";
+/// EPS NAS framing signature
+const NAS_EPS_SIGNATURE: &[u8] = b"nas-eps";
+/// 5GS NAS framing signature
+const NAS_5GS_SIGNATURE: &[u8] = b"nas-5gs";
+
+/// payload follows
+const PAYLOAD_TAG: u8 = 0x01;
+/// SN length
+const SN_LENGTH_TAG: u8 = 0x02;
+/// direction
+const DIRECTION_TAG: u8 = 0x03;
+/// bearer type
+const BEARER_TYPE_TAG: u8 = 0x04;
+/// radio bearer identity
+const BEARER_ID_TAG: u8 = 0x05;
+/// UE identifier
+const UE_ID_TAG: u8 = 0x06;
+/// `MAC-I` present
+const MAC_I_PRESENT_TAG: u8 = 0x0f;
+
+/// pcap magic number, microsecond timestamps
+const PCAP_MAGIC_US: u32 = 0xa1b2_c3d4;
+/// pcap magic number, nanosecond timestamps
+const PCAP_MAGIC_NS: u32 = 0xa1b2_3c4d;
+/// pcapng section header block type
+const PCAPNG_SHB: u32 = 0x0a0d_0d0a;
+/// pcapng byte-order magic
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+/// pcapng interface description block type
+const PCAPNG_IDB: u32 = 1;
+/// pcapng simple packet block type
+const PCAPNG_SPB: u32 = 3;
+/// pcapng enhanced packet block type
+const PCAPNG_EPB: u32 = 6;
+
+/// Ethernet link type
+const LINKTYPE_ETHERNET: u32 = 1;
+/// BSD loopback link type
+const LINKTYPE_NULL: u32 = 0;
+/// Raw IP link type
+const LINKTYPE_RAW: u32 = 101;
+/// Linux cooked capture link type
+const LINKTYPE_LINUX_SLL: u32 = 113;
+/// Raw IPv4 link type
+const LINKTYPE_IPV4: u32 = 228;
+/// Raw IPv6 link type
+const LINKTYPE_IPV6: u32 = 229;
+/// Linux cooked capture v2 link type
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+/// Capture errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PcapError {
+    /// The input is neither a pcap nor a pcapng capture
+    UnknownFormat,
+    /// The capture ends in the middle of a header or block
+    Truncated,
+    /// A packet refers to an undefined pcapng interface
+    UnknownInterface(u32),
+    /// The pcapng interfaces have different link types
+    MixedLinkTypes,
+}
+
+impl fmt::Display for PcapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PcapError::UnknownFormat => write!(f, "unknown capture format"),
+            PcapError::Truncated => write!(f, "truncated capture"),
+            PcapError::UnknownInterface(id) => write!(f, "unknown pcapng interface {id}"),
+            PcapError::MixedLinkTypes => write!(f, "pcapng interfaces have different link types"),
+        }
+    }
+}
+
+impl std::error::Error for PcapError {}
+
+/// Keys used to decrypt a capture
+///
+/// A missing ciphering key means NEA0.
+/// Protected messages of a kind whose integrity key is missing are copied unchanged,
+/// except DRBs without `MAC-I` and, with [`Keys::nia0`], PDCP PDUs.
+#[derive(Clone, Default)]
+pub struct Keys {
+    /// `K_NASenc`
+    pub nas_enc: Option<[u8; 16]>,
+    /// `K_NASint`
+    pub nas_int: Option<[u8; 16]>,
+    /// `K_RRCenc`
+    pub rrc_enc: Option<[u8; 16]>,
+    /// `K_RRCint`
+    pub rrc_int: Option<[u8; 16]>,
+    /// `K_UPenc`
+    pub up_enc: Option<[u8; 16]>,
+    /// `K_UPint`
+    pub up_int: Option<[u8; 16]>,
+    /// Checks PDCP `MAC-I` against NIA0, all zeros, when `K_RRCint` or `K_UPint` is missing
+    pub nia0: bool,
+}
+
+impl fmt::Debug for Keys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Keys { ... }")
+    }
+}
+
+/// PDCP plane
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Plane {
+    /// Signalling radio bearers
+    Signalling = 1,
+    /// Data radio bearers
+    User = 2,
+}
+
+/// A PDCP PDU in `pdcp-nr` framing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PdcpFrame {
+    /// UE identifier
+    pub ue_id: u16,
+    /// Plane
+    pub plane: Plane,
+    /// SN length
+    pub sn_length: SnLength,
+    /// Transmitting direction
+    pub direction: Direction,
+    /// Radio bearer identity, 1 to 32
+    pub rb_identity: u8,
+    /// Whether the PDU carries `MAC-I`
+    pub mac_i_present: bool,
+    /// PDCP PDU
+    pub pdu: Vec<u8>,
+}
+
+/// A NAS message in `nas-eps` or `nas-5gs` framing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NasFrame {
+    /// UE identifier
+    pub ue_id: u16,
+    /// NAS system
+    pub system: NasSystem,
+    /// Transmitting direction
+    pub direction: Direction,
+    /// NAS message
+    pub message: Vec<u8>,
+}
+
+/// A framed message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    /// PDCP PDU
+    Pdcp(PdcpFrame),
+    /// NAS message
+    Nas(NasFrame),
+}
+
+/// Decodes a direction byte
+fn direction_from_u8(value: u8) -> Option<Direction> {
+    match value {
+        0 => Some(Direction::Uplink),
+        1 => Some(Direction::Downlink),
+        _ => None,
+    }
+}
+
+impl Frame {
+    /// Decodes a UDP payload, returns `None` if it is not framed
+    #[must_use]
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        let (nas_system, mut rest) = if let Some(rest) = payload.strip_prefix(PDCP_NR_SIGNATURE) {
+            (None, rest)
+        } else if let Some(rest) = payload.strip_prefix(NAS_EPS_SIGNATURE) {
+            (Some(NasSystem::Eps), rest)
+        } else if let Some(rest) = payload.strip_prefix(NAS_5GS_SIGNATURE) {
+            (Some(NasSystem::FiveGs), rest)
+        } else {
+            return None;
+        };
+
+        let plane = if nas_system.is_none() {
+            let (&plane, tail) = rest.split_first()?;
+            rest = tail;
+            match plane {
+                1 => Some(Plane::Signalling),
+                2 => Some(Plane::User),
+                _ => return None,
+            }
+        } else {
+            None
+        };
+
+        let mut sn_length = SnLength::Bits12;
+        let mut direction = Direction::Uplink;
+        let mut rb_identity = None;
+        let mut ue_id = 0;
+        let mut mac_i_present = false;
+
+        loop {
+            let (&tag, tail) = rest.split_first()?;
+            rest = tail;
+            let value_len = match tag {
+                PAYLOAD_TAG => break,
+                MAC_I_PRESENT_TAG => 0,
+                SN_LENGTH_TAG | DIRECTION_TAG | BEARER_TYPE_TAG | BEARER_ID_TAG => 1,
+                UE_ID_TAG => 2,
+                _ => return None,
+            };
+            if rest.len() < value_len {
+                return None;
+            }
+            let (value, tail) = rest.split_at(value_len);
+            rest = tail;
+            match tag {
+                SN_LENGTH_TAG => {
+                    sn_length = match value[0] {
+                        12 => SnLength::Bits12,
+                        18 => SnLength::Bits18,
+                        _ => return None,
+                    };
+                }
+                DIRECTION_TAG => direction = direction_from_u8(value[0])?,
+                BEARER_ID_TAG => rb_identity = Some(value[0]),
+                UE_ID_TAG => ue_id = u16::from_be_bytes([value[0], value[1]]),
+                MAC_I_PRESENT_TAG => mac_i_present = true,
+                _ => {}
+            }
+        }
+
+        match (nas_system, plane) {
+            (Some(system), _) => Some(Frame::Nas(NasFrame {
+                ue_id,
+                system,
+                direction,
+                message: rest.to_vec(),
+            })),
+            (None, Some(plane)) => {
+                let rb_identity = rb_identity.filter(|rb| (1..=32).contains(rb))?;
+                if plane == Plane::Signalling && sn_length != SnLength::Bits12 {
+                    return None;
+                }
+                Some(Frame::Pdcp(PdcpFrame {
+                    ue_id,
+                    plane,
+                    sn_length,
+                    direction,
+                    rb_identity,
+                    mac_i_present: mac_i_present || plane == Plane::Signalling,
+                    pdu: rest.to_vec(),
+                }))
+            }
+            (None, None) => None,
+        }
+    }
+
+    /// Encodes the frame as a UDP payload
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut v = Vec::new();
+        match self {
+            Frame::Pdcp(f) => {
+                v.extend_from_slice(PDCP_NR_SIGNATURE);
+                v.push(f.plane as u8);
+                let sn_bits = match f.sn_length {
+                    SnLength::Bits12 => 12,
+                    SnLength::Bits18 => 18,
+                };
+                v.extend_from_slice(&[SN_LENGTH_TAG, sn_bits]);
+                v.extend_from_slice(&[DIRECTION_TAG, f.direction.bit()]);
+                v.extend_from_slice(&[BEARER_ID_TAG, f.rb_identity]);
+                v.push(UE_ID_TAG);
+                v.extend_from_slice(&f.ue_id.to_be_bytes());
+                if f.mac_i_present && f.plane == Plane::User {
+                    v.push(MAC_I_PRESENT_TAG);
+                }
+                v.push(PAYLOAD_TAG);
+                v.extend_from_slice(&f.pdu);
+            }
+            Frame::Nas(f) => {
+                v.extend_from_slice(match f.system {
+                    NasSystem::Eps => NAS_EPS_SIGNATURE,
+                    NasSystem::FiveGs => NAS_5GS_SIGNATURE,
+                });
+                v.extend_from_slice(&[DIRECTION_TAG, f.direction.bit()]);
+                v.push(UE_ID_TAG);
+                v.extend_from_slice(&f.ue_id.to_be_bytes());
+                v.push(PAYLOAD_TAG);
+                v.extend_from_slice(&f.message);
+            }
+        }
+        v
+    }
+}
+
+/// Why a message was not decrypted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    /// NAS security error
+    Nas(NasError),
+    /// PDCP security error
+    Pdcp(PdcpError),
+    /// The keys for this message were not given
+    MissingKey,
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailureKind::Nas(e) => write!(f, "NAS: {e}"),
+            FailureKind::Pdcp(e) => write!(f, "PDCP: {e}"),
+            FailureKind::MissingKey => write!(f, "missing key"),
+        }
+    }
+}
+
+/// A message that was copied unchanged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Failure {
+    /// Packet number in the capture, starting at 1
+    pub packet: usize,
+    /// UE identifier
+    pub ue_id: u16,
+    /// Transmitting direction
+    pub direction: Direction,
+    /// Radio bearer identity, `None` for NAS
+    pub rb_identity: Option<u8>,
+    /// Reason
+    pub kind: FailureKind,
+}
+
+/// Decryption report
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    /// Number of packets in the capture
+    pub packets: usize,
+    /// Number of NAS messages found
+    pub nas_messages: usize,
+    /// Number of PDCP PDUs found
+    pub pdcp_pdus: usize,
+    /// Number of messages decrypted and verified
+    pub decrypted: usize,
+    /// Messages copied unchanged
+    pub failures: Vec<Failure>,
+}
+
+impl Report {
+    /// Number of integrity failures
+    #[must_use]
+    pub fn integrity_failures(&self) -> usize {
+        self.failures
+            .iter()
+            .filter(|x| {
+                matches!(
+                    x.kind,
+                    FailureKind::Nas(NasError::IntegrityFailure { .. })
+                        | FailureKind::Pdcp(PdcpError::IntegrityFailure { .. })
+                )
+            })
+            .count()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "packets:            {}", self.packets)?;
+        writeln!(f, "NAS messages:       {}", self.nas_messages)?;
+        writeln!(f, "PDCP PDUs:          {}", self.pdcp_pdus)?;
+        writeln!(f, "decrypted:          {}", self.decrypted)?;
+        writeln!(f, "integrity failures: {}", self.integrity_failures())?;
+        for x in &self.failures {
+            write!(f, "packet {}: UE {} {:?}", x.packet, x.ue_id, x.direction)?;
+            if let Some(rb) = x.rb_identity {
+                write!(f, " RB {rb}")?;
+            }
+            writeln!(f, ": {}", x.kind)?;
+        }
+        Ok(())
+    }
+}
+
+/// A captured packet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet {
+    /// Seconds since the epoch
+    pub ts_sec: u32,
+    /// Microseconds
+    pub ts_usec: u32,
+    /// Original length on the wire
+    pub orig_len: u32,
+    /// Captured bytes
+    pub data: Vec<u8>,
+}
+
+/// A parsed capture
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capture {
+    /// Link type
+    pub link_type: u32,
+    /// Packets
+    pub packets: Vec<Packet>,
+}
+
+/// Little or big endian reader
+#[derive(Clone, Copy)]
+struct Endian {
+    /// big endian
+    big: bool,
+}
+
+impl Endian {
+    /// Reads a `u16`
+    fn u16(self, b: &[u8], at: usize) -> Result<u16, PcapError> {
+        let b: [u8; 2] = b
+            .get(at..at + 2)
+            .ok_or(PcapError::Truncated)?
+            .try_into()
+            .unwrap();
+        Ok(if self.big {
+            u16::from_be_bytes(b)
+        } else {
+            u16::from_le_bytes(b)
+        })
+    }
+
+    /// Reads a `u32`
+    fn u32(self, b: &[u8], at: usize) -> Result<u32, PcapError> {
+        let b: [u8; 4] = b
+            .get(at..at + 4)
+            .ok_or(PcapError::Truncated)?
+            .try_into()
+            .unwrap();
+        Ok(if self.big {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        })
+    }
+}
+
+/// Converts a length field to `usize`
+fn to_usize(x: u32) -> usize {
+    usize::try_from(x).expect("32-bit length overflow")
+}
+
+impl Capture {
+    /// Parses a pcap or pcapng capture
+    ///
+    /// # Errors
+    /// + Returns [`PcapError`] if the capture is malformed.
+    pub fn parse(input: &[u8]) -> Result<Self, PcapError> {
+        let magic = match input {
+            [a, b, c, d, ..] => u32::from_le_bytes([*a, *b, *c, *d]),
+            _ => return Err(PcapError::UnknownFormat),
+        };
+        match magic {
+            PCAP_MAGIC_US | PCAP_MAGIC_NS => Self::parse_pcap(input, false, magic == PCAP_MAGIC_NS),
+            _ if magic.swap_bytes() == PCAP_MAGIC_US || magic.swap_bytes() == PCAP_MAGIC_NS => {
+                Self::parse_pcap(input, true, magic.swap_bytes() == PCAP_MAGIC_NS)
+            }
+            PCAPNG_SHB => Self::parse_pcapng(input),
+            _ => Err(PcapError::UnknownFormat),
+        }
+    }
+
+    /// Parses a classic pcap capture
+    fn parse_pcap(input: &[u8], big: bool, nanos: bool) -> Result<Self, PcapError> {
+        let e = Endian { big };
+        let link_type = e.u32(input, 20)? & 0x0fff_ffff;
+        let mut packets = Vec::new();
+        let mut pos = 24;
+        while pos < input.len() {
+            let ts_sec = e.u32(input, pos)?;
+            let ts_frac = e.u32(input, pos + 4)?;
+            let incl_len = to_usize(e.u32(input, pos + 8)?);
+            let orig_len = e.u32(input, pos + 12)?;
+            let data = input
+                .get(pos + 16..pos + 16 + incl_len)
+                .ok_or(PcapError::Truncated)?;
+            packets.push(Packet {
+                ts_sec,
+                ts_usec: if nanos { ts_frac / 1000 } else { ts_frac },
+                orig_len,
+                data: data.to_vec(),
+            });
+            pos += 16 + incl_len;
+        }
+        Ok(Self { link_type, packets })
+    }
+
+    /// Parses a pcapng capture
+    fn parse_pcapng(input: &[u8]) -> Result<Self, PcapError> {
+        // link type and timestamp units per second of each interface in the section
+        let mut interfaces: Vec<(u32, u64)> = Vec::new();
+        let mut link_type = None;
+        let mut packets = Vec::new();
+        let mut e = Endian { big: false };
+        let mut pos = 0;
+
+        while pos < input.len() {
+            let block_type = e.u32(input, pos)?;
+            if block_type == PCAPNG_SHB {
+                let bom = Endian { big: false }.u32(input, pos + 8)?;
+                e = Endian {
+                    big: bom != PCAPNG_BYTE_ORDER_MAGIC,
+                };
+                if e.u32(input, pos + 8)? != PCAPNG_BYTE_ORDER_MAGIC {
+                    return Err(PcapError::UnknownFormat);
+                }
+                interfaces.clear();
+            }
+            let block_len = to_usize(e.u32(input, pos + 4)?);
+            if block_len < 12 || block_len & 3 != 0 {
+                return Err(PcapError::Truncated);
+            }
+            let block = input
+                .get(pos..pos + block_len)
+                .ok_or(PcapError::Truncated)?;
+            let body = &block[8..block_len - 4];
+
+            match block_type {
+                PCAPNG_IDB => {
+                    let lt = u32::from(e.u16(body, 0)?);
+                    interfaces.push((lt, Self::tsresol(e, body.get(8..).unwrap_or(&[]))?));
+                    if *link_type.get_or_insert(lt) != lt {
+                        return Err(PcapError::MixedLinkTypes);
+                    }
+                }
+                PCAPNG_EPB => {
+                    let id = e.u32(body, 0)?;
+                    let &(_, units) = interfaces
+                        .get(to_usize(id))
+                        .ok_or(PcapError::UnknownInterface(id))?;
+                    let ts = (u64::from(e.u32(body, 4)?) << 32) | u64::from(e.u32(body, 8)?);
+                    let cap_len = to_usize(e.u32(body, 12)?);
+                    let orig_len = e.u32(body, 16)?;
+                    let data = body.get(20..20 + cap_len).ok_or(PcapError::Truncated)?;
+                    let ts = Self::split_timestamp(ts, units);
+                    packets.push(Packet {
+                        ts_sec: ts.0,
+                        ts_usec: ts.1,
+                        orig_len,
+                        data: data.to_vec(),
+                    });
+                }
+                PCAPNG_SPB => {
+                    if interfaces.is_empty() {
+                        return Err(PcapError::UnknownInterface(0));
+                    }
+                    let orig_len = e.u32(body, 0)?;
+                    let len = to_usize(orig_len).min(body.len() - 4);
+                    packets.push(Packet {
+                        ts_sec: 0,
+                        ts_usec: 0,
+                        orig_len,
+                        data: body[4..4 + len].to_vec(),
+                    });
+                }
+                _ => {}
+            }
+            pos += block_len;
+        }
+
+        Ok(Self {
+            link_type: link_type.unwrap_or(LINKTYPE_ETHERNET),
+            packets,
+        })
+    }
+
+    /// Reads the `if_tsresol` option, returns timestamp units per second
+    fn tsresol(e: Endian, mut options: &[u8]) -> Result<u64, PcapError> {
+        while options.len() >= 4 {
+            let code = e.u16(options, 0)?;
+            let len = usize::from(e.u16(options, 2)?);
+            if code == 0 {
+                break;
+            }
+            let value = options.get(4..4 + len).ok_or(PcapError::Truncated)?;
+            if code == 9 && len == 1 {
+                let v = u32::from(value[0] & 0x7f);
+                let units = if value[0] & 0x80 == 0 {
+                    10_u64.checked_pow(v)
+                } else {
+                    2_u64.checked_pow(v)
+                };
+                return units.ok_or(PcapError::UnknownFormat);
+            }
+            options = options.get(4 + len.next_multiple_of(4)..).unwrap_or(&[]);
+        }
+        Ok(1_000_000)
+    }
+
+    /// Splits a pcapng timestamp into seconds and microseconds
+    fn split_timestamp(ts: u64, units: u64) -> (u32, u32) {
+        let sec = u32::try_from(ts / units).unwrap_or(u32::MAX);
+        let frac = u128::from(ts % units) * 1_000_000 / u128::from(units);
+        (sec, u32::try_from(frac).unwrap_or(0))
+    }
+
+    /// Encodes the capture as classic little-endian pcap with microsecond timestamps
+    ///
+    /// # Panics
+    /// + Panics if a packet is longer than `u32::MAX`.
+    #[must_use]
+    pub fn to_pcap(&self) -> Vec<u8> {
+        let mut v = Vec::new();
+        v.extend_from_slice(&PCAP_MAGIC_US.to_le_bytes());
+        v.extend_from_slice(&2_u16.to_le_bytes());
+        v.extend_from_slice(&4_u16.to_le_bytes());
+        v.extend_from_slice(&0_i32.to_le_bytes());
+        v.extend_from_slice(&0_u32.to_le_bytes());
+        v.extend_from_slice(&262_144_u32.to_le_bytes());
+        v.extend_from_slice(&self.link_type.to_le_bytes());
+        for p in &self.packets {
+            let incl_len = u32::try_from(p.data.len()).expect("packet too long");
+            v.extend_from_slice(&p.ts_sec.to_le_bytes());
+            v.extend_from_slice(&p.ts_usec.to_le_bytes());
+            v.extend_from_slice(&incl_len.to_le_bytes());
+            v.extend_from_slice(&p.orig_len.max(incl_len).to_le_bytes());
+            v.extend_from_slice(&p.data);
+        }
+        v
+    }
+}
+
+/// Location of a UDP payload in a packet
+struct UdpLocation {
+    /// offset of the IP header
+    ip: usize,
+    /// IP version
+    version: u8,
+    /// offset of the UDP header
+    udp: usize,
+    /// end of the UDP payload
+    end: usize,
+}
+
+/// Finds the offset and version of the IP header
+fn ip_offset(link_type: u32, data: &[u8]) -> Option<usize> {
+    let ethertype_ip = |t: u16| matches!(t, 0x0800 | 0x86dd);
+    match link_type {
+        LINKTYPE_ETHERNET => {
+            let mut pos = 12;
+            loop {
+                let t = u16::from_be_bytes([*data.get(pos)?, *data.get(pos + 1)?]);
+                match t {
+                    0x8100 | 0x88a8 => pos += 4,
+                    _ if ethertype_ip(t) => return Some(pos + 2),
+                    _ => return None,
+                }
+            }
+        }
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => Some(0),
+        LINKTYPE_LINUX_SLL => {
+            ethertype_ip(u16::from_be_bytes([*data.get(14)?, *data.get(15)?])).then_some(16)
+        }
+        LINKTYPE_LINUX_SLL2 => {
+            ethertype_ip(u16::from_be_bytes([*data.first()?, *data.get(1)?])).then_some(20)
+        }
+        LINKTYPE_NULL => Some(4),
+        _ => None,
+    }
+}
+
+/// Locates the UDP payload of a packet
+fn locate_udp(link_type: u32, data: &[u8]) -> Option<UdpLocation> {
+    let ip = ip_offset(link_type, data)?;
+    let version = data.get(ip)? >> 4;
+    let (udp, ip_end) = match version {
+        4 => {
+            let ihl = usize::from(data[ip] & 0x0f) * 4;
+            let total = usize::from(u16::from_be_bytes([*data.get(ip + 2)?, *data.get(ip + 3)?]));
+            let frag = u16::from_be_bytes([*data.get(ip + 6)?, *data.get(ip + 7)?]);
+            if *data.get(ip + 9)? != 17 || frag & 0x3fff != 0 || ihl < 20 {
+                return None;
+            }
+            (ip + ihl, ip + total)
+        }
+        6 => {
+            let payload = usize::from(u16::from_be_bytes([*data.get(ip + 4)?, *data.get(ip + 5)?]));
+            if *data.get(ip + 6)? != 17 {
+                return None;
+            }
+            (ip + 40, ip + 40 + payload)
+        }
+        _ => return None,
+    };
+    let udp_len = usize::from(u16::from_be_bytes([
+        *data.get(udp + 4)?,
+        *data.get(udp + 5)?,
+    ]));
+    let end = udp + udp_len;
+    if udp_len < 8 || end > ip_end || end > data.len() {
+        return None;
+    }
+    Some(UdpLocation {
+        ip,
+        version,
+        udp,
+        end,
+    })
+}
+
+/// Internet checksum
+fn checksum(chunks: &[&[u8]]) -> u16 {
+    let mut sum = 0_u32;
+    let mut odd = None;
+    for &b in chunks.iter().flat_map(|c| c.iter()) {
+        match odd.take() {
+            None => odd = Some(b),
+            Some(hi) => sum += u32::from(u16::from_be_bytes([hi, b])),
+        }
+    }
+    if let Some(hi) = odd {
+        sum += u32::from(u16::from_be_bytes([hi, 0]));
+    }
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !u16::try_from(sum).unwrap()
+}
+
+/// Replaces the UDP payload of a packet, fixing lengths and checksums
+fn replace_udp_payload(data: &[u8], loc: &UdpLocation, payload: &[u8]) -> Option<Vec<u8>> {
+    let udp_len = u16::try_from(8 + payload.len()).ok()?;
+    let mut v = Vec::with_capacity(loc.udp + 8 + payload.len());
+    v.extend_from_slice(&data[..loc.udp + 8]);
+    v.extend_from_slice(payload);
+
+    let ip = loc.ip;
+    let udp = loc.udp;
+    v[udp + 4..udp + 6].copy_from_slice(&udp_len.to_be_bytes());
+    v[udp + 6..udp + 8].copy_from_slice(&[0, 0]);
+
+    if loc.version == 4 {
+        let total = u16::try_from(udp - ip).ok()?.checked_add(udp_len)?;
+        v[ip + 2..ip + 4].copy_from_slice(&total.to_be_bytes());
+        v[ip + 10..ip + 12].copy_from_slice(&[0, 0]);
+        let sum = checksum(&[&v[ip..udp]]);
+        v[ip + 10..ip + 12].copy_from_slice(&sum.to_be_bytes());
+    } else {
+        v[ip + 4..ip + 6].copy_from_slice(&udp_len.to_be_bytes());
+        let pseudo_len = u32::from(udp_len).to_be_bytes();
+        let sum = checksum(&[&v[ip + 8..ip + 40], &pseudo_len, &[0, 0, 0, 17], &v[udp..]]);
+        let sum = if sum == 0 { 0xffff } else { sum };
+        v[udp + 6..udp + 8].copy_from_slice(&sum.to_be_bytes());
+    }
+    Some(v)
+}
+
+/// Key of a PDCP entity
+type PdcpKey = (u16, Plane, u8, Direction);
+/// Key of a NAS context
+type NasKey = (u16, NasSystem, Direction);
+
+/// Capture decryptor
+///
+/// Keeps the security contexts of every UE and bearer seen so far.
+#[derive(Clone)]
+pub struct Decryptor {
+    /// keys
+    keys: Keys,
+    /// receiving PDCP entities
+    pdcp: HashMap<PdcpKey, PdcpSecurity>,
+    /// receiving NAS contexts
+    nas: HashMap<NasKey, NasSecurityContext>,
+}
+
+impl fmt::Debug for Decryptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Decryptor { ... }")
+    }
+}
+
+impl Decryptor {
+    /// Creates a decryptor
+    #[must_use]
+    pub fn new(keys: Keys) -> Self {
+        Self {
+            keys,
+            pdcp: HashMap::new(),
+            nas: HashMap::new(),
+        }
+    }
+
+    /// Decrypts a framed message
+    ///
+    /// Returns the frame with the plain message.
+    ///
+    /// # Errors
+    /// + Returns [`FailureKind`] if the message cannot be decrypted.
+    ///
+    /// # Panics
+    /// + Panics if the message bit length is greater than `u32::MAX`.
+    pub fn decrypt(&mut self, frame: &Frame) -> Result<Frame, FailureKind> {
+        match frame {
+            Frame::Nas(f) => {
+                let header_type = SecurityHeaderType::of_message(f.system, &f.message)
+                    .map_err(FailureKind::Nas)?;
+                if header_type == SecurityHeaderType::Plain {
+                    return Ok(frame.clone());
+                }
+                let integrity_key = self.keys.nas_int.ok_or(FailureKind::MissingKey)?;
+                let ciphering_key = self.keys.nas_enc;
+                let ctx = self
+                    .nas
+                    .entry((f.ue_id, f.system, f.direction))
+                    .or_insert_with(|| {
+                        NasSecurityContext::new(NasConfig {
+                            system: f.system,
+                            direction: f.direction.reverse(),
+                            bearer: 0,
+                            ciphering_key,
+                            integrity_key,
+                        })
+                    });
+                let (_, message) = ctx.unprotect(&f.message).map_err(FailureKind::Nas)?;
+                Ok(Frame::Nas(NasFrame {
+                    message,
+                    ..f.clone()
+                }))
+            }
+            Frame::Pdcp(f) => {
+                if f.plane == Plane::User && f.pdu.first().is_some_and(|b| b & 0x80 == 0) {
+                    // control PDUs are not protected
+                    return Ok(frame.clone());
+                }
+                let (ciphering_key, integrity_key) = match f.plane {
+                    Plane::Signalling => (self.keys.rrc_enc, self.keys.rrc_int),
+                    Plane::User => (self.keys.up_enc, self.keys.up_int),
+                };
+                let integrity_key = match (f.mac_i_present, integrity_key) {
+                    (true, None) if !self.keys.nia0 => return Err(FailureKind::MissingKey),
+                    (true, key) => key,
+                    (false, _) => None,
+                };
+                let first_sn = f
+                    .pdu
+                    .get(..f.sn_length.header_len())
+                    .map_or(0, |header| f.sn_length.read_sn(header));
+                let entity = self
+                    .pdcp
+                    .entry((f.ue_id, f.plane, f.rb_identity, f.direction))
+                    .or_insert_with(|| {
+                        PdcpSecurity::new(PdcpConfig {
+                            bearer: bearer_from_rb_identity(f.rb_identity),
+                            direction: f.direction.reverse(),
+                            sn_length: f.sn_length,
+                            mac_i: f.mac_i_present,
+                            ciphering_key,
+                            integrity_key,
+                            tx_next: 0,
+                            rx_deliv: first_sn,
+                        })
+                    });
+                let (count, sdu) = entity
+                    .unprotect_with_count(&f.pdu)
+                    .map_err(FailureKind::Pdcp)?;
+                let header_len = f.sn_length.header_len();
+                let mut pdu = f.pdu[..header_len].to_vec();
+                pdu.extend_from_slice(&sdu);
+                if f.mac_i_present {
+                    let mac_i = integrity_key.map_or(0, |ik| {
+                        let bearer = bearer_from_rb_identity(f.rb_identity);
+                        let length = u32::try_from(pdu.len() * 8).expect("PDU bit length overflow");
+                        eia3_128_generate_mac(count, bearer, f.direction.bit(), &ik, length, &pdu)
+                    });
+                    pdu.extend_from_slice(&mac_i.to_be_bytes());
+                }
+                Ok(Frame::Pdcp(PdcpFrame { pdu, ..f.clone() }))
+            }
+        }
+    }
+
+    /// Decrypts every framed message of a capture
+    ///
+    /// # Panics
+    /// + Panics if a message bit length is greater than `u32::MAX`.
+    #[must_use]
+    pub fn decrypt_capture(&mut self, capture: &Capture) -> (Capture, Report) {
+        let mut report = Report {
+            packets: capture.packets.len(),
+            ..Report::default()
+        };
+        let mut packets = Vec::with_capacity(capture.packets.len());
+
+        for (i, packet) in capture.packets.iter().enumerate() {
+            let loc = locate_udp(capture.link_type, &packet.data);
+            let frame = loc
+                .as_ref()
+                .and_then(|loc| Frame::decode(&packet.data[loc.udp + 8..loc.end]));
+            let (Some(loc), Some(frame)) = (loc, frame) else {
+                packets.push(packet.clone());
+                continue;
+            };
+
+            let (ue_id, direction, rb_identity) = match &frame {
+                Frame::Nas(f) => {
+                    report.nas_messages += 1;
+                    (f.ue_id, f.direction, None)
+                }
+                Frame::Pdcp(f) => {
+                    report.pdcp_pdus += 1;
+                    (f.ue_id, f.direction, Some(f.rb_identity))
+                }
+            };
+
+            let data = match self.decrypt(&frame) {
+                Ok(plain) => replace_udp_payload(&packet.data, &loc, &plain.encode()),
+                Err(kind) => {
+                    report.failures.push(Failure {
+                        packet: i + 1,
+                        ue_id,
+                        direction,
+                        rb_identity,
+                        kind,
+                    });
+                    None
+                }
+            };
+            match data {
+                Some(data) => {
+                    report.decrypted += 1;
+                    let orig_len = u32::try_from(data.len()).expect("packet too long");
+                    packets.push(Packet {
+                        orig_len,
+                        data,
+                        ..packet.clone()
+                    });
+                }
+                None => packets.push(packet.clone()),
+            }
+        }
+
+        let capture = Capture {
+            link_type: capture.link_type,
+            packets,
+        };
+        (capture, report)
+    }
+}
+
+/// Decrypts a pcap or pcapng capture
+///
+/// Input:
+/// - `input`:    pcap or pcapng capture
+/// - `keys`:     NAS, RRC and UP keys
+///
+/// Output:
+/// - [`Vec<u8>`]:  decrypted pcap capture
+/// - [`Report`]:   decryption report
+///
+/// # Errors
+/// + Returns [`PcapError`] if the capture is malformed.
+///
+/// # Panics
+/// + Panics if a message bit length is greater than `u32::MAX`.
+pub fn decrypt_pcap(input: &[u8], keys: Keys) -> Result<(Vec<u8>, Report), PcapError> {
+    let capture = Capture::parse(input)?;
+    let (capture, report) = Decryptor::new(keys).decrypt_capture(&capture);
+    Ok((capture.to_pcap(), report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use const_str::hex;
+
+    const K_NAS_ENC: [u8; 16] = hex!("d4 55 2a 8f d6 e6 1c c8 1a 20 09 14 1a 29 c1 0b");
+    const K_NAS_INT: [u8; 16] = hex!("6b 8b 08 ee 79 e0 b5 98 2d 6d 12 8e a9 f2 20 cb");
+    const K_RRC_ENC: [u8; 16] = hex!("e5 bd 3e a0 eb 55 ad e8 66 c6 ac 58 bd 54 30 2a");
+    const K_RRC_INT: [u8; 16] = hex!("c9 e6 ce c4 60 7c 72 db 00 0a ef a8 83 85 ab 0a");
+    const K_UP_ENC: [u8; 16] = hex!("17 3d 14 ba 50 03 73 1d 7a 60 04 94 70 f0 0a 29");
+
+    fn keys() -> Keys {
+        Keys {
+            nas_enc: Some(K_NAS_ENC),
+            nas_int: Some(K_NAS_INT),
+            rrc_enc: Some(K_RRC_ENC),
+            rrc_int: Some(K_RRC_INT),
+            up_enc: Some(K_UP_ENC),
+            up_int: None,
+            nia0: false,
+        }
+    }
+
+    /// Wraps a UDP payload in Ethernet, IPv4 and UDP headers
+    fn ethernet_ipv4(payload: &[u8]) -> Vec<u8> {
+        let mut v = hex!("02 00 00 00 00 01 02 00 00 00 00 02 08 00").to_vec();
+        v.extend_from_slice(&hex!(
+            "45 00 00 1c 00 00 40 00 40 11 00 00 0a 00 00 01 0a 00 00 02"
+        ));
+        v.extend_from_slice(&hex!("d4 31 26 94 00 08 00 00"));
+        let loc = locate_udp(LINKTYPE_ETHERNET, &v).unwrap();
+        replace_udp_payload(&v, &loc, payload).unwrap()
+    }
+
+    /// Wraps a UDP payload in IPv6 and UDP headers
+    fn raw_ipv6(payload: &[u8]) -> Vec<u8> {
+        let mut v = hex!("60 00 00 00 00 08 11 40").to_vec();
+        v.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8]);
+        v.extend_from_slice(&[0; 11]);
+        v.push(1);
+        v.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8]);
+        v.extend_from_slice(&[0; 11]);
+        v.push(2);
+        v.extend_from_slice(&hex!("d4 31 26 94 00 08 00 00"));
+        let loc = locate_udp(LINKTYPE_RAW, &v).unwrap();
+        replace_udp_payload(&v, &loc, payload).unwrap()
+    }
+
+    /// Encodes packets as a little-endian pcapng capture with nanosecond timestamps
+    fn pcapng(link_type: u16, packets: &[Vec<u8>]) -> Vec<u8> {
+        let mut v = Vec::new();
+        let mut block = |block_type: u32, body: &[u8]| {
+            let len = u32::try_from(12 + body.len().next_multiple_of(4)).unwrap();
+            v.extend_from_slice(&block_type.to_le_bytes());
+            v.extend_from_slice(&len.to_le_bytes());
+            v.extend_from_slice(body);
+            v.resize(v.len() + body.len().next_multiple_of(4) - body.len(), 0);
+            v.extend_from_slice(&len.to_le_bytes());
+        };
+        let mut shb = PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes().to_vec();
+        shb.extend_from_slice(&[1, 0, 0, 0]);
+        shb.extend_from_slice(&u64::MAX.to_le_bytes());
+        block(PCAPNG_SHB, &shb);
+
+        let mut idb = link_type.to_le_bytes().to_vec();
+        idb.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+        idb.extend_from_slice(&[9, 0, 1, 0, 9, 0, 0, 0, 0, 0, 0, 0]);
+        block(PCAPNG_IDB, &idb);
+
+        for (i, p) in packets.iter().enumerate() {
+            let ts = 1_700_000_000_000_000_000_u64 + u64::try_from(i).unwrap() * 1500;
+            let len = u32::try_from(p.len()).unwrap();
+            let mut epb = 0_u32.to_le_bytes().to_vec();
+            epb.extend_from_slice(&u32::try_from(ts >> 32).unwrap().to_le_bytes());
+            epb.extend_from_slice(&u32::try_from(ts & 0xffff_ffff).unwrap().to_le_bytes());
+            epb.extend_from_slice(&len.to_le_bytes());
+            epb.extend_from_slice(&len.to_le_bytes());
+            epb.extend_from_slice(p);
+            block(PCAPNG_EPB, &epb);
+        }
+        v
+    }
+
+    fn pcap(link_type: u32, packets: &[Vec<u8>]) -> Vec<u8> {
+        let packets = packets
+            .iter()
+            .map(|data| Packet {
+                ts_sec: 1_700_000_000,
+                ts_usec: 0,
+                orig_len: 0,
+                data: data.clone(),
+            })
+            .collect();
+        Capture { link_type, packets }.to_pcap()
+    }
+
+    fn udp_payload(link_type: u32, data: &[u8]) -> Vec<u8> {
+        let loc = locate_udp(link_type, data).unwrap();
+        data[loc.udp + 8..loc.end].to_vec()
+    }
+
+    /// Registration request (plain 5GMM message)
+    static REGISTRATION_REQUEST: &[u8] =
+        &hex!("7e 00 41 79 00 0d 01 09 f1 07 00 00 00 00 00 00 00 00 10");
+
+    /// Builds a NAS exchange, PDCP SRB and DRB traffic and their plain frames
+    fn traffic() -> (Vec<Frame>, Vec<Frame>) {
+        let mut protected = Vec::new();
+        let mut plain = Vec::new();
+
+        let mut ue = NasSecurityContext::new(NasConfig {
+            system: NasSystem::FiveGs,
+            direction: Direction::Uplink,
+            bearer: 0,
+            ciphering_key: Some(K_NAS_ENC),
+            integrity_key: K_NAS_INT,
+        });
+        for i in 0..3_u8 {
+            let header_type = if i == 0 {
+                SecurityHeaderType::IntegrityProtectedAndCipheredWithNewContext
+            } else {
+                SecurityHeaderType::IntegrityProtectedAndCiphered
+            };
+            let message = [REGISTRATION_REQUEST, &[i]].concat();
+            let nas = NasFrame {
+                ue_id: 7,
+                system: NasSystem::FiveGs,
+                direction: Direction::Uplink,
+                message: ue.protect(header_type, &message).unwrap(),
+            };
+            protected.push(Frame::Nas(nas.clone()));
+            plain.push(Frame::Nas(NasFrame { message, ..nas }));
+        }
+
+        let mut srb = PdcpSecurity::new(PdcpConfig {
+            bearer: bearer_from_rb_identity(1),
+            direction: Direction::Downlink,
+            sn_length: SnLength::Bits12,
+            mac_i: true,
+            ciphering_key: Some(K_RRC_ENC),
+            integrity_key: Some(K_RRC_INT),
+            tx_next: 0,
+            rx_deliv: 0,
+        });
+        let mut drb = PdcpSecurity::new(PdcpConfig {
+            bearer: bearer_from_rb_identity(4),
+            direction: Direction::Uplink,
+            sn_length: SnLength::Bits18,
+            mac_i: false,
+            ciphering_key: Some(K_UP_ENC),
+            integrity_key: None,
+            tx_next: 0x3_fffe,
+            rx_deliv: 0,
+        });
+        // DRB SNs wrap around 2^18 to exercise HFN tracking
+        for (i, sn) in [0_u32, 1, 2].into_iter().enumerate() {
+            let sdu = vec![0x5a ^ u8::try_from(i).unwrap(); 40 + i];
+            let header = [0x00, u8::try_from(sn).unwrap()];
+            let pdu = srb.protect(sn, &header, &sdu).unwrap();
+            let frame = PdcpFrame {
+                ue_id: 7,
+                plane: Plane::Signalling,
+                sn_length: SnLength::Bits12,
+                direction: Direction::Downlink,
+                rb_identity: 1,
+                mac_i_present: true,
+                pdu,
+            };
+            protected.push(Frame::Pdcp(frame.clone()));
+            let mut pdu = [&header[..], &sdu].concat();
+            let length = u32::try_from(pdu.len() * 8).unwrap();
+            let mac_i = eia3_128_generate_mac(sn, 0, 1, &K_RRC_INT, length, &pdu);
+            pdu.extend_from_slice(&mac_i.to_be_bytes());
+            plain.push(Frame::Pdcp(PdcpFrame { pdu, ..frame }));
+        }
+        for (i, count) in [0x3_fffe_u32, 0x3_ffff, 0x4_0000, 0x4_0001]
+            .into_iter()
+            .enumerate()
+        {
+            let sn = count & 0x3_ffff;
+            let sdu = vec![u8::try_from(i).unwrap(); 100];
+            let [_, b0, b1, b2] = sn.to_be_bytes();
+            let header = [0x80 | b0, b1, b2];
+            let pdu = drb.protect(sn, &header, &sdu).unwrap();
+            let frame = PdcpFrame {
+                ue_id: 7,
+                plane: Plane::User,
+                sn_length: SnLength::Bits18,
+                direction: Direction::Uplink,
+                rb_identity: 4,
+                mac_i_present: false,
+                pdu,
+            };
+            protected.push(Frame::Pdcp(frame.clone()));
+            plain.push(Frame::Pdcp(PdcpFrame {
+                pdu: [&header[..], &sdu].concat(),
+                ..frame
+            }));
+        }
+        (protected, plain)
+    }
+
+    #[test]
+    fn framing() {
+        let (protected, _) = traffic();
+        for frame in &protected {
+            assert_eq!(Frame::decode(&frame.encode()).as_ref(), Some(frame));
+        }
+        assert_eq!(Frame::decode(b"hello"), None);
+        assert_eq!(Frame::decode(b"pdcp-nr\x01\x05\x00\x01"), None);
+        assert_eq!(Frame::decode(b"nas-eps\x7f\x01"), None);
+    }
+
+    #[test]
+    fn decrypt_pcap_ethernet() {
+        let (protected, plain) = traffic();
+        let mut packets: Vec<Vec<u8>> = protected
+            .iter()
+            .map(|f| ethernet_ipv4(&f.encode()))
+            .collect();
+        packets.insert(2, ethernet_ipv4(b"not framed"));
+
+        let (output, report) = decrypt_pcap(&pcap(LINKTYPE_ETHERNET, &packets), keys()).unwrap();
+        assert_eq!(report.packets, 11);
+        assert_eq!(report.nas_messages, 3);
+        assert_eq!(report.pdcp_pdus, 7);
+        assert_eq!(report.decrypted, 10);
+        assert!(report.failures.is_empty());
+
+        let output = Capture::parse(&output).unwrap();
+        assert_eq!(output.link_type, LINKTYPE_ETHERNET);
+        assert_eq!(output.packets[2].data, packets[2]);
+        let frames: Vec<Frame> = output
+            .packets
+            .iter()
+            .filter_map(|p| Frame::decode(&udp_payload(LINKTYPE_ETHERNET, &p.data)))
+            .collect();
+        assert_eq!(frames, plain);
+
+        let ip = &output.packets[0].data[14..34];
+        assert_eq!(checksum(&[ip]), 0);
+    }
+
+    #[test]
+    fn decrypt_pcapng_ipv6() {
+        let (protected, plain) = traffic();
+        let packets: Vec<Vec<u8>> = protected.iter().map(|f| raw_ipv6(&f.encode())).collect();
+
+        let input = pcapng(101, &packets);
+        let (output, report) = decrypt_pcap(&input, keys()).unwrap();
+        assert_eq!(report.decrypted, 10);
+
+        let output = Capture::parse(&output).unwrap();
+        assert_eq!(output.packets[1].ts_sec, 1_700_000_000);
+        assert_eq!(output.packets[1].ts_usec, 1);
+        for (p, expected) in output.packets.iter().zip(&plain) {
+            let udp_len = u32::try_from(p.data.len() - 40).unwrap().to_be_bytes();
+            let pseudo = [&p.data[8..40], &udp_len, &[0, 0, 0, 17], &p.data[40..]];
+            assert_eq!(checksum(&pseudo), 0);
+            assert_eq!(
+                Frame::decode(&udp_payload(LINKTYPE_RAW, &p.data)).as_ref(),
+                Some(expected)
+            );
+        }
+    }
+
+    #[test]
+    fn integrity_failures() {
+        let (mut protected, _) = traffic();
+        for i in [1, 4] {
+            match &mut protected[i] {
+                Frame::Nas(f) => *f.message.last_mut().unwrap() ^= 1,
+                Frame::Pdcp(f) => *f.pdu.last_mut().unwrap() ^= 1,
+            }
+        }
+        let packets: Vec<Vec<u8>> = protected
+            .iter()
+            .map(|f| ethernet_ipv4(&f.encode()))
+            .collect();
+
+        let (output, report) = decrypt_pcap(&pcap(LINKTYPE_ETHERNET, &packets), keys()).unwrap();
+        assert_eq!(report.decrypted, 8);
+        assert_eq!(report.integrity_failures(), 2);
+        assert_eq!(report.failures[0].packet, 2);
+        assert_eq!(report.failures[0].rb_identity, None);
+        assert_eq!(report.failures[1].packet, 5);
+        assert_eq!(report.failures[1].rb_identity, Some(1));
+        assert!(matches!(
+            report.failures[1].kind,
+            FailureKind::Pdcp(PdcpError::IntegrityFailure { count: 1 })
+        ));
+
+        let output = Capture::parse(&output).unwrap();
+        assert_eq!(output.packets[1].data, packets[1]);
+        assert_eq!(output.packets[4].data, packets[4]);
+
+        let text = report.to_string();
+        assert!(text.contains("integrity failures: 2"));
+        assert!(text.contains("packet 5: UE 7 Downlink RB 1: PDCP: integrity check failed"));
+    }
+
+    #[test]
+    fn missing_keys() {
+        let (protected, _) = traffic();
+        let packets: Vec<Vec<u8>> = protected
+            .iter()
+            .map(|f| ethernet_ipv4(&f.encode()))
+            .collect();
+        let no_nas = Keys {
+            nas_int: None,
+            ..keys()
+        };
+        let (_, report) = decrypt_pcap(&pcap(LINKTYPE_ETHERNET, &packets), no_nas).unwrap();
+        assert_eq!(report.decrypted, 7);
+        assert_eq!(report.failures.len(), 3);
+        assert!(report
+            .failures
+            .iter()
+            .all(|x| x.kind == FailureKind::MissingKey));
+        assert_eq!(report.integrity_failures(), 0);
+
+        let no_rrc = Keys {
+            rrc_int: None,
+            ..keys()
+        };
+        let (_, report) = decrypt_pcap(&pcap(LINKTYPE_ETHERNET, &packets), no_rrc).unwrap();
+        assert_eq!(report.decrypted, 7);
+        assert_eq!(report.failures.len(), 3);
+        assert!(report
+            .failures
+            .iter()
+            .all(|x| x.kind == FailureKind::MissingKey && x.rb_identity == Some(1)));
+
+        // with NIA0 requested the SRB PDUs are checked against an all-zero MAC-I
+        let nia0 = Keys {
+            rrc_int: None,
+            nia0: true,
+            ..keys()
+        };
+        let (_, report) = decrypt_pcap(&pcap(LINKTYPE_ETHERNET, &packets), nia0).unwrap();
+        assert_eq!(report.decrypted, 7);
+        assert_eq!(report.integrity_failures(), 3);
+    }
+
+    #[test]
+    fn plain_nas() {
+        let frame = Frame::Nas(NasFrame {
+            ue_id: 3,
+            system: NasSystem::Eps,
+            direction: Direction::Uplink,
+            message: hex!("07 41 71 08 29 80 39 10 00 00 00 00").to_vec(),
+        });
+        let packets = [ethernet_ipv4(&frame.encode())];
+        let (output, report) =
+            decrypt_pcap(&pcap(LINKTYPE_ETHERNET, &packets), Keys::default()).unwrap();
+        assert_eq!(report.nas_messages, 1);
+        assert!(report.failures.is_empty());
+        assert_eq!(Capture::parse(&output).unwrap().packets[0].data, packets[0]);
+    }
+
+    #[test]
+    fn srb_nia0() {
+        let mut srb = PdcpSecurity::new(PdcpConfig {
+            bearer: bearer_from_rb_identity(1),
+            direction: Direction::Uplink,
+            sn_length: SnLength::Bits12,
+            mac_i: true,
+            ciphering_key: None,
+            integrity_key: None,
+            tx_next: 0,
+            rx_deliv: 0,
+        });
+        let header = [0x00, 0x00];
+        let frame = PdcpFrame {
+            ue_id: 9,
+            plane: Plane::Signalling,
+            sn_length: SnLength::Bits12,
+            direction: Direction::Uplink,
+            rb_identity: 1,
+            mac_i_present: true,
+            pdu: srb.protect(0, &header, b"emergency").unwrap(),
+        };
+        let packets = [ethernet_ipv4(&Frame::Pdcp(frame.clone()).encode())];
+
+        let keys = Keys {
+            rrc_enc: None,
+            rrc_int: None,
+            nia0: true,
+            ..keys()
+        };
+        let (output, report) = decrypt_pcap(&pcap(LINKTYPE_ETHERNET, &packets), keys).unwrap();
+        assert_eq!(report.decrypted, 1);
+        assert!(report.failures.is_empty());
+
+        let output = Capture::parse(&output).unwrap();
+        let plain = Frame::decode(&udp_payload(LINKTYPE_ETHERNET, &output.packets[0].data));
+        let pdu = [&header[..], b"emergency", &[0; 4]].concat();
+        assert_eq!(plain, Some(Frame::Pdcp(PdcpFrame { pdu, ..frame })));
+    }
+
+    #[test]
+    fn redacted_debug() {
+        assert_eq!(format!("{:?}", keys()), "Keys { ... }");
+        let mut decryptor = Decryptor::new(keys());
+        let (protected, _) = traffic();
+        for frame in &protected {
+            decryptor.decrypt(frame).unwrap();
+        }
+        assert_eq!(format!("{decryptor:?}"), "Decryptor { ... }");
+    }
+
+    #[test]
+    fn malformed() {
+        assert_eq!(Capture::parse(b""), Err(PcapError::UnknownFormat));
+        assert_eq!(Capture::parse(b"GIF89a"), Err(PcapError::UnknownFormat));
+        let input = pcap(LINKTYPE_ETHERNET, &[ethernet_ipv4(b"x")]);
+        assert_eq!(
+            Capture::parse(&input[..input.len() - 1]),
+            Err(PcapError::Truncated)
+        );
+    }
+}
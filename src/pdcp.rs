@@ -0,0 +1,700 @@
+//! PDCP security entity
+//! ([TS 36.323](https://www.3gpp.org/DynaReport/36323.htm), [TS 38.323](https://www.3gpp.org/DynaReport/38323.htm))
+//!
+//! A PDCP data PDU is `header || data` with an optional 32-bit `MAC-I` appended.
+//! `MAC-I` is computed with 128-EIA3 over `header || data`, or is all zeros
+//! under NIA0, then `data || MAC-I` is ciphered with 128-EEA3.
+//! The header is never ciphered.
+//!
+//! The transmitting side never reuses a COUNT. Retransmission buffering is left
+//! to the caller, which must send the PDU built the first time.
+//! The receiving side discards PDUs whose COUNT is below `RX_DELIV` or was
+//! already received (TS 38.323 §5.2.2.1).
+
+use crate::{eea3_128_encrypt, eia3_128_generate_mac, Direction};
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Length of `MAC-I` in bytes
+pub const MAC_I_LEN: usize = 4;
+
+/// PDCP SN length
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SnLength {
+    /// 12-bit SN, 2-byte header (SRBs and DRBs)
+    Bits12,
+    /// 18-bit SN, 3-byte header (NR DRBs)
+    Bits18,
+}
+
+impl SnLength {
+    /// Number of SN bits
+    #[must_use]
+    pub fn bits(self) -> u32 {
+        match self {
+            SnLength::Bits12 => 12,
+            SnLength::Bits18 => 18,
+        }
+    }
+
+    /// Length of the data PDU header in bytes
+    #[must_use]
+    pub fn header_len(self) -> usize {
+        match self {
+            SnLength::Bits12 => 2,
+            SnLength::Bits18 => 3,
+        }
+    }
+
+    /// Extracts the SN from a data PDU header
+    ///
+    /// # Panics
+    /// + Panics if `header` is shorter than [`SnLength::header_len`].
+    #[must_use]
+    pub fn read_sn(self, header: &[u8]) -> u32 {
+        match self {
+            SnLength::Bits12 => (u32::from(header[0] & 0x0f) << 8) | u32::from(header[1]),
+            SnLength::Bits18 => {
+                (u32::from(header[0] & 0x03) << 16)
+                    | (u32::from(header[1]) << 8)
+                    | u32::from(header[2])
+            }
+        }
+    }
+}
+
+/// Maps a radio bearer identity (1..=32) to the 5-bit `BEARER` input
+///
+/// `BEARER` is the RB identity minus one.
+///
+/// # Panics
+/// + Panics if `rb_identity` is not in `1..=32`.
+#[must_use]
+pub fn bearer_from_rb_identity(rb_identity: u8) -> u8 {
+    assert!(
+        (1..=32).contains(&rb_identity),
+        "`rb_identity` is out of range"
+    );
+    rb_identity - 1
+}
+
+/// PDCP security configuration
+#[derive(Debug, Clone)]
+pub struct PdcpConfig {
+    /// 5-bit `BEARER` input, see [`bearer_from_rb_identity`]
+    pub bearer: u8,
+    /// Transmitting direction of this entity, the receiving direction is the opposite
+    pub direction: Direction,
+    /// SN length
+    pub sn_length: SnLength,
+    /// Ciphering key, `None` for NEA0
+    pub ciphering_key: Option<[u8; 16]>,
+    /// Whether data PDUs carry `MAC-I`: always on SRBs, on DRBs with integrity protection
+    pub mac_i: bool,
+    /// Integrity key, `None` for NIA0 or if `MAC-I` is not carried
+    pub integrity_key: Option<[u8; 16]>,
+    /// Initial `TX_NEXT`
+    pub tx_next: u32,
+    /// Initial `RX_DELIV`
+    pub rx_deliv: u32,
+}
+
+/// PDCP security errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PdcpError {
+    /// The SN does not fit in the configured SN length, or does not match the header
+    InvalidSn,
+    /// The SN maps to a COUNT already used for transmission
+    CountReuse {
+        /// COUNT of the rejected SDU
+        count: u32,
+    },
+    /// The PDU is shorter than its header and `MAC-I`
+    TooShort,
+    /// COUNT would leave the 32-bit range, the keys must be refreshed
+    CountWrap,
+    /// The SN is more than half the SN space behind the first HFN
+    OutOfWindow {
+        /// SN of the rejected PDU
+        sn: u32,
+    },
+    /// `MAC-I` verification failed
+    IntegrityFailure {
+        /// COUNT of the failed PDU
+        count: u32,
+    },
+    /// The PDU is below `RX_DELIV` or was already received
+    Duplicate {
+        /// COUNT of the discarded PDU
+        count: u32,
+    },
+}
+
+impl fmt::Display for PdcpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PdcpError::InvalidSn => write!(f, "SN out of range or not matching the header"),
+            PdcpError::CountReuse { count } => {
+                write!(f, "COUNT already used (COUNT = {count:#010x})")
+            }
+            PdcpError::TooShort => write!(f, "PDU too short"),
+            PdcpError::CountWrap => write!(f, "COUNT wrap around"),
+            PdcpError::OutOfWindow { sn } => write!(f, "SN out of window (SN = {sn:#x})"),
+            PdcpError::IntegrityFailure { count } => {
+                write!(f, "integrity check failed (COUNT = {count:#010x})")
+            }
+            PdcpError::Duplicate { count } => {
+                write!(f, "duplicate PDU (COUNT = {count:#010x})")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PdcpError {}
+
+/// Converts a byte length to a bit length
+///
+/// # Panics
+/// + Panics if the bit length is greater than `u32::MAX`.
+fn bit_length(len: usize) -> u32 {
+    len.checked_mul(8)
+        .and_then(|n| u32::try_from(n).ok())
+        .expect("PDU bit length overflow")
+}
+
+/// PDCP security entity
+///
+/// Applies ciphering and integrity protection to data PDUs,
+/// tracking HFN in both directions.
+///
+/// The receiving HFN is derived relative to the highest received COUNT, and
+/// received COUNTs above `RX_DELIV` are kept within the same window:
+/// `RX_DELIV` moves up to half the SN space behind the highest received COUNT,
+/// the COUNTs skipped over are considered lost.
+#[derive(Debug, Clone)]
+pub struct PdcpSecurity {
+    /// security configuration
+    config: PdcpConfig,
+    /// COUNT of the next PDU to transmit (up to 2^32)
+    tx_next: u64,
+    /// COUNT of the first PDU not yet received (up to 2^32)
+    rx_deliv: u64,
+    /// highest received COUNT plus one, the reference of the receiving HFN
+    rx_next: u64,
+    /// COUNTs above `rx_deliv` already received
+    received: BTreeSet<u64>,
+    /// number of integrity failures
+    integrity_failures: u64,
+}
+
+impl PdcpSecurity {
+    /// Creates a PDCP security entity
+    ///
+    /// # Panics
+    /// + Panics if `config` has an integrity key but no `MAC-I`.
+    #[must_use]
+    pub fn new(config: PdcpConfig) -> Self {
+        assert!(
+            config.mac_i || config.integrity_key.is_none(),
+            "`integrity_key` is set without `mac_i`"
+        );
+        let tx_next = u64::from(config.tx_next);
+        let rx_deliv = u64::from(config.rx_deliv);
+        Self {
+            config,
+            tx_next,
+            rx_deliv,
+            rx_next: rx_deliv,
+            received: BTreeSet::new(),
+            integrity_failures: 0,
+        }
+    }
+
+    /// Half the SN space, the reordering window
+    fn window(&self) -> u64 {
+        1 << (self.config.sn_length.bits() - 1)
+    }
+
+    /// Returns `TX_NEXT`, or `None` if COUNT is exhausted
+    #[must_use]
+    pub fn tx_next(&self) -> Option<u32> {
+        u32::try_from(self.tx_next).ok()
+    }
+
+    /// Returns `RX_DELIV`, or `None` if COUNT is exhausted
+    #[must_use]
+    pub fn rx_deliv(&self) -> Option<u32> {
+        u32::try_from(self.rx_deliv).ok()
+    }
+
+    /// Returns the number of PDUs that failed integrity verification
+    #[must_use]
+    pub fn integrity_failures(&self) -> u64 {
+        self.integrity_failures
+    }
+
+    /// Derives COUNT from a SN relative to a reference COUNT
+    ///
+    /// The HFN is the one of `reference`, adjusted by one
+    /// when `sn` is more than half the SN space away from the SN of `reference`.
+    /// An SN behind the first HFN is out of window, not a COUNT wrap.
+    fn count_for_sn(&self, reference: u64, sn: u32) -> Result<u32, PdcpError> {
+        let bits = self.config.sn_length.bits();
+        let window = self.window();
+        let out_of_window = PdcpError::OutOfWindow { sn };
+        let sn = u64::from(sn);
+
+        let ref_sn = reference & ((1 << bits) - 1);
+        let ref_hfn = reference >> bits;
+
+        let hfn = if sn + window < ref_sn {
+            ref_hfn + 1
+        } else if sn >= ref_sn + window {
+            ref_hfn.checked_sub(1).ok_or(out_of_window)?
+        } else {
+            ref_hfn
+        };
+
+        u32::try_from((hfn << bits) | sn).map_err(|_| PdcpError::CountWrap)
+    }
+
+    /// Protects a PDCP SDU
+    ///
+    /// The COUNT of `sn` must not be below `TX_NEXT`:
+    /// a PDU is retransmitted as it was built the first time.
+    ///
+    /// Input:
+    /// - `sn`:       PDCP SN carried in `header`
+    /// - `header`:   PDCP data PDU header
+    /// - `sdu`:      PDCP SDU
+    ///
+    /// Output:
+    /// - [`Vec<u8>`]:  PDCP data PDU
+    ///
+    /// # Errors
+    /// + Returns [`PdcpError::InvalidSn`] if `sn` does not fit in the SN length,
+    ///   `header` is not [`SnLength::header_len`] bytes long or does not carry `sn`.
+    /// + Returns [`PdcpError::CountReuse`] if the COUNT of `sn` is below `TX_NEXT`.
+    /// + Returns [`PdcpError::OutOfWindow`] if `sn` is more than half the SN space
+    ///   behind `TX_NEXT` in the first HFN.
+    /// + Returns [`PdcpError::CountWrap`] if COUNT is exhausted.
+    ///
+    /// # Panics
+    /// + Panics if the PDU bit length is greater than `u32::MAX`.
+    pub fn protect(&mut self, sn: u32, header: &[u8], sdu: &[u8]) -> Result<Vec<u8>, PdcpError> {
+        let sn_length = self.config.sn_length;
+        if sn >> sn_length.bits() != 0
+            || header.len() != sn_length.header_len()
+            || sn_length.read_sn(header) != sn
+        {
+            return Err(PdcpError::InvalidSn);
+        }
+        let count = self.count_for_sn(self.tx_next, sn)?;
+        if u64::from(count) < self.tx_next {
+            return Err(PdcpError::CountReuse { count });
+        }
+
+        let PdcpConfig {
+            bearer, direction, ..
+        } = self.config;
+
+        let mut pdu = Vec::with_capacity(header.len() + sdu.len() + MAC_I_LEN);
+        pdu.extend_from_slice(header);
+        pdu.extend_from_slice(sdu);
+
+        if self.config.mac_i {
+            let mac_i = self.mac_i(count, direction, &pdu);
+            pdu.extend_from_slice(&mac_i.to_be_bytes());
+        }
+
+        if let Some(ck) = &self.config.ciphering_key {
+            let body = &mut pdu[header.len()..];
+            let length = bit_length(body.len());
+            let obs = eea3_128_encrypt(count, bearer, direction.bit(), ck, length, body);
+            body.copy_from_slice(&obs);
+        }
+
+        self.tx_next = u64::from(count) + 1;
+        Ok(pdu)
+    }
+
+    /// Computes `MAC-I` of `header || data`, all zeros under NIA0
+    ///
+    /// # Panics
+    /// + Panics if the bit length of `m` is greater than `u32::MAX`.
+    fn mac_i(&self, count: u32, direction: Direction, m: &[u8]) -> u32 {
+        match &self.config.integrity_key {
+            Some(ik) => {
+                let length = bit_length(m.len());
+                eia3_128_generate_mac(count, self.config.bearer, direction.bit(), ik, length, m)
+            }
+            None => 0,
+        }
+    }
+
+    /// Unprotects a PDCP data PDU
+    ///
+    /// Input:
+    /// - `pdu`:      PDCP data PDU
+    ///
+    /// Output:
+    /// - [`Vec<u8>`]:  PDCP SDU
+    ///
+    /// # Errors
+    /// + Returns [`PdcpError::TooShort`] if `pdu` is shorter than its header and `MAC-I`.
+    /// + Returns [`PdcpError::OutOfWindow`] if the SN is more than half the SN space
+    ///   behind `RX_DELIV` in the first HFN.
+    /// + Returns [`PdcpError::CountWrap`] if COUNT is out of range.
+    /// + Returns [`PdcpError::IntegrityFailure`] if `MAC-I` verification fails.
+    /// + Returns [`PdcpError::Duplicate`] if COUNT is below `RX_DELIV` or was already received.
+    ///
+    /// # Panics
+    /// + Panics if the PDU bit length is greater than `u32::MAX`.
+    pub fn unprotect(&mut self, pdu: &[u8]) -> Result<Vec<u8>, PdcpError> {
+        self.unprotect_with_count(pdu).map(|(_, sdu)| sdu)
+    }
+
+    /// Unprotects a PDCP data PDU and returns its COUNT
+    ///
+    /// See [`PdcpSecurity::unprotect`].
+    ///
+    /// # Errors
+    /// + Returns [`PdcpError`] as [`PdcpSecurity::unprotect`] does.
+    ///
+    /// # Panics
+    /// + Panics if the PDU bit length is greater than `u32::MAX`.
+    pub fn unprotect_with_count(&mut self, pdu: &[u8]) -> Result<(u32, Vec<u8>), PdcpError> {
+        let sn_length = self.config.sn_length;
+        let mac_len = if self.config.mac_i { MAC_I_LEN } else { 0 };
+        if pdu.len() < sn_length.header_len() + mac_len {
+            return Err(PdcpError::TooShort);
+        }
+
+        let (header, body) = pdu.split_at(sn_length.header_len());
+        let sn = sn_length.read_sn(header);
+        let count = self.count_for_sn(self.rx_next, sn)?;
+
+        let bearer = self.config.bearer;
+        let direction = self.config.direction.reverse();
+
+        let mut body = match &self.config.ciphering_key {
+            Some(ck) => eea3_128_encrypt(
+                count,
+                bearer,
+                direction.bit(),
+                ck,
+                bit_length(body.len()),
+                body,
+            ),
+            None => body.to_vec(),
+        };
+
+        if self.config.mac_i {
+            let data_len = body.len() - MAC_I_LEN;
+            let mut m = Vec::with_capacity(header.len() + data_len);
+            m.extend_from_slice(header);
+            m.extend_from_slice(&body[..data_len]);
+
+            let xmac_i = self.mac_i(count, direction, &m);
+            if body[data_len..] != xmac_i.to_be_bytes() {
+                self.integrity_failures += 1;
+                return Err(PdcpError::IntegrityFailure { count });
+            }
+            body.truncate(data_len);
+        }
+
+        self.receive(count)?;
+        Ok((count, body))
+    }
+
+    /// Records the reception of `count`, advancing `RX_DELIV`
+    fn receive(&mut self, count: u32) -> Result<(), PdcpError> {
+        let c = u64::from(count);
+        if c < self.rx_deliv || !self.received.insert(c) {
+            return Err(PdcpError::Duplicate { count });
+        }
+        self.rx_next = self.rx_next.max(c + 1);
+        let oldest = self.rx_next.saturating_sub(self.window());
+        if self.rx_deliv < oldest {
+            self.rx_deliv = oldest;
+            self.received = self.received.split_off(&oldest);
+        }
+        while self.received.remove(&self.rx_deliv) {
+            self.rx_deliv += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use const_str::hex;
+
+    fn config(direction: Direction, sn_length: SnLength) -> PdcpConfig {
+        PdcpConfig {
+            bearer: bearer_from_rb_identity(1),
+            direction,
+            sn_length,
+            mac_i: true,
+            ciphering_key: Some(hex!("17 3d 14 ba 50 03 73 1d 7a 60 04 94 70 f0 0a 29")),
+            integrity_key: Some(hex!("c9 e6 ce c4 60 7c 72 db 00 0a ef a8 83 85 ab 0a")),
+            tx_next: 0,
+            rx_deliv: 0,
+        }
+    }
+
+    /// EEA3 Test Set 2 carried in a downlink DRB data PDU with 18-bit SN
+    /// FROM <https://www.gsma.com/solutions-and-impact/technologies/security/wp-content/uploads/2019/05/eea3eia3testdatav11.pdf>
+    #[test]
+    fn drb_18bit_sn() {
+        let ibs = hex!([
+            "14a8ef69 3d678507 bbe7270a 7f67ff50 06c3525b 9807e467 c4e56000 ba338f5d",
+            "42955903 67518222 46c80d3b 38f07f4b e2d8ff58 05f51322 29bde93b bbdcaf38",
+            "2bf1ee97 2fbf9977 bada8945 847a2a6c 9ad34a66 7554e04d 1f7fa2c3 3241bd8f",
+            "01ba220d",
+        ]);
+        let obs = hex!([
+            "131d43e0 dea1be5c 5a1bfd97 1d852cbf 712d7b4f 57961fea 3208afa8 bca433f4",
+            "56ad09c7 417e58bc 69cf8866 d1353f74 865e8078 1d202dfb 3ecff7fc bc3b190f",
+            "e82a204e d0e350fc 0f6f2613 b2f2bca6 df5a473a 57a4a00d 985ebad8 80d6f238",
+            "64a07b01",
+        ]);
+
+        // COUNT = 0x00056823 = HFN 1 || SN 0x16823
+        let header = [0x81, 0x68, 0x23];
+        let mut expected = header.to_vec();
+        expected.extend_from_slice(&obs);
+
+        let base = PdcpConfig {
+            bearer: bearer_from_rb_identity(25),
+            direction: Direction::Downlink,
+            sn_length: SnLength::Bits18,
+            mac_i: false,
+            ciphering_key: Some(hex!("e5 bd 3e a0 eb 55 ad e8 66 c6 ac 58 bd 54 30 2a")),
+            integrity_key: None,
+            tx_next: 0x0005_6823,
+            rx_deliv: 0x0005_6823,
+        };
+
+        let mut gnb = PdcpSecurity::new(base.clone());
+        let pdu = gnb.protect(0x1_6823, &header, &ibs).unwrap();
+        assert_eq!(pdu, expected);
+        assert_eq!(gnb.tx_next(), Some(0x0005_6824));
+
+        let mut ue = PdcpSecurity::new(PdcpConfig {
+            direction: Direction::Uplink,
+            ..base
+        });
+        assert_eq!(ue.unprotect(&pdu).unwrap(), ibs);
+        assert_eq!(ue.rx_deliv(), Some(0x0005_6824));
+    }
+
+    #[test]
+    fn srb_12bit_sn() {
+        let cfg = config(Direction::Uplink, SnLength::Bits12);
+        let ck = cfg.ciphering_key.unwrap();
+        let ik = cfg.integrity_key.unwrap();
+
+        let header = [0x00, 0x05];
+        let sdu = hex!("38f07f4b e2d8ff58 05f51322 29bde93b bbdcaf38");
+
+        let mut m = header.to_vec();
+        m.extend_from_slice(&sdu);
+        let mac_i = eia3_128_generate_mac(5, 0, 0, &ik, bit_length(m.len()), &m);
+
+        let mut body = sdu.to_vec();
+        body.extend_from_slice(&mac_i.to_be_bytes());
+        let body = eea3_128_encrypt(5, 0, 0, &ck, bit_length(body.len()), &body);
+
+        let mut expected = header.to_vec();
+        expected.extend_from_slice(&body);
+
+        let mut ue = PdcpSecurity::new(cfg.clone());
+        let pdu = ue.protect(5, &header, &sdu).unwrap();
+        assert_eq!(pdu, expected);
+
+        let mut gnb = PdcpSecurity::new(PdcpConfig {
+            direction: Direction::Downlink,
+            ..cfg
+        });
+        assert_eq!(gnb.unprotect(&pdu).unwrap(), sdu);
+    }
+
+    #[test]
+    fn srb_nia0() {
+        let mut cfg = config(Direction::Uplink, SnLength::Bits12);
+        cfg.integrity_key = None;
+        let ck = cfg.ciphering_key.unwrap();
+
+        let header = [0x00, 0x07];
+        let sdu = b"emergency";
+        let mut body = sdu.to_vec();
+        body.extend_from_slice(&[0; MAC_I_LEN]);
+        let body = eea3_128_encrypt(7, 0, 0, &ck, bit_length(body.len()), &body);
+
+        let mut ue = PdcpSecurity::new(cfg.clone());
+        let pdu = ue.protect(7, &header, sdu).unwrap();
+        assert_eq!(pdu, [&header[..], &body].concat());
+
+        let mut gnb = PdcpSecurity::new(PdcpConfig {
+            direction: Direction::Downlink,
+            ..cfg
+        });
+        let mut forged = pdu.clone();
+        *forged.last_mut().unwrap() ^= 1;
+        assert_eq!(
+            gnb.unprotect(&forged),
+            Err(PdcpError::IntegrityFailure { count: 7 })
+        );
+        assert_eq!(gnb.unprotect(&pdu).unwrap(), sdu);
+    }
+
+    #[test]
+    #[should_panic(expected = "`integrity_key` is set without `mac_i`")]
+    fn integrity_key_without_mac_i() {
+        let mut cfg = config(Direction::Uplink, SnLength::Bits12);
+        cfg.mac_i = false;
+        let _ = PdcpSecurity::new(cfg);
+    }
+
+    #[test]
+    fn integrity_failure() {
+        let mut ue = PdcpSecurity::new(config(Direction::Uplink, SnLength::Bits12));
+        let mut gnb = PdcpSecurity::new(config(Direction::Downlink, SnLength::Bits12));
+
+        let mut pdu = ue.protect(0, &[0x00, 0x00], b"hello").unwrap();
+        pdu[3] ^= 0x01;
+        assert_eq!(
+            gnb.unprotect(&pdu),
+            Err(PdcpError::IntegrityFailure { count: 0 })
+        );
+        assert_eq!(gnb.integrity_failures(), 1);
+        assert_eq!(gnb.rx_deliv(), Some(0));
+
+        assert_eq!(gnb.unprotect(&pdu[..5]), Err(PdcpError::TooShort));
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    #[test]
+    fn hfn_tracking() {
+        let mut ue = PdcpSecurity::new(config(Direction::Uplink, SnLength::Bits12));
+        let mut gnb = PdcpSecurity::new(config(Direction::Downlink, SnLength::Bits12));
+
+        let mut last = Vec::new();
+        for count in 0..5000_u32 {
+            let sn = count & 0xfff;
+            let header = [(sn >> 8) as u8, sn as u8];
+            let sdu = count.to_be_bytes();
+            let pdu = ue.protect(sn, &header, &sdu).unwrap();
+            assert_eq!(gnb.unprotect(&pdu).unwrap(), sdu);
+            if count == 0xfff {
+                last = pdu;
+            }
+        }
+        assert_eq!(ue.tx_next(), Some(5000));
+        assert_eq!(gnb.rx_deliv(), Some(5000));
+
+        // an SN just behind the wrap keeps the previous HFN, and its COUNT was used
+        assert_eq!(
+            ue.protect(0xfff, &[0x0f, 0xff], b"late"),
+            Err(PdcpError::CountReuse { count: 0xfff })
+        );
+        assert_eq!(
+            gnb.unprotect(&last),
+            Err(PdcpError::Duplicate { count: 0xfff })
+        );
+        assert_eq!(ue.tx_next(), Some(5000));
+
+        assert_eq!(
+            ue.protect(0x1000, &[0x10, 0x00], b""),
+            Err(PdcpError::InvalidSn)
+        );
+        // header not matching the SN or the SN length
+        assert_eq!(
+            ue.protect(0x388, &[0x03, 0x89], b""),
+            Err(PdcpError::InvalidSn)
+        );
+        assert_eq!(
+            ue.protect(0x388, &[0x03, 0x88, 0x00], b""),
+            Err(PdcpError::InvalidSn)
+        );
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    #[test]
+    fn duplicates() {
+        let mut ue = PdcpSecurity::new(config(Direction::Uplink, SnLength::Bits12));
+        let mut gnb = PdcpSecurity::new(config(Direction::Downlink, SnLength::Bits12));
+
+        let pdus: Vec<_> = (0..6_u32)
+            .map(|sn| ue.protect(sn, &[0, sn as u8], &sn.to_be_bytes()).unwrap())
+            .collect();
+
+        // out of order
+        for i in [0, 2, 1, 5] {
+            assert_eq!(gnb.unprotect(&pdus[i]).unwrap(), (i as u32).to_be_bytes());
+        }
+        assert_eq!(gnb.rx_deliv(), Some(3));
+
+        // below RX_DELIV and above it
+        for (i, count) in [(1, 1), (5, 5)] {
+            assert_eq!(gnb.unprotect(&pdus[i]), Err(PdcpError::Duplicate { count }));
+        }
+        assert_eq!(gnb.unprotect(&pdus[3]).unwrap(), 3_u32.to_be_bytes());
+        assert_eq!(gnb.rx_deliv(), Some(4));
+
+        // PDUs lost for half the SN space are skipped
+        let header = [0x08, 0x05];
+        let pdu = ue.protect(0x805, &header, b"far").unwrap();
+        assert_eq!(gnb.unprotect(&pdu).unwrap(), b"far");
+        assert_eq!(gnb.rx_deliv(), Some(6));
+    }
+
+    #[test]
+    fn count_wrap() {
+        let mut cfg = config(Direction::Uplink, SnLength::Bits18);
+        cfg.tx_next = u32::MAX;
+        let mut ue = PdcpSecurity::new(cfg);
+
+        let header = [0x83, 0xff, 0xff];
+        assert!(ue.protect(0x3_ffff, &header, b"last").is_ok());
+        assert_eq!(ue.tx_next(), None);
+        assert_eq!(
+            ue.protect(0, &[0x80, 0, 0], b"next"),
+            Err(PdcpError::CountWrap)
+        );
+
+        let mut cfg = config(Direction::Downlink, SnLength::Bits18);
+        cfg.rx_deliv = u32::MAX - 1;
+        let mut gnb = PdcpSecurity::new(cfg);
+        assert_eq!(
+            gnb.unprotect(&[0x80, 0x00, 0x00, 0, 0, 0, 0]),
+            Err(PdcpError::CountWrap)
+        );
+    }
+
+    #[test]
+    fn out_of_window() {
+        // half the SN space behind COUNT 0 is stale, not a COUNT wrap
+        let mut gnb = PdcpSecurity::new(config(Direction::Downlink, SnLength::Bits18));
+        assert_eq!(
+            gnb.unprotect(&[0x82, 0x00, 0x00, 0, 0, 0, 0]),
+            Err(PdcpError::OutOfWindow { sn: 0x2_0000 })
+        );
+        assert_eq!(
+            PdcpError::OutOfWindow { sn: 0x2_0000 }.to_string(),
+            "SN out of window (SN = 0x20000)"
+        );
+
+        let mut cfg = config(Direction::Uplink, SnLength::Bits12);
+        cfg.tx_next = 0x10;
+        let mut ue = PdcpSecurity::new(cfg);
+        assert_eq!(
+            ue.protect(0x900, &[0x89, 0x00], b"stale"),
+            Err(PdcpError::OutOfWindow { sn: 0x900 })
+        );
+    }
+}
@@ -0,0 +1,622 @@
+//! Record layer over ZUC-256
+//!
+//! A record is `header || ciphertext || tag`, with an 11-byte header:
+//!
+//! | field             | bytes | value                                          |
+//! |-------------------|-------|------------------------------------------------|
+//! | content type      | 1     | chosen by the application                      |
+//! | sequence number   | 8     | big-endian                                     |
+//! | length            | 2     | length of `ciphertext || tag`, big-endian      |
+//!
+//! Every record has a 64-bit sequence number, starting at zero for each set of keys
+//! and counting the records sent. It is sent in the header, so records may be
+//! lost or reordered, as over a datagram transport.
+//! The record IV is the base IV with the 64-bit big-endian sequence number
+//! XOR-ed into its first 8 bytes.
+//!
+//! The payload is XOR-ed with the ZUC-256 keystream under the encryption key
+//! and the record IV. The tag is the 64-bit or 128-bit ZUC-256 MAC under the
+//! integrity key and the record IV over `header || ciphertext`.
+//!
+//! The receiver keeps a window of the last [`REPLAY_WINDOW`] sequence numbers
+//! below the highest one accepted, and rejects records accepted before or older
+//! than the window as replays. Only records passing the tag check move the window.
+//! Both ends stop after a configured number of records until new keys are installed.
+
+use crate::zuc256::{indexed_iv, Zuc256};
+use crate::zuc256_mac::zuc256_generate_mac;
+
+use std::fmt;
+
+use cipher::{KeyIvInit, StreamCipher};
+
+/// Length of the record header in bytes
+pub const HEADER_LEN: usize = 11;
+
+/// Maximum payload length in bytes
+pub const MAX_PAYLOAD_LEN: usize = 16384;
+
+/// Number of sequence numbers tracked by the replay window
+pub const REPLAY_WINDOW: u64 = 64;
+
+/// Record tag size
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TagSize {
+    /// 64-bit tag
+    Tag64,
+    /// 128-bit tag
+    Tag128,
+}
+
+impl TagSize {
+    /// Tag length in bytes
+    #[must_use]
+    pub fn bytes(self) -> usize {
+        match self {
+            TagSize::Tag64 => 8,
+            TagSize::Tag128 => 16,
+        }
+    }
+}
+
+/// Record keys
+#[derive(Clone, PartialEq, Eq)]
+pub struct RecordKeys {
+    /// Encryption key
+    pub enc_key: [u8; 32],
+    /// Integrity key
+    pub int_key: [u8; 32],
+    /// Base IV
+    pub iv: [u8; 23],
+}
+
+impl fmt::Debug for RecordKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RecordKeys { ... }")
+    }
+}
+
+/// Wipes the keys and the IV
+#[cfg(feature = "zeroize")]
+impl Drop for RecordKeys {
+    fn drop(&mut self) {
+        use zeroize::Zeroize;
+
+        self.enc_key.zeroize();
+        self.int_key.zeroize();
+        self.iv.zeroize();
+    }
+}
+
+/// The keys are wiped on drop
+#[cfg(feature = "zeroize")]
+#[cfg_attr(docsrs, doc(cfg(feature = "zeroize")))]
+impl zeroize::ZeroizeOnDrop for RecordKeys {}
+
+/// Record layer configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordConfig {
+    /// Keys
+    pub keys: RecordKeys,
+    /// Tag size
+    pub tag_size: TagSize,
+    /// Number of records after which new keys are required, `None` for no limit
+    pub rekey_after: Option<u64>,
+}
+
+/// Record layer errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordError {
+    /// The payload is longer than [`MAX_PAYLOAD_LEN`]
+    PayloadTooLong,
+    /// The buffer cannot hold the header, payload and tag
+    BufferTooSmall,
+    /// The record is malformed or its length does not match the header
+    InvalidRecord,
+    /// The configured number of records has been reached, new keys are required
+    RekeyRequired,
+    /// The 64-bit sequence number space is exhausted
+    SequenceExhausted,
+    /// The record has already been accepted or is older than the replay window
+    Replay {
+        /// sequence number of the record
+        seq: u64,
+    },
+    /// Tag verification failed
+    IntegrityFailure {
+        /// sequence number of the record
+        seq: u64,
+    },
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordError::PayloadTooLong => write!(f, "payload too long"),
+            RecordError::BufferTooSmall => write!(f, "buffer too small"),
+            RecordError::InvalidRecord => write!(f, "invalid record"),
+            RecordError::RekeyRequired => write!(f, "record limit reached, rekey required"),
+            RecordError::SequenceExhausted => write!(f, "sequence number exhausted"),
+            RecordError::Replay { seq } => write!(f, "replayed record (seq = {seq})"),
+            RecordError::IntegrityFailure { seq } => {
+                write!(f, "integrity check failed (seq = {seq})")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+/// Returns the total length of the record starting at `buf`,
+/// or `None` if `buf` is shorter than a header
+#[must_use]
+pub fn record_len(buf: &[u8]) -> Option<usize> {
+    let length = buf.get(HEADER_LEN - 2..HEADER_LEN)?;
+    Some(HEADER_LEN + usize::from(u16::from_be_bytes([length[0], length[1]])))
+}
+
+/// Applies the keystream of record `seq`
+fn apply_keystream(keys: &RecordKeys, seq: u64, payload: &mut [u8]) {
+    let mut zuc = Zuc256::new(&keys.enc_key.into(), &indexed_iv(&keys.iv, seq).into());
+    zuc.apply_keystream(payload);
+}
+
+/// Computes the tag of `header || ciphertext`
+///
+/// # Panics
+/// + Panics if the bit length is greater than `u32::MAX`.
+fn compute_tag(keys: &RecordKeys, tag_size: TagSize, seq: u64, m: &[u8], tag: &mut [u8]) {
+    let iv = indexed_iv(&keys.iv, seq);
+    let bitlen = u32::try_from(m.len() * 8).expect("record is too long");
+    match tag_size {
+        TagSize::Tag64 => {
+            let mac = zuc256_generate_mac::<u64>(&keys.int_key, &iv, bitlen, m);
+            tag.copy_from_slice(&mac.to_be_bytes());
+        }
+        TagSize::Tag128 => {
+            let mac = zuc256_generate_mac::<u128>(&keys.int_key, &iv, bitlen, m);
+            tag.copy_from_slice(&mac.to_be_bytes());
+        }
+    }
+}
+
+/// Checks the record limits of a sequence number
+fn check_limits(config: &RecordConfig, seq: u64) -> Result<(), RecordError> {
+    if seq == u64::MAX {
+        return Err(RecordError::SequenceExhausted);
+    }
+    match config.rekey_after {
+        Some(limit) if seq >= limit => Err(RecordError::RekeyRequired),
+        _ => Ok(()),
+    }
+}
+
+/// Sending end of the record layer
+#[derive(Debug, Clone)]
+pub struct RecordSealer {
+    /// configuration
+    config: RecordConfig,
+    /// sequence number of the next record
+    seq: u64,
+}
+
+impl RecordSealer {
+    /// Creates a record sealer
+    #[must_use]
+    pub fn new(config: RecordConfig) -> Self {
+        Self { config, seq: 0 }
+    }
+
+    /// Returns the sequence number of the next record
+    #[must_use]
+    pub fn sequence(&self) -> u64 {
+        self.seq
+    }
+
+    /// Installs new keys and restarts the sequence numbers
+    pub fn rekey(&mut self, keys: RecordKeys) {
+        self.config.keys = keys;
+        self.seq = 0;
+    }
+
+    /// Seals a record in place
+    ///
+    /// The payload is read from `buf[HEADER_LEN..HEADER_LEN + payload_len]`,
+    /// the record is written to the start of `buf`.
+    ///
+    /// Input:
+    /// - `content_type`:     record content type
+    /// - `buf`:              buffer holding the payload after room for the header
+    /// - `payload_len`:      payload length in bytes
+    ///
+    /// Output:
+    /// - `usize`:    the record length
+    ///
+    /// # Errors
+    /// + Returns [`RecordError::PayloadTooLong`] if the payload is longer than [`MAX_PAYLOAD_LEN`].
+    /// + Returns [`RecordError::BufferTooSmall`] if `buf` cannot hold the record.
+    /// + Returns [`RecordError::SequenceExhausted`] if the sequence numbers are exhausted.
+    /// + Returns [`RecordError::RekeyRequired`] if the record limit is reached.
+    ///
+    /// # Panics
+    /// + Panics if the record bit length is greater than `u32::MAX`.
+    pub fn seal(
+        &mut self,
+        content_type: u8,
+        buf: &mut [u8],
+        payload_len: usize,
+    ) -> Result<usize, RecordError> {
+        if payload_len > MAX_PAYLOAD_LEN {
+            return Err(RecordError::PayloadTooLong);
+        }
+        let tag_len = self.config.tag_size.bytes();
+        let body_len = payload_len + tag_len;
+        let total = HEADER_LEN + body_len;
+        if buf.len() < total {
+            return Err(RecordError::BufferTooSmall);
+        }
+        check_limits(&self.config, self.seq)?;
+
+        let seq = self.seq;
+        let length = u16::try_from(body_len).expect("record length is bounded");
+        buf[0] = content_type;
+        buf[1..9].copy_from_slice(&seq.to_be_bytes());
+        buf[9..11].copy_from_slice(&length.to_be_bytes());
+
+        let keys = &self.config.keys;
+        let (record, tag) = buf[..total].split_at_mut(HEADER_LEN + payload_len);
+        apply_keystream(keys, seq, &mut record[HEADER_LEN..]);
+        compute_tag(keys, self.config.tag_size, seq, record, tag);
+
+        self.seq += 1;
+        Ok(total)
+    }
+}
+
+/// Receiving end of the record layer
+#[derive(Debug, Clone)]
+pub struct RecordOpener {
+    /// configuration
+    config: RecordConfig,
+    /// highest accepted sequence number plus one, zero before the first record
+    next: u64,
+    /// replay window, bit `i` is set if `next - 1 - i` has been accepted
+    window: u64,
+}
+
+impl RecordOpener {
+    /// Creates a record opener
+    #[must_use]
+    pub fn new(config: RecordConfig) -> Self {
+        Self {
+            config,
+            next: 0,
+            window: 0,
+        }
+    }
+
+    /// Returns the highest accepted sequence number plus one
+    #[must_use]
+    pub fn sequence(&self) -> u64 {
+        self.next
+    }
+
+    /// Installs new keys and restarts the sequence numbers
+    pub fn rekey(&mut self, keys: RecordKeys) {
+        self.config.keys = keys;
+        self.next = 0;
+        self.window = 0;
+    }
+
+    /// Checks the replay window
+    fn check_replay(&self, seq: u64) -> Result<(), RecordError> {
+        if seq >= self.next {
+            return Ok(());
+        }
+        let age = self.next - 1 - seq;
+        if age >= REPLAY_WINDOW || self.window & (1 << age) != 0 {
+            return Err(RecordError::Replay { seq });
+        }
+        Ok(())
+    }
+
+    /// Marks a sequence number as accepted
+    fn accept(&mut self, seq: u64) {
+        if seq >= self.next {
+            let shift = seq - self.next + 1;
+            self.window = if shift >= REPLAY_WINDOW {
+                0
+            } else {
+                self.window << shift
+            };
+            self.window |= 1;
+            self.next = seq + 1;
+        } else {
+            self.window |= 1 << (self.next - 1 - seq);
+        }
+    }
+
+    /// Opens a record in place
+    ///
+    /// `record` must hold exactly one record, see [`record_len`].
+    /// On success the payload is decrypted in place.
+    ///
+    /// Input:
+    /// - `record`:   the record
+    ///
+    /// Output:
+    /// - `u8`:       the content type
+    /// - `&[u8]`:    the payload
+    ///
+    /// # Errors
+    /// + Returns [`RecordError::InvalidRecord`] if the record is malformed.
+    /// + Returns [`RecordError::SequenceExhausted`] if the sequence numbers are exhausted.
+    /// + Returns [`RecordError::RekeyRequired`] if the record limit is reached.
+    /// + Returns [`RecordError::Replay`] if the record has been accepted before or is too old.
+    /// + Returns [`RecordError::IntegrityFailure`] if tag verification fails.
+    ///
+    /// # Panics
+    /// + Panics if the record bit length is greater than `u32::MAX`.
+    pub fn open<'a>(&mut self, record: &'a mut [u8]) -> Result<(u8, &'a [u8]), RecordError> {
+        let tag_len = self.config.tag_size.bytes();
+        if record_len(record) != Some(record.len()) || record.len() < HEADER_LEN + tag_len {
+            return Err(RecordError::InvalidRecord);
+        }
+        if record.len() - HEADER_LEN - tag_len > MAX_PAYLOAD_LEN {
+            return Err(RecordError::InvalidRecord);
+        }
+        let content_type = record[0];
+        let seq = u64::from_be_bytes(record[1..9].try_into().expect("header is 11 bytes"));
+        check_limits(&self.config, seq)?;
+        self.check_replay(seq)?;
+
+        let keys = &self.config.keys;
+        let (body, tag) = record.split_at_mut(record.len() - tag_len);
+        let mut expected = [0; 16];
+        compute_tag(
+            keys,
+            self.config.tag_size,
+            seq,
+            body,
+            &mut expected[..tag_len],
+        );
+        if expected[..tag_len] != *tag {
+            return Err(RecordError::IntegrityFailure { seq });
+        }
+        apply_keystream(keys, seq, &mut body[HEADER_LEN..]);
+
+        self.accept(seq);
+        Ok((content_type, &body[HEADER_LEN..]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(tag_size: TagSize, rekey_after: Option<u64>) -> RecordConfig {
+        RecordConfig {
+            keys: RecordKeys {
+                enc_key: [0x01; 32],
+                int_key: [0x02; 32],
+                iv: [0x03; 23],
+            },
+            tag_size,
+            rekey_after,
+        }
+    }
+
+    fn seal(sealer: &mut RecordSealer, payload: &[u8]) -> Vec<u8> {
+        let mut buf = vec![0; HEADER_LEN + payload.len() + 16];
+        buf[HEADER_LEN..HEADER_LEN + payload.len()].copy_from_slice(payload);
+        let n = sealer.seal(23, &mut buf, payload.len()).unwrap();
+        buf.truncate(n);
+        buf
+    }
+
+    #[test]
+    fn roundtrip() {
+        for tag_size in [TagSize::Tag64, TagSize::Tag128] {
+            let mut sealer = RecordSealer::new(config(tag_size, None));
+            let mut opener = RecordOpener::new(config(tag_size, None));
+            for len in [0, 1, 100, MAX_PAYLOAD_LEN] {
+                let payload = vec![0x5a; len];
+                let mut record = seal(&mut sealer, &payload);
+                assert_eq!(record.len(), HEADER_LEN + len + tag_size.bytes());
+                assert_eq!(record_len(&record), Some(record.len()));
+                if len > 0 {
+                    assert_ne!(&record[HEADER_LEN..HEADER_LEN + len], &payload[..]);
+                }
+                let (content_type, plain) = opener.open(&mut record).unwrap();
+                assert_eq!(content_type, 23);
+                assert_eq!(plain, payload);
+            }
+            assert_eq!(sealer.sequence(), 4);
+        }
+
+        let mut sealer = RecordSealer::new(config(TagSize::Tag64, None));
+        let mut buf = vec![0; MAX_PAYLOAD_LEN + 100];
+        assert_eq!(
+            sealer.seal(0, &mut buf, MAX_PAYLOAD_LEN + 1),
+            Err(RecordError::PayloadTooLong)
+        );
+        assert_eq!(
+            sealer.seal(0, &mut buf[..20], 10),
+            Err(RecordError::BufferTooSmall)
+        );
+        assert_eq!(sealer.sequence(), 0);
+    }
+
+    #[test]
+    fn tampering() {
+        let mut sealer = RecordSealer::new(config(TagSize::Tag128, None));
+        let mut opener = RecordOpener::new(config(TagSize::Tag128, None));
+        let record = seal(&mut sealer, b"telemetry");
+
+        for pos in 0..record.len() {
+            let mut r = record.clone();
+            r[pos] ^= 0x80;
+            let err = opener.open(&mut r).unwrap_err();
+            if pos == 9 || pos == 10 {
+                assert_eq!(err, RecordError::InvalidRecord);
+            } else if (1..9).contains(&pos) {
+                let seq = 0x80 << (8 * (8 - pos));
+                assert_eq!(err, RecordError::IntegrityFailure { seq });
+            } else {
+                assert_eq!(err, RecordError::IntegrityFailure { seq: 0 });
+            }
+        }
+        assert_eq!(
+            opener.open(&mut record[..10].to_vec()),
+            Err(RecordError::InvalidRecord)
+        );
+
+        // wrong keys and tag size
+        let mut other = config(TagSize::Tag128, None);
+        other.keys.int_key[0] ^= 1;
+        let mut r = record.clone();
+        assert_eq!(
+            RecordOpener::new(other).open(&mut r),
+            Err(RecordError::IntegrityFailure { seq: 0 })
+        );
+        let mut r = record.clone();
+        assert_eq!(
+            RecordOpener::new(config(TagSize::Tag64, None)).open(&mut r),
+            Err(RecordError::IntegrityFailure { seq: 0 })
+        );
+
+        let mut r = record.clone();
+        assert_eq!(opener.open(&mut r).unwrap().1, b"telemetry");
+    }
+
+    #[test]
+    fn replay() {
+        let mut sealer = RecordSealer::new(config(TagSize::Tag64, None));
+        let mut opener = RecordOpener::new(config(TagSize::Tag64, None));
+        let records: Vec<Vec<u8>> = (0..100u8).map(|i| seal(&mut sealer, &[i])).collect();
+
+        // in order, then replayed
+        for i in 0..3u8 {
+            assert_eq!(
+                opener.open(&mut records[usize::from(i)].clone()).unwrap().1,
+                [i]
+            );
+        }
+        assert_eq!(
+            opener.open(&mut records[1].clone()),
+            Err(RecordError::Replay { seq: 1 })
+        );
+
+        // lost and reordered within the window
+        assert!(opener.open(&mut records[10].clone()).is_ok());
+        assert!(opener.open(&mut records[5].clone()).is_ok());
+        assert_eq!(
+            opener.open(&mut records[5].clone()),
+            Err(RecordError::Replay { seq: 5 })
+        );
+        assert_eq!(
+            opener.open(&mut records[10].clone()),
+            Err(RecordError::Replay { seq: 10 })
+        );
+        assert!(opener.open(&mut records[4].clone()).is_ok());
+        assert_eq!(opener.sequence(), 11);
+
+        // older than the window
+        assert!(opener.open(&mut records[80].clone()).is_ok());
+        assert_eq!(
+            opener.open(&mut records[16].clone()),
+            Err(RecordError::Replay { seq: 16 })
+        );
+        assert!(opener.open(&mut records[17].clone()).is_ok());
+
+        // a forged record is not marked as accepted
+        let mut r = records[20].clone();
+        r[HEADER_LEN] ^= 1;
+        assert_eq!(
+            opener.open(&mut r),
+            Err(RecordError::IntegrityFailure { seq: 20 })
+        );
+        assert!(opener.open(&mut records[20].clone()).is_ok());
+
+        // nor is one claiming a fresh sequence number
+        let mut r = records[21].clone();
+        r[1..9].copy_from_slice(&90_u64.to_be_bytes());
+        assert_eq!(
+            opener.open(&mut r),
+            Err(RecordError::IntegrityFailure { seq: 90 })
+        );
+        assert_eq!(opener.sequence(), 81);
+        assert!(opener.open(&mut records[90].clone()).is_ok());
+    }
+
+    #[test]
+    fn redacted_debug() {
+        let config = config(TagSize::Tag64, None);
+        let opener = RecordOpener::new(config.clone());
+        for text in [format!("{config:?}"), format!("{opener:?}")] {
+            assert!(text.contains("RecordKeys { ... }"));
+            assert!(!text.contains("enc_key"));
+        }
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn zeroize() {
+        fn zeroize_on_drop<T: zeroize::ZeroizeOnDrop>() {}
+        zeroize_on_drop::<RecordKeys>();
+    }
+
+    #[test]
+    fn sequence_exhaustion() {
+        let mut sealer = RecordSealer::new(config(TagSize::Tag64, None));
+        let mut opener = RecordOpener::new(config(TagSize::Tag64, None));
+        sealer.seq = u64::MAX - 1;
+        let mut record = seal(&mut sealer, b"last");
+        assert!(opener.open(&mut record.clone()).is_ok());
+        assert_eq!(opener.sequence(), u64::MAX);
+
+        let mut buf = [0; 32];
+        assert_eq!(
+            sealer.seal(0, &mut buf, 1),
+            Err(RecordError::SequenceExhausted)
+        );
+        assert_eq!(sealer.sequence(), u64::MAX);
+        assert_eq!(
+            opener.open(&mut record.clone()),
+            Err(RecordError::Replay { seq: u64::MAX - 1 })
+        );
+        record[1..9].copy_from_slice(&u64::MAX.to_be_bytes());
+        assert_eq!(
+            opener.open(&mut record),
+            Err(RecordError::SequenceExhausted)
+        );
+    }
+
+    #[test]
+    fn rekey() {
+        let mut sealer = RecordSealer::new(config(TagSize::Tag128, Some(2)));
+        let mut opener = RecordOpener::new(config(TagSize::Tag128, Some(2)));
+        for _ in 0..2 {
+            let mut record = seal(&mut sealer, b"data");
+            assert!(opener.open(&mut record).is_ok());
+        }
+        let mut buf = [0; 64];
+        assert_eq!(sealer.seal(0, &mut buf, 4), Err(RecordError::RekeyRequired));
+
+        let mut keys = config(TagSize::Tag128, None).keys;
+        keys.enc_key = [0x11; 32];
+        keys.int_key = [0x22; 32];
+        sealer.rekey(keys.clone());
+        assert_eq!(sealer.sequence(), 0);
+
+        // the opener still uses the old keys
+        let mut record = seal(&mut sealer, b"data");
+        assert_eq!(
+            opener.open(&mut record.clone()),
+            Err(RecordError::Replay { seq: 0 })
+        );
+
+        opener.rekey(keys);
+        assert_eq!(opener.open(&mut record).unwrap().1, b"data");
+    }
+}
@@ -0,0 +1,264 @@
+//! Parameterised ZUC constructions for cryptanalysis experiments
+//!
+//! [`ZucBuilder`] loads a key and IV with the ZUC-128 or ZUC-256 layout and lets
+//! the `D` constants, the number of initialization rounds and the S boxes be replaced.
+//! Without changes it builds the standard generators: the keystream of
+//! [`ZucBuilder::zuc128`] equals [`Zuc128Core`](crate::Zuc128Core) and the keystream of
+//! [`ZucBuilder::zuc256`] equals [`Zuc256Core`](crate::Zuc256Core).
+//!
+//! The modified constructions are **not** secure ciphers.
+
+use crate::zuc::{Zuc, S0, S1};
+use crate::{zuc128, zuc256};
+
+use cipher::consts::{U1, U4};
+
+/// Key and IV loading
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    /// ZUC-128 key and IV
+    Zuc128 {
+        /// key
+        key: [u8; 16],
+        /// iv
+        iv: [u8; 16],
+    },
+    /// ZUC-256 key and IV
+    Zuc256 {
+        /// key
+        key: [u8; 32],
+        /// iv
+        iv: [u8; 23],
+    },
+}
+
+/// Builder of a [`ZucResearchCore`]
+#[derive(Debug, Clone)]
+pub struct ZucBuilder {
+    /// key and IV loading
+    layout: Layout,
+    /// d constants
+    d: [u16; 16],
+    /// number of initialization rounds
+    init_rounds: u32,
+    /// S0 box
+    s0: [u8; 256],
+    /// S1 box
+    s1: [u8; 256],
+}
+
+impl ZucBuilder {
+    /// Creates a builder loading a key and IV with the ZUC-128 layout
+    ///
+    /// The `D` constants are the 15-bit constants of ZUC-128.
+    #[must_use]
+    pub fn zuc128(key: &[u8; 16], iv: &[u8; 16]) -> Self {
+        let layout = Layout::Zuc128 { key: *key, iv: *iv };
+        let d = zuc128::D.map(|d| u16::try_from(d >> 8).unwrap_or(u16::MAX));
+        Self::with_layout(layout, d)
+    }
+
+    /// Creates a builder loading a key and IV with the ZUC-256 layout
+    ///
+    /// The `D` constants are the 7-bit constants of the ZUC-256 keystream generator.
+    #[must_use]
+    pub fn zuc256(key: &[u8; 32], iv: &[u8; 23]) -> Self {
+        let layout = Layout::Zuc256 { key: *key, iv: *iv };
+        Self::with_layout(layout, zuc256::D.map(u16::from))
+    }
+
+    /// Creates a builder with the standard parameters
+    fn with_layout(layout: Layout, d: [u16; 16]) -> Self {
+        Self {
+            layout,
+            d,
+            init_rounds: 32,
+            s0: S0,
+            s1: S1,
+        }
+    }
+
+    /// Sets the `D` constants
+    ///
+    /// # Panics
+    /// + Panics if a constant is wider than 15 bits with the ZUC-128 layout,
+    ///   or wider than 7 bits with the ZUC-256 layout.
+    #[must_use]
+    pub fn d_constants(mut self, d: [u16; 16]) -> Self {
+        let bits = match self.layout {
+            Layout::Zuc128 { .. } => 15,
+            Layout::Zuc256 { .. } => 7,
+        };
+        assert!(
+            d.iter().all(|&d| d >> bits == 0),
+            "d constant wider than {bits} bits"
+        );
+        self.d = d;
+        self
+    }
+
+    /// Sets the number of initialization rounds, 32 in the standard
+    #[must_use]
+    pub fn init_rounds(mut self, rounds: u32) -> Self {
+        self.init_rounds = rounds;
+        self
+    }
+
+    /// Replaces the S boxes `S0` and `S1`
+    #[must_use]
+    pub fn sboxes(mut self, s0: &[u8; 256], s1: &[u8; 256]) -> Self {
+        self.s0 = *s0;
+        self.s1 = *s1;
+        self
+    }
+
+    /// Loads the key and IV and runs the initialization stage
+    #[must_use]
+    pub fn build(self) -> ZucResearchCore {
+        let mut zuc = match self.layout {
+            Layout::Zuc128 { key, iv } => {
+                zuc128::load(&key, &iv, &self.d.map(|d| u32::from(d) << 8))
+            }
+            Layout::Zuc256 { key, iv } => {
+                let d = self.d.map(|d| u8::try_from(d).unwrap_or(u8::MAX));
+                zuc256::load(&key, &iv, &d)
+            }
+        };
+        zuc.init_with(self.init_rounds, &self.s0, &self.s1);
+        ZucResearchCore {
+            core: zuc,
+            s0: self.s0,
+            s1: self.s1,
+        }
+    }
+}
+
+/// Parameterised ZUC stream cipher
+pub type ZucResearch = cipher::StreamCipherCoreWrapper<ZucResearchCore>;
+
+/// Parameterised ZUC keystream generator, created by [`ZucBuilder`]
+#[derive(Debug, Clone)]
+pub struct ZucResearchCore {
+    /// zuc core
+    core: Zuc,
+    /// S0 box
+    s0: [u8; 256],
+    /// S1 box
+    s1: [u8; 256],
+}
+
+impl ZucResearchCore {
+    /// Generates the next 32-bit word in the keystream
+    pub fn generate(&mut self) -> u32 {
+        self.core.generate_with(&self.s0, &self.s1)
+    }
+}
+
+impl Iterator for ZucResearchCore {
+    type Item = u32;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.generate())
+    }
+}
+
+impl cipher::AlgorithmName for ZucResearchCore {
+    fn write_alg_name(f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "ZucResearch")
+    }
+}
+
+impl cipher::BlockSizeUser for ZucResearchCore {
+    type BlockSize = U4;
+}
+
+impl cipher::ParBlocksSizeUser for ZucResearchCore {
+    type ParBlocksSize = U1;
+}
+
+impl cipher::StreamBackend for ZucResearchCore {
+    fn gen_ks_block(&mut self, block: &mut cipher::Block<Self>) {
+        let z = self.generate();
+        block.copy_from_slice(&z.to_be_bytes());
+    }
+}
+
+impl cipher::StreamCipherCore for ZucResearchCore {
+    fn remaining_blocks(&self) -> Option<usize> {
+        None
+    }
+
+    fn process_with_backend(&mut self, f: impl cipher::StreamClosure<BlockSize = Self::BlockSize>) {
+        f.call(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Zuc128, Zuc128Core, Zuc256Core};
+
+    use cipher::{KeyIvInit, StreamCipher};
+
+    #[test]
+    fn standard() {
+        for _ in 0..20 {
+            let k: [u8; 16] = rand::random();
+            let iv: [u8; 16] = rand::random();
+            let research = ZucBuilder::zuc128(&k, &iv).build();
+            let standard = Zuc128Core::new(&k, &iv);
+            assert!(research.zip(standard).take(100).all(|(a, b)| a == b));
+
+            let k: [u8; 32] = rand::random();
+            let iv: [u8; 23] = rand::random();
+            let research = ZucBuilder::zuc256(&k, &iv).build();
+            let standard = Zuc256Core::new(&k, &iv);
+            assert!(research.zip(standard).take(100).all(|(a, b)| a == b));
+        }
+
+        let (k, iv) = ([0x3d; 16], [0x84; 16]);
+        let mut expected = vec![0u8; 1001];
+        Zuc128::new(&k.into(), &iv.into()).apply_keystream(&mut expected);
+        let mut buf = vec![0u8; 1001];
+        ZucResearch::from_core(ZucBuilder::zuc128(&k, &iv).build()).apply_keystream(&mut buf);
+        assert_eq!(buf, expected);
+    }
+
+    #[test]
+    fn parameters() {
+        let (k, iv) = ([0; 32], [0; 23]);
+        let first = |builder: ZucBuilder| builder.build().generate();
+        let standard = first(ZucBuilder::zuc256(&k, &iv));
+
+        let d = zuc256::D.map(u16::from);
+        let mut tweaked = d;
+        tweaked[0] ^= 1;
+        assert_ne!(
+            first(ZucBuilder::zuc256(&k, &iv).d_constants(tweaked)),
+            standard
+        );
+        assert_eq!(first(ZucBuilder::zuc256(&k, &iv).d_constants(d)), standard);
+
+        assert_ne!(first(ZucBuilder::zuc256(&k, &iv).init_rounds(8)), standard);
+
+        assert_ne!(
+            first(ZucBuilder::zuc256(&k, &iv).sboxes(&S1, &S0)),
+            standard
+        );
+
+        // zero rounds leave the loaded LFSR in place
+        let mut core = ZucBuilder::zuc128(&[0; 16], &[0; 16])
+            .init_rounds(0)
+            .build();
+        let loaded = zuc128::load(&[0; 16], &[0; 16], &zuc128::D);
+        assert_eq!(core.core.s, loaded.s);
+        let _ = core.generate();
+    }
+
+    #[test]
+    #[should_panic(expected = "d constant wider than 7 bits")]
+    fn wide_d() {
+        let _ = ZucBuilder::zuc256(&[0; 32], &[0; 23]).d_constants([0x80; 16]);
+    }
+}
@@ -0,0 +1,258 @@
+//! Deterministic random bit generator over the ZUC-256 keystream
+//!
+//! [`ZucRng`] outputs the ZUC-256 keystream of its seed, each 32-bit word
+//! in big-endian byte order, so the output is the same on every platform.
+//! `next_u32` and `next_u64` read the next 4 or 8 bytes of that byte stream
+//! as big-endian integers.
+//!
+//! With a reseed interval, after every interval of output the generator draws
+//! a new 256-bit key and 184-bit IV from its own keystream and restarts,
+//! so earlier output cannot be recovered from a later state.
+
+use crate::zuc256::Zuc256Core;
+
+use rand_core::{CryptoRng, Error, RngCore, SeedableRng};
+
+/// Number of keystream words generated at a time
+const BLOCK_WORDS: usize = 16;
+
+/// Number of bytes generated at a time
+const BLOCK_LEN: usize = BLOCK_WORDS * 4;
+
+/// Seed length in bytes, a 256-bit key followed by a 184-bit IV
+const SEED_LEN: usize = 55;
+
+/// Seed of a [`ZucRng`], a 256-bit key followed by a 184-bit IV
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZucRngSeed(pub [u8; SEED_LEN]);
+
+impl Default for ZucRngSeed {
+    fn default() -> Self {
+        Self([0; SEED_LEN])
+    }
+}
+
+impl AsMut<[u8]> for ZucRngSeed {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+impl AsRef<[u8]> for ZucRngSeed {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Deterministic random bit generator over the ZUC-256 keystream
+///
+/// With the `getrandom` feature, `SeedableRng::from_entropy` seeds it from the
+/// operating system.
+#[derive(Clone)]
+pub struct ZucRng {
+    /// keystream generator
+    core: Zuc256Core,
+    /// generated bytes
+    buf: [u8; BLOCK_LEN],
+    /// read position in `buf`
+    pos: usize,
+    /// number of output bytes between reseeds, `None` for no reseed
+    reseed_interval: Option<u64>,
+    /// number of bytes generated since the last reseed
+    generated: u64,
+}
+
+impl ZucRng {
+    /// Creates a generator from a key and an IV
+    #[must_use]
+    pub fn new(key: &[u8; 32], iv: &[u8; 23]) -> Self {
+        Self {
+            core: Zuc256Core::new(key, iv),
+            buf: [0; BLOCK_LEN],
+            pos: BLOCK_LEN,
+            reseed_interval: None,
+            generated: 0,
+        }
+    }
+
+    /// Sets the reseed interval
+    ///
+    /// The generator reseeds itself at the first 64-byte block boundary after
+    /// `bytes` bytes of output.
+    ///
+    /// # Panics
+    /// + Panics if `bytes` is zero.
+    #[must_use]
+    pub fn with_reseed_interval(mut self, bytes: u64) -> Self {
+        assert!(bytes > 0, "reseed interval is zero");
+        self.reseed_interval = Some(bytes);
+        self
+    }
+
+    /// Draws a new key and IV from the keystream and restarts the generator
+    ///
+    /// With the `zeroize` feature, the drawn key and IV and the buffered output
+    /// are wiped.
+    pub fn reseed(&mut self) {
+        let mut seed = [0; 56];
+        for chunk in seed.chunks_exact_mut(4) {
+            chunk.copy_from_slice(&self.core.generate().to_be_bytes());
+        }
+        let mut key = [0; 32];
+        let mut iv = [0; 23];
+        key.copy_from_slice(&seed[..32]);
+        iv.copy_from_slice(&seed[32..55]);
+        self.core = Zuc256Core::new(&key, &iv);
+        #[cfg(feature = "zeroize")]
+        {
+            use zeroize::Zeroize;
+            seed.zeroize();
+            key.zeroize();
+            iv.zeroize();
+            self.buf.zeroize();
+        }
+        self.pos = BLOCK_LEN;
+        self.generated = 0;
+    }
+
+    /// Refills the buffer
+    fn refill(&mut self) {
+        if let Some(interval) = self.reseed_interval {
+            if self.generated >= interval {
+                self.reseed();
+            }
+        }
+        for chunk in self.buf.chunks_exact_mut(4) {
+            chunk.copy_from_slice(&self.core.generate().to_be_bytes());
+        }
+        self.pos = 0;
+        self.generated += BLOCK_LEN as u64;
+    }
+
+    /// Reads the next `N` bytes
+    fn next_array<const N: usize>(&mut self) -> [u8; N] {
+        let mut out = [0; N];
+        self.fill_bytes(&mut out);
+        out
+    }
+}
+
+impl core::fmt::Debug for ZucRng {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("ZucRng { ... }")
+    }
+}
+
+impl RngCore for ZucRng {
+    fn next_u32(&mut self) -> u32 {
+        u32::from_be_bytes(self.next_array())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        u64::from_be_bytes(self.next_array())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        let mut filled = 0;
+        while filled < dest.len() {
+            if self.pos == BLOCK_LEN {
+                self.refill();
+            }
+            let n = (dest.len() - filled).min(BLOCK_LEN - self.pos);
+            dest[filled..filled + n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+            self.pos += n;
+            filled += n;
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for ZucRng {}
+
+impl SeedableRng for ZucRng {
+    type Seed = ZucRngSeed;
+
+    fn from_seed(seed: ZucRngSeed) -> Self {
+        let (key, iv) = seed.0.split_at(32);
+        let key: &[u8; 32] = key.try_into().expect("32 bytes");
+        let iv: &[u8; 23] = iv.try_into().expect("23 bytes");
+        Self::new(key, iv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static KEY: [u8; 32] = [0xff; 32];
+    static IV: [u8; 23] = [0xff; 23];
+
+    fn keystream(core: &mut Zuc256Core, words: usize) -> Vec<u8> {
+        (0..words)
+            .flat_map(|_| core.generate().to_be_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn keystream_order() {
+        // ZUC-256 test vector, key and IV all ones
+        let mut rng = ZucRng::new(&KEY, &IV);
+        assert_eq!(rng.next_u32(), 0x3356_cbae);
+        assert_eq!(rng.next_u64(), 0xd1a1_c18b_6baa_4ffe);
+
+        let mut seed = ZucRngSeed::default();
+        seed.as_mut()[..32].copy_from_slice(&KEY);
+        seed.as_mut()[32..].copy_from_slice(&IV);
+        let mut rng = ZucRng::from_seed(seed);
+        let expected = keystream(&mut Zuc256Core::new(&KEY, &IV), 100);
+        let mut out = vec![0; 400];
+        for piece in out.chunks_mut(7) {
+            rng.fill_bytes(piece);
+        }
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn reseed() {
+        let mut rng = ZucRng::new(&KEY, &IV).with_reseed_interval(100);
+        let mut out = vec![0; 256];
+        rng.fill_bytes(&mut out);
+
+        // reseeded after two 64-byte blocks
+        let mut core = Zuc256Core::new(&KEY, &IV);
+        let mut expected = keystream(&mut core, 32);
+        let seed = keystream(&mut core, 14);
+        let key: [u8; 32] = seed[..32].try_into().unwrap();
+        let iv: [u8; 23] = seed[32..55].try_into().unwrap();
+        let mut core = Zuc256Core::new(&key, &iv);
+        expected.extend(keystream(&mut core, 32));
+        assert_eq!(out, expected);
+
+        let mut a = ZucRng::new(&KEY, &IV);
+        let mut b = a.clone();
+        b.reseed();
+        assert_ne!(a.next_u64(), b.next_u64());
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn reseed_clears_buffer() {
+        let mut rng = ZucRng::new(&KEY, &IV);
+        rng.next_u32();
+        rng.reseed();
+        assert_eq!(rng.buf, [0; BLOCK_LEN]);
+        assert_eq!(rng.pos, BLOCK_LEN);
+    }
+
+    #[cfg(feature = "getrandom")]
+    #[test]
+    fn from_entropy() {
+        let mut a = ZucRng::from_entropy();
+        let mut b = ZucRng::from_entropy();
+        assert_ne!(a.next_u64(), b.next_u64());
+    }
+}
@@ -0,0 +1,260 @@
+//! Power-on known-answer self-tests
+
+use crate::zuc::{S0, S1};
+use crate::{eea3_128_encrypt, eia3_128_generate_mac, zuc128, zuc256, zuc256_generate_mac};
+
+use std::fmt;
+
+use const_str::hex;
+
+/// Known-answer test that did not produce the expected output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelfTestError {
+    /// S box tables
+    SBox,
+    /// ZUC-128 keystream
+    Zuc128Keystream,
+    /// ZUC-256 keystream
+    Zuc256Keystream,
+    /// 128-EEA3 encryption
+    Eea3,
+    /// 128-EIA3 MAC
+    Eia3,
+    /// ZUC-256 MAC with a tag of `bits` bits
+    Zuc256Mac {
+        /// tag size in bits
+        bits: u32,
+    },
+}
+
+impl fmt::Display for SelfTestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "known-answer test failed: ")?;
+        match self {
+            SelfTestError::SBox => write!(f, "S box tables"),
+            SelfTestError::Zuc128Keystream => write!(f, "ZUC-128 keystream"),
+            SelfTestError::Zuc256Keystream => write!(f, "ZUC-256 keystream"),
+            SelfTestError::Eea3 => write!(f, "128-EEA3"),
+            SelfTestError::Eia3 => write!(f, "128-EIA3"),
+            SelfTestError::Zuc256Mac { bits } => write!(f, "ZUC-256 MAC ({bits} bit tag)"),
+        }
+    }
+}
+
+impl std::error::Error for SelfTestError {}
+
+/// FNV-1a digests of the S boxes `S0` and `S1`
+const SBOX_DIGESTS: [u32; 2] = [0xa287_2d09, 0x8908_f2ab];
+
+/// ZUC-128 keystream examples: key, iv and the first keystream words
+static ZUC128_KEYSTREAM: [([u8; 16], [u8; 16], [u32; 2]); 2] = [
+    ([0; 16], [0; 16], [0x27be_de74, 0x0180_82da]),
+    ([0xff; 16], [0xff; 16], [0x0657_cfa0, 0x7096_398b]),
+];
+
+/// ZUC-256 keystream examples: key, iv and the first keystream words
+static ZUC256_KEYSTREAM: [([u8; 32], [u8; 23], [u32; 4]); 2] = [
+    (
+        [0; 32],
+        [0; 23],
+        [0x58d0_3ad6, 0x2e03_2ce2, 0xdafc_683a, 0x39bd_cb03],
+    ),
+    (
+        [0xff; 32],
+        [0xff; 23],
+        [0x3356_cbae, 0xd1a1_c18b, 0x6baa_4ffe, 0x343f_777c],
+    ),
+];
+
+/// Runs the known-answer tests of every algorithm
+///
+/// The tests check the digests of the S box tables and cover the ZUC-128 and ZUC-256 keystreams, 128-EEA3, 128-EIA3
+/// and the ZUC-256 MAC with 32, 64 and 128 bit tags, using the examples of the standards.
+///
+/// # Errors
+/// + Returns the first test whose output differs from the expected one.
+pub fn self_test() -> Result<(), SelfTestError> {
+    let _running = Running::enter();
+    sbox_check(&S0, &S1)?;
+    keystream_kats(&S0, &S1)?;
+    mode_kats()
+}
+
+/// Compares the digests of the S boxes `s0`, `s1` with the ones of the standard
+///
+/// The keystream examples read only part of the tables, a digest covers every entry.
+fn sbox_check(s0: &[u8; 256], s1: &[u8; 256]) -> Result<(), SelfTestError> {
+    let fnv1a = |table: &[u8; 256]| {
+        table.iter().fold(0x811c_9dc5_u32, |h, &b| {
+            (h ^ u32::from(b)).wrapping_mul(0x0100_0193)
+        })
+    };
+    if [fnv1a(s0), fnv1a(s1)] == SBOX_DIGESTS {
+        Ok(())
+    } else {
+        Err(SelfTestError::SBox)
+    }
+}
+
+/// Keystream known-answer tests with the S boxes `s0`, `s1`
+fn keystream_kats(s0: &[u8; 256], s1: &[u8; 256]) -> Result<(), SelfTestError> {
+    for (k, iv, expected) in &ZUC128_KEYSTREAM {
+        let mut zuc = zuc128::load(k, iv, &zuc128::D);
+        zuc.init_with(32, s0, s1);
+        if expected.iter().any(|&z| zuc.generate_with(s0, s1) != z) {
+            return Err(SelfTestError::Zuc128Keystream);
+        }
+    }
+    for (k, iv, expected) in &ZUC256_KEYSTREAM {
+        let mut zuc = zuc256::load(k, iv, &zuc256::D);
+        zuc.init_with(32, s0, s1);
+        if expected.iter().any(|&z| zuc.generate_with(s0, s1) != z) {
+            return Err(SelfTestError::Zuc256Keystream);
+        }
+    }
+    Ok(())
+}
+
+/// Known-answer tests of the confidentiality and integrity algorithms
+fn mode_kats() -> Result<(), SelfTestError> {
+    // EEA3 Test Set 1
+    let ck = hex!("17 3d 14 ba 50 03 73 1d 7a 60 04 94 70 f0 0a 29");
+    let ibs = hex!("6cf65340 735552ab 0c9752fa 6f9025fe 0bd675d9 005875b2 00000000");
+    let obs = hex!("a6c85fc6 6afb8533 aafc2518 dfe78494 0ee1e4b0 30238cc8 00000000");
+    if eea3_128_encrypt(0x6603_5492, 0xf, 0, &ck, 193, &ibs) != obs {
+        return Err(SelfTestError::Eea3);
+    }
+
+    // EIA3 Test Set 2
+    let ik = hex!("47 05 41 25 56 1e b2 dd a9 40 59 da 05 09 78 50");
+    if eia3_128_generate_mac(0x561e_b2dd, 0x14, 0, &ik, 90, &[0; 12]) != 0x6719_a088 {
+        return Err(SelfTestError::Eia3);
+    }
+
+    let (k, iv, m) = ([0; 32], [0; 23], [0; 50]);
+    if zuc256_generate_mac::<u32>(&k, &iv, 400, &m) != 0x9b97_2a74 {
+        return Err(SelfTestError::Zuc256Mac { bits: 32 });
+    }
+    if zuc256_generate_mac::<u64>(&k, &iv, 400, &m) != 0x673e_5499_0034_d38c {
+        return Err(SelfTestError::Zuc256Mac { bits: 64 });
+    }
+    if zuc256_generate_mac::<u128>(&k, &iv, 400, &m) != 0xd85e_54bb_cb96_0096_7084_c952_a165_4b26 {
+        return Err(SelfTestError::Zuc256Mac { bits: 128 });
+    }
+    Ok(())
+}
+
+thread_local! {
+    /// whether this thread is running the self-tests
+    static RUNNING: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// Marks the current thread as running the self-tests until dropped
+struct Running {
+    /// previous mark, restored on drop
+    previous: bool,
+}
+
+impl Running {
+    /// Marks the current thread
+    fn enter() -> Self {
+        Self {
+            previous: RUNNING.with(|r| r.replace(true)),
+        }
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        RUNNING.with(|r| r.set(self.previous));
+    }
+}
+
+/// Runs the self-tests once before the first key setup and panics if they failed
+///
+/// Key setups made by the self-tests themselves are not checked.
+///
+/// # Panics
+/// + Panics if the self-tests failed.
+#[cfg(feature = "auto-self-test")]
+pub(crate) fn ensure() {
+    /// outcome of the first run
+    static RESULT: std::sync::OnceLock<Result<(), SelfTestError>> = std::sync::OnceLock::new();
+
+    if RUNNING.with(std::cell::Cell::get) {
+        return;
+    }
+    fail_closed(*RESULT.get_or_init(self_test));
+}
+
+/// Panics if the self-tests failed
+#[cfg(feature = "auto-self-test")]
+fn fail_closed(result: Result<(), SelfTestError>) {
+    if let Err(e) = result {
+        panic!("ZUC self-test failed, refusing to operate: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pass() {
+        assert_eq!(self_test(), Ok(()));
+        assert!(!RUNNING.with(std::cell::Cell::get));
+    }
+
+    #[test]
+    fn corrupted_sbox() {
+        for i in 0..256 {
+            let mut s0 = S0;
+            s0[i] ^= 0x01;
+            assert_eq!(sbox_check(&s0, &S1), Err(SelfTestError::SBox), "S0[{i}]");
+
+            let mut s1 = S1;
+            s1[i] ^= 0x80;
+            assert_eq!(sbox_check(&S0, &s1), Err(SelfTestError::SBox), "S1[{i}]");
+        }
+
+        // a swapped pair is still a permutation
+        let mut s1 = S1;
+        s1.swap(0x10, 0x20);
+        assert_eq!(sbox_check(&S0, &s1), Err(SelfTestError::SBox));
+
+        let s0 = S0.map(|x| x ^ 0x01);
+        assert_eq!(
+            keystream_kats(&s0, &S1),
+            Err(SelfTestError::Zuc128Keystream)
+        );
+        let s1 = S1.map(|x| x.rotate_left(1));
+        assert_eq!(
+            keystream_kats(&S0, &s1),
+            Err(SelfTestError::Zuc128Keystream)
+        );
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            SelfTestError::Zuc256Mac { bits: 64 }.to_string(),
+            "known-answer test failed: ZUC-256 MAC (64 bit tag)"
+        );
+    }
+
+    #[cfg(feature = "auto-self-test")]
+    #[test]
+    #[should_panic(
+        expected = "ZUC self-test failed, refusing to operate: known-answer test failed: 128-EIA3"
+    )]
+    fn fails_closed() {
+        fail_closed(Err(SelfTestError::Eia3));
+    }
+
+    #[cfg(feature = "auto-self-test")]
+    #[test]
+    fn auto() {
+        ensure();
+        let _ = crate::Zuc128Core::new(&[0; 16], &[0; 16]);
+    }
+}
@@ -0,0 +1,108 @@
+//! SM3-based key derivation
+//! ([GB/T 32905-2016](https://openstd.samr.gov.cn/bzgk/gb/newGbInfo?hcno=45B1A67F20F3BF339211C391E9278F5E),
+//! [GB/T 32918.4-2016](https://openstd.samr.gov.cn/bzgk/gb/newGbInfo?hcno=370AF152CB5CA4A377EB4D1B21DECAE0))
+
+use ::sm3::{Digest, Sm3};
+
+/// SM3 hash of the concatenation of `parts`
+fn sm3(parts: &[&[u8]]) -> [u8; 32] {
+    let mut h = Sm3::new();
+    for part in parts {
+        h.update(part);
+    }
+    h.finalize().into()
+}
+
+/// SM3 key derivation function
+/// ([GB/T 32918.4-2016](https://openstd.samr.gov.cn/bzgk/gb/newGbInfo?hcno=370AF152CB5CA4A377EB4D1B21DECAE0))
+///
+/// Input:
+/// - `z`:          shared secret
+/// - `klen`:       bit length of the derived key
+///
+/// Output:
+/// - [`Vec<u8>`]:  derived key, the bits after `klen` are zero
+///
+/// # Panics
+/// + Panics if `klen` is greater than `(2^32 - 1) * 256`.
+#[must_use]
+pub fn sm3_kdf(z: &[u8], klen: usize) -> Vec<u8> {
+    let blocks =
+        u32::try_from(klen.div_ceil(256)).expect("`klen` is greater than `(2^32 - 1) * 256`");
+
+    let mut k = Vec::with_capacity(klen.div_ceil(8));
+    for ct in 1..=blocks {
+        k.extend_from_slice(&sm3(&[z, &ct.to_be_bytes()]));
+    }
+
+    k.truncate(klen.div_ceil(8));
+    let rem = klen % 8;
+    if rem != 0 {
+        if let Some(last) = k.last_mut() {
+            *last &= 0xFF << (8 - rem);
+        }
+    }
+    k
+}
+
+/// Derives a ZUC128 key and IV with [`sm3_kdf`]
+///
+/// The first 128 bits are the key and the following 128 bits are the IV.
+#[must_use]
+pub fn sm3_kdf_zuc128(z: &[u8]) -> ([u8; 16], [u8; 16]) {
+    let k = sm3_kdf(z, 256);
+    let mut key = [0; 16];
+    let mut iv = [0; 16];
+    key.copy_from_slice(&k[..16]);
+    iv.copy_from_slice(&k[16..]);
+    (key, iv)
+}
+
+/// Derives a ZUC256 key and IV with [`sm3_kdf`]
+///
+/// The first 256 bits are the key and the following 184 bits are the IV.
+#[must_use]
+pub fn sm3_kdf_zuc256(z: &[u8]) -> ([u8; 32], [u8; 23]) {
+    let k = sm3_kdf(z, 440);
+    let mut key = [0; 32];
+    let mut iv = [0; 23];
+    key.copy_from_slice(&k[..32]);
+    iv.copy_from_slice(&k[32..]);
+    (key, iv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use const_str::hex;
+
+    // example from GB/T 32918.4-2016 Appendix A.2
+    #[test]
+    fn kdf_example() {
+        let z = hex!([
+            "57E7B636 23FAE5F0 8CDA468E 872A20AF A03DED41 BF140377 0E040DC8 3AF31A67",
+            "991F2B01 EBF9EFD8 881F0A04 93000603",
+        ]);
+        let t = hex!("046B04A9 ADF53B38 9B9E2AAF B47D90F4 D08978");
+        assert_eq!(sm3_kdf(&z, 152), t);
+    }
+
+    // generated from OpenSSL
+    #[test]
+    fn kdf_lengths() {
+        assert_eq!(sm3_kdf(b"abc", 0), []);
+        assert_eq!(sm3_kdf(b"abc", 13), hex!("fe18"));
+
+        let (key, iv) = sm3_kdf_zuc128(b"abc");
+        assert_eq!(key, hex!("fe1ea80dac6f100c33537bd24619ec7c"));
+        assert_eq!(iv, hex!("72a1e8b1ffeaefb1eb52a37791fdaf61"));
+
+        let (key, iv) = sm3_kdf_zuc256(b"abc");
+        assert_eq!(
+            key,
+            hex!("fe1ea80dac6f100c33537bd24619ec7c72a1e8b1ffeaefb1eb52a37791fdaf61")
+        );
+        assert_eq!(iv, hex!("9db16c0ac7bebb47238c6cc925ff66af7936e278e12d26"));
+    }
+}
@@ -0,0 +1,795 @@
+//! Statistical randomness tests over generated keystream
+//!
+//! Native implementations of tests of NIST SP 800-22 Rev. 1a: frequency, block frequency,
+//! runs, longest run of ones, binary matrix rank, discrete Fourier transform, serial,
+//! approximate entropy and cumulative sums. Each test returns its p-values;
+//! a sequence passes a test at level `alpha` when all of them are at least `alpha`.
+//!
+//! [`run_suite`] runs all tests on the keystreams of many (key, IV) pairs and
+//! assesses the proportion of passing sequences and the uniformity of the p-values,
+//! as section 4.2 of the specification describes.
+//! The uniformity is only assessed with at least [`MIN_UNIFORMITY_SEQUENCES`] sequences.
+
+#![allow(clippy::cast_precision_loss)]
+
+use crate::{Zuc128Core, Zuc256Core};
+
+use std::f64::consts::{LN_2, PI, SQRT_2};
+use std::fmt;
+
+/// Converts bytes to bits, most significant bit first
+#[must_use]
+pub fn bytes_to_bits(bytes: &[u8]) -> Vec<bool> {
+    bytes
+        .iter()
+        .flat_map(|b| (0..8).rev().map(move |i| b >> i & 1 == 1))
+        .collect()
+}
+
+/// Frequency (monobit) test
+#[must_use]
+pub fn frequency(bits: &[bool]) -> f64 {
+    let n = bits.len() as f64;
+    let sum: i64 = bits.iter().map(|&b| if b { 1 } else { -1 }).sum();
+    erfc(sum.unsigned_abs() as f64 / n.sqrt() / SQRT_2)
+}
+
+/// Frequency test within blocks of `m` bits
+///
+/// # Panics
+/// + Panics if `m` is zero or greater than the sequence length.
+#[must_use]
+pub fn block_frequency(bits: &[bool], m: usize) -> f64 {
+    assert!(m > 0 && m <= bits.len(), "invalid block length");
+    let blocks = bits.len() / m;
+    let chi2: f64 = bits
+        .chunks_exact(m)
+        .map(|block| {
+            let pi = ones(block) as f64 / m as f64;
+            (pi - 0.5).powi(2)
+        })
+        .sum::<f64>()
+        * 4.0
+        * m as f64;
+    igamc(blocks as f64 / 2.0, chi2 / 2.0)
+}
+
+/// Runs test
+#[must_use]
+pub fn runs(bits: &[bool]) -> f64 {
+    let n = bits.len() as f64;
+    let pi = ones(bits) as f64 / n;
+    if (pi - 0.5).abs() >= 2.0 / n.sqrt() {
+        return 0.0;
+    }
+    let runs = 1 + bits.windows(2).filter(|w| w[0] != w[1]).count();
+    let num = (runs as f64 - 2.0 * n * pi * (1.0 - pi)).abs();
+    erfc(num / (2.0 * (2.0 * n).sqrt() * pi * (1.0 - pi)))
+}
+
+/// Test for the longest run of ones in a block
+///
+/// Returns `None` for sequences shorter than 128 bits.
+#[must_use]
+pub fn longest_run(bits: &[bool]) -> Option<f64> {
+    let (m, min, probabilities): (usize, usize, &[f64]) = match bits.len() {
+        0..=127 => return None,
+        128..=6271 => (8, 1, &[0.2148, 0.3672, 0.2305, 0.1875]),
+        6272..=749_999 => (128, 4, &[0.1174, 0.2430, 0.2493, 0.1752, 0.1027, 0.1124]),
+        _ => (
+            10_000,
+            10,
+            &[0.0882, 0.2092, 0.2483, 0.1933, 0.1208, 0.0675, 0.0727],
+        ),
+    };
+    let k = probabilities.len() - 1;
+    let mut counts = vec![0usize; k + 1];
+    for block in bits.chunks_exact(m) {
+        let mut longest = 0;
+        let mut run = 0;
+        for &b in block {
+            run = if b { run + 1 } else { 0 };
+            longest = longest.max(run);
+        }
+        counts[longest.clamp(min, min + k) - min] += 1;
+    }
+    let blocks = (bits.len() / m) as f64;
+    let chi2: f64 = counts
+        .iter()
+        .zip(probabilities)
+        .map(|(&v, &p)| (v as f64 - blocks * p).powi(2) / (blocks * p))
+        .sum();
+    Some(igamc(k as f64 / 2.0, chi2 / 2.0))
+}
+
+/// Binary matrix rank test over 32x32 matrices
+///
+/// Returns `None` for sequences of fewer than 38 matrices.
+#[must_use]
+pub fn rank(bits: &[bool]) -> Option<f64> {
+    let matrices = bits.len() / 1024;
+    if matrices < 38 {
+        return None;
+    }
+    let (mut full, mut full_minus_one) = (0usize, 0usize);
+    for matrix in bits.chunks_exact(1024) {
+        let mut rows: Vec<u32> = matrix
+            .chunks_exact(32)
+            .map(|row| row.iter().fold(0, |r, &b| (r << 1) | u32::from(b)))
+            .collect();
+        match gf2_rank(&mut rows) {
+            32 => full += 1,
+            31 => full_minus_one += 1,
+            _ => {}
+        }
+    }
+    let n = matrices as f64;
+    let rest = (matrices - full - full_minus_one) as f64;
+    let chi2 = (full as f64 - 0.2888 * n).powi(2) / (0.2888 * n)
+        + (full_minus_one as f64 - 0.5776 * n).powi(2) / (0.5776 * n)
+        + (rest - 0.1336 * n).powi(2) / (0.1336 * n);
+    Some((-chi2 / 2.0).exp())
+}
+
+/// Rank over GF(2) of a matrix of 32-bit rows
+fn gf2_rank(rows: &mut [u32]) -> usize {
+    let mut rank = 0;
+    for bit in (0..32).rev() {
+        let Some(pivot) = (rank..rows.len()).find(|&r| rows[r] >> bit & 1 == 1) else {
+            continue;
+        };
+        rows.swap(rank, pivot);
+        for r in 0..rows.len() {
+            if r != rank && rows[r] >> bit & 1 == 1 {
+                rows[r] ^= rows[rank];
+            }
+        }
+        rank += 1;
+    }
+    rank
+}
+
+/// Discrete Fourier transform (spectral) test
+///
+/// Sequences of any length use a fast Fourier transform,
+/// by Bluestein's algorithm for lengths that are not a power of two.
+#[must_use]
+pub fn dft(bits: &[bool]) -> f64 {
+    let n = bits.len();
+    let x: Vec<(f64, f64)> = bits
+        .iter()
+        .map(|&b| (if b { 1.0 } else { -1.0 }, 0.0))
+        .collect();
+    let spectrum = if n.is_power_of_two() {
+        fft(x)
+    } else {
+        bluestein(&x)
+    };
+    let threshold = ((1.0f64 / 0.05).ln() * n as f64).sqrt();
+    let below = spectrum[..n / 2]
+        .iter()
+        .filter(|(re, im)| re.hypot(*im) < threshold)
+        .count();
+    let expected = 0.95 * n as f64 / 2.0;
+    let d = (below as f64 - expected) / (n as f64 * 0.95 * 0.05 / 4.0).sqrt();
+    erfc(d.abs() / SQRT_2)
+}
+
+/// Iterative radix-2 fast Fourier transform
+fn fft(mut x: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    let n = x.len();
+    if n <= 1 {
+        return x;
+    }
+    let shift = usize::BITS - n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> shift;
+        if i < j {
+            x.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let (re, im) = x[start + k + len / 2];
+                let t = (re * cos - im * sin, re * sin + im * cos);
+                let u = x[start + k];
+                x[start + k] = (u.0 + t.0, u.1 + t.1);
+                x[start + k + len / 2] = (u.0 - t.0, u.1 - t.1);
+            }
+        }
+        len *= 2;
+    }
+    x
+}
+
+/// Discrete Fourier transform of any length as a convolution of power-of-two length
+#[allow(clippy::many_single_char_names)]
+fn bluestein(x: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let n = x.len();
+    let len = (2 * n - 1).next_power_of_two();
+    let mul = |a: (f64, f64), b: (f64, f64)| (a.0 * b.0 - a.1 * b.1, a.0 * b.1 + a.1 * b.0);
+    // exp(-i pi k^2 / n), k^2 reduced modulo 2n to keep the angle accurate
+    let chirp: Vec<(f64, f64)> = (0..n as u128)
+        .map(|k| {
+            let angle = -PI * ((k * k) % (2 * n as u128)) as f64 / n as f64;
+            let (sin, cos) = angle.sin_cos();
+            (cos, sin)
+        })
+        .collect();
+    let mut a = vec![(0.0, 0.0); len];
+    for (i, (&v, &c)) in x.iter().zip(&chirp).enumerate() {
+        a[i] = mul(v, c);
+    }
+    let mut b = vec![(0.0, 0.0); len];
+    b[0] = (chirp[0].0, -chirp[0].1);
+    for k in 1..n {
+        b[k] = (chirp[k].0, -chirp[k].1);
+        b[len - k] = b[k];
+    }
+    // inverse transform of the product as the conjugate transform of the conjugate
+    let product: Vec<(f64, f64)> = fft(a)
+        .into_iter()
+        .zip(fft(b))
+        .map(|(a, b)| {
+            let p = mul(a, b);
+            (p.0, -p.1)
+        })
+        .collect();
+    fft(product)
+        .into_iter()
+        .zip(chirp)
+        .map(|(v, c)| mul((v.0 / len as f64, -v.1 / len as f64), c))
+        .collect()
+}
+
+/// Counts of the overlapping `m`-bit patterns, the sequence being extended by its first `m - 1` bits
+fn pattern_counts(bits: &[bool], m: usize) -> Vec<usize> {
+    let mut counts = vec![0; 1 << m];
+    if m == 0 {
+        counts[0] = bits.len();
+        return counts;
+    }
+    let mask = (1 << m) - 1;
+    let mut pattern = 0;
+    for (i, &b) in bits.iter().chain(&bits[..m - 1]).enumerate() {
+        pattern = ((pattern << 1) | usize::from(b)) & mask;
+        if i >= m - 1 {
+            counts[pattern] += 1;
+        }
+    }
+    counts
+}
+
+/// Serial test with `m`-bit patterns
+///
+/// Returns the two p-values of the first and second differences.
+///
+/// # Panics
+/// + Panics if `m` is not in `2..=24` or greater than the sequence length.
+#[must_use]
+pub fn serial(bits: &[bool], m: usize) -> [f64; 2] {
+    assert!(
+        (2..=24).contains(&m) && m <= bits.len(),
+        "invalid pattern length"
+    );
+    let n = bits.len() as f64;
+    let psi2 = |m: usize| -> f64 {
+        let sum: f64 = pattern_counts(bits, m)
+            .iter()
+            .map(|&c| (c as f64).powi(2))
+            .sum();
+        sum * (1u64 << m) as f64 / n - n
+    };
+    let (p0, p1, p2) = (psi2(m), psi2(m - 1), psi2(m - 2));
+    let d1 = p0 - p1;
+    let d2 = p0 - 2.0 * p1 + p2;
+    [
+        igamc((1u64 << (m - 2)) as f64, d1 / 2.0),
+        igamc((1u64 << (m - 2)) as f64 / 2.0, d2 / 2.0),
+    ]
+}
+
+/// Approximate entropy test with `m`-bit patterns
+///
+/// # Panics
+/// + Panics if `m` is not in `1..=23` or not less than the sequence length.
+#[must_use]
+pub fn approximate_entropy(bits: &[bool], m: usize) -> f64 {
+    assert!(
+        (1..=23).contains(&m) && m < bits.len(),
+        "invalid pattern length"
+    );
+    let n = bits.len() as f64;
+    let phi = |m: usize| -> f64 {
+        pattern_counts(bits, m)
+            .iter()
+            .filter(|&&c| c > 0)
+            .map(|&c| {
+                let p = c as f64 / n;
+                p * p.ln()
+            })
+            .sum()
+    };
+    let apen = phi(m) - phi(m + 1);
+    let chi2 = 2.0 * n * (LN_2 - apen);
+    igamc((1u64 << (m - 1)) as f64, chi2 / 2.0)
+}
+
+/// Cumulative sums test
+///
+/// Returns the p-values of the forward and backward modes.
+#[must_use]
+pub fn cumulative_sums(bits: &[bool]) -> [f64; 2] {
+    let mode = |iter: &mut dyn Iterator<Item = &bool>| -> f64 {
+        let mut sum = 0i64;
+        let mut z = 0;
+        for &b in iter {
+            sum += if b { 1 } else { -1 };
+            z = z.max(sum.unsigned_abs());
+        }
+        cusum_p_value(bits.len() as f64, z as f64)
+    };
+    [mode(&mut bits.iter()), mode(&mut bits.iter().rev())]
+}
+
+/// P-value of the cumulative sums test for the maximum excursion `z`
+#[allow(clippy::cast_possible_truncation)]
+fn cusum_p_value(n: f64, z: f64) -> f64 {
+    let sqrt_n = n.sqrt();
+    let range = |lo: f64, hi: f64| (lo / 4.0).trunc() as i64..=(hi / 4.0).trunc() as i64;
+    let mut p = 1.0;
+    for k in range(-n / z + 1.0, n / z - 1.0) {
+        let k = k as f64;
+        p -= normal_cdf((4.0 * k + 1.0) * z / sqrt_n) - normal_cdf((4.0 * k - 1.0) * z / sqrt_n);
+    }
+    for k in range(-n / z - 3.0, n / z - 1.0) {
+        let k = k as f64;
+        p += normal_cdf((4.0 * k + 3.0) * z / sqrt_n) - normal_cdf((4.0 * k + 1.0) * z / sqrt_n);
+    }
+    p
+}
+
+/// Number of set bits
+fn ones(bits: &[bool]) -> usize {
+    bits.iter().filter(|&&b| b).count()
+}
+
+/// Standard normal cumulative distribution function
+fn normal_cdf(x: f64) -> f64 {
+    if x < 0.0 {
+        erfc(-x / SQRT_2) / 2.0
+    } else {
+        1.0 - erfc(x / SQRT_2) / 2.0
+    }
+}
+
+/// Complementary error function for `x >= 0`
+fn erfc(x: f64) -> f64 {
+    igamc(0.5, x * x)
+}
+
+/// Natural logarithm of the gamma function for `x > 0` (Lanczos approximation)
+fn ln_gamma(x: f64) -> f64 {
+    /// coefficients for g = 7, n = 9
+    const C: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // reflection formula
+        return (PI / (PI * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let sum = C[1..]
+        .iter()
+        .enumerate()
+        .fold(C[0], |s, (i, &c)| s + c / (x + i as f64 + 1.0));
+    0.5 * (2.0 * PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+/// Regularized upper incomplete gamma function `Q(a, x)` for `a > 0`, `x >= 0`
+#[allow(clippy::many_single_char_names)]
+fn igamc(a: f64, x: f64) -> f64 {
+    /// relative precision
+    const EPS: f64 = 1e-15;
+    /// smallest magnitude of the continued fraction terms
+    const TINY: f64 = 1e-300;
+
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let prefactor = (-x + a * x.ln() - ln_gamma(a)).exp();
+    if x < a + 1.0 {
+        // series of the lower function P(a, x)
+        let (mut ap, mut del, mut sum) = (a, 1.0 / a, 1.0 / a);
+        for _ in 0..10_000 {
+            ap += 1.0;
+            del *= x / ap;
+            sum += del;
+            if del.abs() < sum.abs() * EPS {
+                break;
+            }
+        }
+        (1.0 - sum * prefactor).max(0.0)
+    } else {
+        // continued fraction, modified Lentz's method
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / TINY;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..10_000 {
+            let i = f64::from(i);
+            let an = -i * (i - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < TINY {
+                d = TINY;
+            }
+            c = b + an / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            d = 1.0 / d;
+            let del = d * c;
+            h *= del;
+            if (del - 1.0).abs() < EPS {
+                break;
+            }
+        }
+        prefactor * h
+    }
+}
+
+/// Test names and p-values of all tests with the parameters recommended by the specification
+///
+/// Block frequency uses 128-bit blocks, serial 16-bit patterns
+/// and approximate entropy 10-bit patterns, each reduced for short sequences.
+/// Tests that need a longer sequence are skipped.
+#[must_use]
+pub fn run_all(bits: &[bool]) -> Vec<(&'static str, Vec<f64>)> {
+    let n = bits.len();
+    let log2 = n.max(1).ilog2() as usize;
+    let mut results = vec![
+        ("frequency", vec![frequency(bits)]),
+        ("block-frequency", vec![block_frequency(bits, 128.min(n))]),
+        ("runs", vec![runs(bits)]),
+    ];
+    if let Some(p) = longest_run(bits) {
+        results.push(("longest-run", vec![p]));
+    }
+    if let Some(p) = rank(bits) {
+        results.push(("rank", vec![p]));
+    }
+    results.push(("dft", vec![dft(bits)]));
+    // m < log2(n) - 2
+    let serial_m = 16.min(log2.saturating_sub(3));
+    if serial_m >= 2 {
+        results.push(("serial", serial(bits, serial_m).to_vec()));
+    }
+    // m < log2(n) - 5
+    let apen_m = 10.min(log2.saturating_sub(6));
+    if apen_m >= 1 {
+        results.push((
+            "approximate-entropy",
+            vec![approximate_entropy(bits, apen_m)],
+        ));
+    }
+    results.push(("cumulative-sums", cumulative_sums(bits).to_vec()));
+    results
+}
+
+/// Minimum number of sequences for the uniformity assessment (SP 800-22 section 4.2.2)
+pub const MIN_UNIFORMITY_SEQUENCES: usize = 55;
+
+/// Keystream generator of the suite
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// ZUC-128
+    Zuc128,
+    /// ZUC-256
+    Zuc256,
+}
+
+/// Assessment of one test over all sequences
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestSummary {
+    /// test name
+    pub name: &'static str,
+    /// p-values of every sequence, each sequence contributing the same number
+    pub p_values: Vec<f64>,
+    /// number of sequences
+    pub sequences: usize,
+    /// number of sequences passing the test
+    pub passed: usize,
+    /// p-value of the uniformity of the p-values,
+    /// `None` with fewer than [`MIN_UNIFORMITY_SEQUENCES`] sequences
+    pub uniformity: Option<f64>,
+}
+
+impl TestSummary {
+    /// Returns whether the proportion of passing sequences reaches the lower bound of
+    /// its 99.7% confidence interval and the p-values are uniform (`uniformity >= 0.0001`)
+    ///
+    /// Only the proportion is checked when the uniformity is not assessed.
+    #[must_use]
+    pub fn is_pass(&self, alpha: f64) -> bool {
+        let p = 1.0 - alpha;
+        let m = self.sequences as f64;
+        let lower = p - 3.0 * (p * (1.0 - p) / m).sqrt();
+        self.passed as f64 / m >= lower && self.uniformity.unwrap_or(1.0) >= 0.0001
+    }
+
+    /// Counts the p-values in each of the 10 intervals `[0, 0.1)`, ..., `[0.9, 1]`
+    #[must_use]
+    pub fn histogram(&self) -> [usize; 10] {
+        histogram(&self.p_values)
+    }
+}
+
+/// Results of [`run_suite`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuiteReport {
+    /// significance level
+    pub alpha: f64,
+    /// assessment of each test
+    pub tests: Vec<TestSummary>,
+}
+
+impl SuiteReport {
+    /// Returns whether every test passes
+    #[must_use]
+    pub fn is_pass(&self) -> bool {
+        self.tests.iter().all(|t| t.is_pass(self.alpha))
+    }
+}
+
+impl fmt::Display for SuiteReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<20}", "test")?;
+        for i in 1..=10 {
+            write!(f, " {:>4}", format!("C{i}"))?;
+        }
+        writeln!(
+            f,
+            " {:>10} {:>12} {:>6}",
+            "proportion", "uniformity", "result"
+        )?;
+        for t in &self.tests {
+            write!(f, "{:<20}", t.name)?;
+            for count in t.histogram() {
+                write!(f, " {count:>4}")?;
+            }
+            writeln!(
+                f,
+                " {:>10} {:>12} {:>6}",
+                format!("{}/{}", t.passed, t.sequences),
+                t.uniformity
+                    .map_or_else(|| "-".into(), |u| format!("{u:.6}")),
+                if t.is_pass(self.alpha) {
+                    "PASS"
+                } else {
+                    "FAIL"
+                }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs all tests on the keystreams of `sequences` (key, IV) pairs of `bits` bits each
+///
+/// The keys and IVs are drawn from a ZUC-256 keystream seeded by `seed`,
+/// so the same arguments test the same keystreams.
+/// The uniformity of the p-values is not assessed with fewer than
+/// [`MIN_UNIFORMITY_SEQUENCES`] sequences.
+///
+/// # Panics
+/// + Panics if `sequences` or `bits` is zero.
+#[must_use]
+pub fn run_suite(
+    algorithm: Algorithm,
+    sequences: usize,
+    bits: usize,
+    seed: u64,
+    alpha: f64,
+) -> SuiteReport {
+    assert!(sequences > 0 && bits > 0, "empty suite");
+    let mut seed_key = [0; 32];
+    seed_key[..8].copy_from_slice(&seed.to_be_bytes());
+    let mut seeds = Zuc256Core::new(&seed_key, &[0; 23]);
+    let mut draw = |len: usize| -> Vec<u8> {
+        let words = len.div_ceil(4);
+        let bytes: Vec<u8> = (0..words)
+            .flat_map(|_| seeds.generate().to_be_bytes())
+            .collect();
+        bytes[..len].to_vec()
+    };
+
+    let words = bits.div_ceil(32);
+    let mut tests: Vec<TestSummary> = Vec::new();
+    for _ in 0..sequences {
+        let keystream: Vec<u8> = match algorithm {
+            Algorithm::Zuc128 => {
+                let (mut key, mut iv) = ([0; 16], [0; 16]);
+                key.copy_from_slice(&draw(16));
+                iv.copy_from_slice(&draw(16));
+                let mut zuc = Zuc128Core::new(&key, &iv);
+                (0..words)
+                    .flat_map(|_| zuc.generate().to_be_bytes())
+                    .collect()
+            }
+            Algorithm::Zuc256 => {
+                let (mut key, mut iv) = ([0; 32], [0; 23]);
+                key.copy_from_slice(&draw(32));
+                iv.copy_from_slice(&draw(23));
+                let mut zuc = Zuc256Core::new(&key, &iv);
+                (0..words)
+                    .flat_map(|_| zuc.generate().to_be_bytes())
+                    .collect()
+            }
+        };
+        let mut sequence = bytes_to_bits(&keystream);
+        sequence.truncate(bits);
+
+        for (i, (name, p_values)) in run_all(&sequence).into_iter().enumerate() {
+            if tests.len() == i {
+                tests.push(TestSummary {
+                    name,
+                    p_values: Vec::new(),
+                    sequences,
+                    passed: 0,
+                    uniformity: None,
+                });
+            }
+            let test = &mut tests[i];
+            if p_values.iter().all(|&p| p >= alpha) {
+                test.passed += 1;
+            }
+            test.p_values.extend(p_values);
+        }
+    }
+
+    if sequences >= MIN_UNIFORMITY_SEQUENCES {
+        for test in &mut tests {
+            test.uniformity = Some(uniformity(&test.p_values));
+        }
+    }
+    SuiteReport { alpha, tests }
+}
+
+/// Counts the p-values in 10 equal intervals
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn histogram(p_values: &[f64]) -> [usize; 10] {
+    let mut bins = [0; 10];
+    for &p in p_values {
+        bins[((p * 10.0) as usize).min(9)] += 1;
+    }
+    bins
+}
+
+/// P-value of the chi-square test of the p-values over 10 equal intervals
+fn uniformity(p_values: &[f64]) -> f64 {
+    let expected = p_values.len() as f64 / 10.0;
+    let chi2: f64 = histogram(p_values)
+        .iter()
+        .map(|&b| (b as f64 - expected).powi(2) / expected)
+        .sum();
+    igamc(4.5, chi2 / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a string of `0` and `1`
+    fn bits(s: &str) -> Vec<bool> {
+        s.bytes()
+            .filter(|b| !b.is_ascii_whitespace())
+            .map(|b| b == b'1')
+            .collect()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-6, "{actual} != {expected}");
+    }
+
+    /// The first 100 bits of the binary expansion of pi, examples of SP 800-22
+    static EPSILON: &str = "11001001000011111101101010100010001000010110100011\
+                            00001000110100110001001100011001100010100010111000";
+
+    #[test]
+    fn specification_examples() {
+        let eps = bits(EPSILON);
+        assert_close(frequency(&eps), 0.109_599);
+        assert_close(block_frequency(&eps, 10), 0.706_438);
+        assert_close(runs(&eps), 0.500_798);
+        let [forward, backward] = cumulative_sums(&eps);
+        assert_close(forward, 0.219_194);
+        assert_close(backward, 0.114_866);
+        // the examples of the specification count fewer peaks below the threshold
+        // than the magnitudes of their own transforms give: 5 of 5 and 48 of 50 here
+        assert_close(
+            dft(&bits("1001010011")),
+            erfc(0.25 / 0.11875f64.sqrt() / SQRT_2),
+        );
+        assert_close(dft(&eps), erfc(0.5 / 1.1875f64.sqrt() / SQRT_2));
+        assert_close(approximate_entropy(&bits("0100110101"), 3), 0.261_961);
+        let [p1, p2] = serial(&bits("0011011101"), 3);
+        assert_close(p1, 0.808_792);
+        assert_close(p2, 0.670_320);
+
+        let longest = bits(
+            "11001100000101010110110001001100111000000000001001\
+             00110101010001000100111101011010000000110101111100\
+             1100111001101101100010110010",
+        );
+        // the specification reports 0.180609 from a rounded chi-square of 4.882457
+        assert_close(longest_run(&longest).unwrap(), 0.180_598);
+    }
+
+    /// Quadratic-time discrete Fourier transform
+    fn naive_dft(x: &[(f64, f64)]) -> Vec<(f64, f64)> {
+        let n = x.len();
+        (0..n)
+            .map(|j| {
+                x.iter().enumerate().fold((0.0, 0.0), |(re, im), (k, v)| {
+                    let angle = -2.0 * PI * ((j * k) % n) as f64 / n as f64;
+                    let (sin, cos) = angle.sin_cos();
+                    (re + v.0 * cos - v.1 * sin, im + v.0 * sin + v.1 * cos)
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn transforms() {
+        for n in [1, 10, 64, 100, 1000] {
+            let x: Vec<(f64, f64)> = (0..n)
+                .map(|i| (f64::from(i % 7) - 3.0, f64::from(i % 3)))
+                .collect();
+            let fast = if x.len().is_power_of_two() {
+                fft(x.clone())
+            } else {
+                bluestein(&x)
+            };
+            for (a, b) in fast.iter().zip(naive_dft(&x)) {
+                assert!((a.0 - b.0).abs() < 1e-6 && (a.1 - b.1).abs() < 1e-6);
+            }
+        }
+        let mut rows = [1, 2, 3, 0];
+        assert_eq!(gf2_rank(&mut rows), 2);
+    }
+
+    #[test]
+    fn suite() {
+        let report = run_suite(Algorithm::Zuc128, 20, 40_000, 1, 0.01);
+        assert_eq!(report.tests.len(), 9);
+        assert!(report.is_pass(), "{report}");
+        assert_eq!(report, run_suite(Algorithm::Zuc128, 20, 40_000, 1, 0.01));
+        for t in &report.tests {
+            assert_eq!(t.uniformity, None);
+            assert_eq!(t.histogram().iter().sum::<usize>(), t.p_values.len());
+        }
+
+        let report = run_suite(Algorithm::Zuc256, 60, 4_000, 1, 0.01);
+        assert!(report.tests.iter().all(|t| t.uniformity.is_some()));
+        assert!(report.is_pass(), "{report}");
+
+        let constant = vec![true; 40_000];
+        assert!(run_all(&constant)
+            .iter()
+            .all(|(_, p)| p.iter().any(|&p| p < 0.01)));
+    }
+}
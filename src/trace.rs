@@ -0,0 +1,223 @@
+//! Per-step internal state tracing
+//!
+//! [`Trace`] replays the initialization and working stages of a keystream generator
+//! round by round and records the values listed in the examples of
+//! GB/T 33133.1 Annex A: `X0..X3`, `R1`, `R2`, `W` (or the output `Z`) and `s15`.
+//! [`Trace::table`] renders them in the layout of these tables.
+
+use crate::zuc::{Zuc, S0, S1};
+use crate::{zuc128, zuc256};
+
+use std::fmt::{self, Write as _};
+
+/// Number of rounds of the initialization stage
+const INIT_ROUNDS: usize = 32;
+
+/// Stage of a traced round
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Initialization stage, 32 rounds
+    Initialization,
+    /// Working stage, the first round of which discards its output
+    Working,
+}
+
+/// Internal values of one round
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Round {
+    /// stage of the round
+    pub stage: Stage,
+    /// round number within the stage, starting from 0
+    pub t: u64,
+    /// `BitReconstruction` output `X0..X3`
+    pub x: [u32; 4],
+    /// `R1` after the round
+    pub r1: u32,
+    /// `R2` after the round
+    pub r2: u32,
+    /// F output `W` in the initialization stage, keystream word `Z = W ^ X3` in the working stage
+    pub w: u32,
+    /// LFSR cell `s15` after the round
+    pub s15: u32,
+}
+
+impl fmt::Display for Round {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [x0, x1, x2, x3] = self.x;
+        write!(
+            f,
+            "{:>2} {x0:08x} {x1:08x} {x2:08x} {x3:08x} {:08x} {:08x} {:08x} {:08x}",
+            self.t, self.r1, self.r2, self.w, self.s15
+        )
+    }
+}
+
+/// Round-by-round trace of a keystream generator
+///
+/// The iterator yields the 32 rounds of the initialization stage,
+/// then the rounds of the working stage without end.
+/// Working round `t` (for `t >= 1`) outputs keystream word `t - 1`.
+#[derive(Debug, Clone)]
+pub struct Trace {
+    /// traced state
+    zuc: Zuc,
+    /// LFSR cells after loading the key and IV
+    initial: [u32; 16],
+    /// number of rounds run
+    rounds: u64,
+}
+
+impl Trace {
+    /// Traces a ZUC128 keystream generator
+    #[must_use]
+    pub fn zuc128(key: &[u8; 16], iv: &[u8; 16]) -> Self {
+        Self::from_loaded(zuc128::load(key, iv, &zuc128::D))
+    }
+
+    /// Traces a ZUC256 keystream generator
+    #[must_use]
+    pub fn zuc256(key: &[u8; 32], iv: &[u8; 23]) -> Self {
+        Self::from_loaded(zuc256::load(key, iv, &zuc256::D))
+    }
+
+    /// Creates a trace from a loaded LFSR
+    fn from_loaded(zuc: Zuc) -> Self {
+        Self {
+            initial: zuc.s,
+            zuc,
+            rounds: 0,
+        }
+    }
+
+    /// Returns the LFSR cells `s0..s15` after loading the key and IV
+    #[must_use]
+    pub fn initial_lfsr(&self) -> &[u32; 16] {
+        &self.initial
+    }
+
+    /// Renders the initial LFSR state, the initialization stage and
+    /// the first `working_rounds` rounds of the working stage
+    #[must_use]
+    pub fn table(self, working_rounds: usize) -> String {
+        let mut out = String::new();
+        let _ = self.write_table(&mut out, working_rounds);
+        out
+    }
+
+    /// Writes the table rendered by [`Trace::table`]
+    fn write_table(self, out: &mut String, working_rounds: usize) -> fmt::Result {
+        let header = |last: &str| {
+            format!(
+                "{:>2} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {last:>8} {:>8}",
+                "t", "X0", "X1", "X2", "X3", "R1", "R2", "S15"
+            )
+        };
+
+        writeln!(out, "LFSR initial state:")?;
+        for cells in self.initial.chunks(8) {
+            let cells: Vec<_> = cells.iter().map(|s| format!("{s:08x}")).collect();
+            writeln!(out, "{}", cells.join(" "))?;
+        }
+
+        let mut stage = None;
+        for round in self.take(INIT_ROUNDS + working_rounds) {
+            if stage != Some(round.stage) {
+                stage = Some(round.stage);
+                match round.stage {
+                    Stage::Initialization => {
+                        writeln!(out, "\nInitialization stage:\n{}", header("W"))?;
+                    }
+                    Stage::Working => writeln!(out, "\nWorking stage:\n{}", header("Z"))?,
+                }
+            }
+            writeln!(out, "{round}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Trace {
+    type Item = Round;
+
+    fn next(&mut self) -> Option<Round> {
+        let zuc = &mut self.zuc;
+        let init_rounds = INIT_ROUNDS as u64;
+        let (stage, t, x, w) = if self.rounds < init_rounds {
+            let (x, w) = zuc.init_round_with(&S0, &S1);
+            (Stage::Initialization, self.rounds, x, w)
+        } else {
+            let x = zuc.bit_reconstruction();
+            let z = zuc.f_with(x, &S0, &S1) ^ x[3];
+            zuc.lfsr_with_work_mode();
+            (Stage::Working, self.rounds - init_rounds, x, z)
+        };
+        self.rounds += 1;
+        Some(Round {
+            stage,
+            t,
+            x,
+            r1: zuc.r1,
+            r2: zuc.r2,
+            w,
+            s15: zuc.s[15],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zuc128_example() {
+        // GB/T 33133.1 Annex A, example 1: key and IV all zeros
+        let mut trace = Trace::zuc128(&[0; 16], &[0; 16]);
+        assert_eq!(trace.initial_lfsr()[..2], [0x0044_d700, 0x0026_bc00]);
+
+        let init: Vec<_> = trace.by_ref().take(32).collect();
+        assert!(init.iter().all(|r| r.stage == Stage::Initialization));
+        assert_eq!(init[31].t, 31);
+
+        let working: Vec<_> = trace.take(3).collect();
+        assert_eq!(working[0].stage, Stage::Working);
+        assert_eq!(
+            working[0].x,
+            [0x7c37_ba6b, 0xb136_7f6c, 0x1e42_6568, 0xdd0b_f9c2]
+        );
+        assert_eq!(
+            (working[0].r1, working[0].r2, working[0].w, working[0].s15),
+            (0x3512_bf50, 0xa092_0453, 0x286d_afe5, 0x7f08_e141)
+        );
+        assert_eq!(
+            (working[1].r1, working[1].r2, working[1].w, working[1].s15),
+            (0xc7ee_7f13, 0x0c0f_a817, 0x27be_de74, 0x3d38_3d04)
+        );
+        assert_eq!(working[2].w, 0x0180_82da);
+    }
+
+    #[test]
+    fn keystream() {
+        let (k, iv) = ([0x3d; 32], [0x84; 23]);
+        let mut zuc = crate::Zuc256Core::new(&k, &iv);
+        for round in Trace::zuc256(&k, &iv).skip(33).take(100) {
+            assert_eq!(round.w, zuc.generate());
+        }
+    }
+
+    #[test]
+    fn table() {
+        let table = Trace::zuc128(&[0; 16], &[0; 16]).table(2);
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(lines.len(), 3 + 3 + 32 + 3 + 2);
+        assert_eq!(lines[0], "LFSR initial state:");
+        assert_eq!(
+            lines[5],
+            " t       X0       X1       X2       X3       R1       R2        W      S15"
+        );
+        assert_eq!(lines[39], "Working stage:");
+        assert_eq!(
+            lines[42],
+            " 1 fe118d6a d4522c3a e955463d 4c2be8f9 c7ee7f13 0c0fa817 27bede74 3d383d04"
+        );
+    }
+}
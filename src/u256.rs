@@ -18,6 +18,14 @@ impl U256 {
     }
 }
 
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for U256 {
+    fn zeroize(&mut self) {
+        self.high.zeroize();
+        self.low.zeroize();
+    }
+}
+
 impl ShlAssign<usize> for U256 {
     fn shl_assign(&mut self, rhs: usize) {
         if rhs >= 256 {
@@ -1,7 +1,10 @@
 //! ZUC shared implementation
 
+use std::fmt;
+use std::num::NonZeroU64;
+
 /// S0 box
-static S0: [u8; 256] = const_str::hex!([
+pub static S0: [u8; 256] = const_str::hex!([
     "3E 72 5B 47 CA E0 00 33 04 D1 54 98 09 B9 6D CB",
     "7B 1B F9 32 AF 9D 6A A5 B8 2D FC 1D 08 53 03 90",
     "4D 4E 84 99 E4 CE D9 91 DD B6 85 48 8B 29 6E AC",
@@ -21,7 +24,7 @@ static S0: [u8; 256] = const_str::hex!([
 ]);
 
 /// S1 box
-static S1: [u8; 256] = const_str::hex!([
+pub static S1: [u8; 256] = const_str::hex!([
     "55 C2 63 71 3B C8 47 86 9F 3C DA 5B 29 AA FD 77",
     "8C C5 94 0C A6 1A 13 00 E3 A8 16 72 40 F9 F8 42",
     "44 26 68 96 81 D9 45 3E 10 76 C6 A7 8B 39 43 E1",
@@ -40,6 +43,30 @@ static S1: [u8; 256] = const_str::hex!([
     "64 BE 85 9B 2F 59 8A D7 B0 25 AC AF 12 03 E2 F2",
 ]);
 
+/// Inverts an S box
+#[allow(clippy::cast_possible_truncation)]
+const fn invert(s: &[u8; 256]) -> [u8; 256] {
+    let mut inv = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        inv[s[i] as usize] = i as u8;
+        i += 1;
+    }
+    inv
+}
+
+/// Inverse S0 box
+static S0_INV: [u8; 256] = invert(&S0);
+
+/// Inverse S1 box
+static S1_INV: [u8; 256] = invert(&S1);
+
+/// 2^31 - 1
+const P: u64 = (1 << 31) - 1;
+
+/// Inverse of (1 + 2^8) modulo (2^31 - 1)
+const INV_257: u64 = 0x00ff_00ff;
+
 /// (a + b) mod (2^32)
 #[inline(always)]
 fn add(a: u32, b: u32) -> u32 {
@@ -66,19 +93,60 @@ fn l2(x: u32) -> u32 {
 
 /// S box transform
 #[inline(always)]
-fn sbox(x: u32) -> u32 {
+fn sbox(x: u32, s0: &[u8; 256], s1: &[u8; 256]) -> u32 {
     let x = x.to_be_bytes();
     let y = [
-        S0[x[0] as usize],
-        S1[x[1] as usize],
-        S0[x[2] as usize],
-        S1[x[3] as usize],
+        s0[x[0] as usize],
+        s1[x[1] as usize],
+        s0[x[2] as usize],
+        s1[x[3] as usize],
+    ];
+    u32::from_be_bytes(y)
+}
+
+/// Left rotations XOR-ed by L1
+#[cfg(any(feature = "research", feature = "masked"))]
+pub(crate) const L1_ROTATIONS: &[u32] = &[0, 2, 10, 18, 24];
+
+/// Left rotations XOR-ed by L2
+#[cfg(any(feature = "research", feature = "masked"))]
+pub(crate) const L2_ROTATIONS: &[u32] = &[0, 8, 14, 22, 30];
+
+/// Left rotations XOR-ed by the inverse of L1
+pub(crate) const L1_INV_ROTATIONS: &[u32] = &[0, 2, 4, 8, 12, 14, 16, 18, 22, 24, 30];
+
+/// Left rotations XOR-ed by the inverse of L2
+pub(crate) const L2_INV_ROTATIONS: &[u32] = &[0, 2, 8, 10, 14, 16, 18, 20, 24, 28, 30];
+
+/// XOR of the left rotations of `x` by `rotations`
+pub(crate) fn rotate_xor(x: u32, rotations: &[u32]) -> u32 {
+    rotations.iter().fold(0, |y, &n| y ^ rol(x, n))
+}
+
+/// Inverse L1 linear transform
+fn l1_inv(x: u32) -> u32 {
+    rotate_xor(x, L1_INV_ROTATIONS)
+}
+
+/// Inverse L2 linear transform
+fn l2_inv(x: u32) -> u32 {
+    rotate_xor(x, L2_INV_ROTATIONS)
+}
+
+/// Inverse S box transform
+fn sbox_inv(x: u32) -> u32 {
+    let x = x.to_be_bytes();
+    let y = [
+        S0_INV[x[0] as usize],
+        S1_INV[x[1] as usize],
+        S0_INV[x[2] as usize],
+        S1_INV[x[3] as usize],
     ];
     u32::from_be_bytes(y)
 }
 
 /// ZUC keystream generator
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub(crate) struct Zuc {
     /// LFSR registers (31-bit words x16)
     pub s: [u32; 16],
@@ -90,6 +158,29 @@ pub(crate) struct Zuc {
     pub r2: u32,
 }
 
+impl fmt::Debug for Zuc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Zuc { ... }")
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for Zuc {
+    fn zeroize(&mut self) {
+        self.s.zeroize();
+        self.r1.zeroize();
+        self.r2.zeroize();
+    }
+}
+
+/// Wipes the state
+#[cfg(feature = "zeroize")]
+impl Drop for Zuc {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
 impl Zuc {
     /// Zero-initialized
     pub fn zeroed() -> Self {
@@ -100,22 +191,36 @@ impl Zuc {
         }
     }
 
-    /// Creates a ZUC128 keystream generator
+    /// Runs the initialization stage on the loaded key and IV
     pub fn init(&mut self) {
-        for _ in 0..32 {
-            let x = self.bit_reconstruction();
-            let w = self.f(x);
-            self.lfsr_with_initialization_mode(w >> 1);
+        self.init_with(32, &S0, &S1);
+    }
+
+    /// Runs `rounds` initialization rounds and the discarded working round with the S boxes `s0`, `s1`
+    pub fn init_with(&mut self, rounds: u32, s0: &[u8; 256], s1: &[u8; 256]) {
+        for _ in 0..rounds {
+            self.init_round_with(s0, s1);
         }
 
         {
             let x = self.bit_reconstruction();
-            self.f(x);
+            self.f_with(x, s0, s1);
         }
     }
 
+    /// Runs one round of the initialization stage with the S boxes `s0`, `s1`
+    ///
+    /// Output: (`X0..X3`, `W`)
+    #[inline(always)]
+    pub fn init_round_with(&mut self, s0: &[u8; 256], s1: &[u8; 256]) -> ([u32; 4], u32) {
+        let x = self.bit_reconstruction();
+        let w = self.f_with(x, s0, s1);
+        self.lfsr_with_initialization_mode(w >> 1);
+        (x, w)
+    }
+
     /// `BitReconstruction` function
-    fn bit_reconstruction(&mut self) -> [u32; 4] {
+    pub fn bit_reconstruction(&self) -> [u32; 4] {
         let Self { s, .. } = self;
         let x0 = ((s[15] & 0x7FFF_8000) << 1) | (s[14] & 0xFFFF);
         let x1 = ((s[11] & 0xFFFF) << 16) | (s[9] >> 15);
@@ -124,15 +229,16 @@ impl Zuc {
         [x0, x1, x2, x3]
     }
 
-    /// F non-linear function
-    fn f(&mut self, x: [u32; 4]) -> u32 {
+    /// F non-linear function with the S boxes `s0`, `s1`
+    #[inline(always)]
+    pub fn f_with(&mut self, x: [u32; 4], s0: &[u8; 256], s1: &[u8; 256]) -> u32 {
         let Self { r1, r2, .. } = self;
 
         let w = add(x[0] ^ (*r1), *r2);
         let w1 = add(*r1, x[1]);
         let w2 = (*r2) ^ x[2];
-        *r1 = sbox(l1((w1 << 16) | (w2 >> 16)));
-        *r2 = sbox(l2((w2 << 16) | (w1 >> 16)));
+        *r1 = sbox(l1((w1 << 16) | (w2 >> 16)), s0, s1);
+        *r2 = sbox(l2((w2 << 16) | (w1 >> 16)), s0, s1);
 
         w
     }
@@ -201,8 +307,304 @@ impl Zuc {
 
     /// Generates the next 32-bit word in ZUC128 keystream
     pub fn generate(&mut self) -> u32 {
+        self.generate_with(&S0, &S1)
+    }
+
+    /// Generates the next 32-bit keystream word with the S boxes `s0`, `s1`
+    #[inline(always)]
+    pub fn generate_with(&mut self, s0: &[u8; 256], s1: &[u8; 256]) -> u32 {
         self.lfsr_with_work_mode();
         let x = self.bit_reconstruction();
-        self.f(x) ^ x[3]
+        self.f_with(x, s0, s1) ^ x[3]
+    }
+
+    /// Reverts one [`Zuc::generate`] step
+    pub fn rewind(&mut self) {
+        // R1 = S(L1(W1L || W2H)), R2 = S(L2(W2L || W1H))
+        let x = self.bit_reconstruction();
+        let a = l1_inv(sbox_inv(self.r1));
+        let b = l2_inv(sbox_inv(self.r2));
+        let w1 = (b << 16) | (a >> 16);
+        let w2 = (a << 16) | (b >> 16);
+        self.r1 = w1.wrapping_sub(x[1]);
+        self.r2 = w2 ^ x[2];
+        self.lfsr_rewind();
+    }
+
+    /// Reverts one `LFSRWithWorkMode` step
+    #[allow(clippy::cast_possible_truncation)]
+    fn lfsr_rewind(&mut self) {
+        let Self { s, .. } = self;
+
+        // s[15] = (2^15 * s[14] + 2^17 * s[12] + 2^21 * s[9] + 2^20 * s[3] + (1+2^8) * s0) mod (2^31 - 1)
+        // with the cells before the shift, and 0 represented as 2^31 - 1
+        let mut v = u64::from(s[3]) << 20;
+        v += u64::from(s[9]) << 21;
+        v += u64::from(s[12]) << 17;
+        v += u64::from(s[14]) << 15;
+        let t = (u64::from(s[15]) % P + P - v % P) % P;
+        let s0 = match t * INV_257 % P {
+            0 => P,
+            s0 => s0,
+        };
+
+        for i in (1..16).rev() {
+            s[i] = s[i - 1];
+        }
+        s[0] = s0 as u32;
+    }
+}
+
+/// Length of a keystream generator state snapshot in bytes
+pub const STATE_LEN: usize = 72;
+
+/// Version of the state snapshot encoding
+const STATE_VERSION: u8 = 1;
+
+/// Algorithm of a state snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub(crate) enum StateAlgorithm {
+    /// ZUC-128
+    Zuc128 = 1,
+    /// ZUC-256
+    Zuc256 = 2,
+}
+
+/// State snapshot errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateError {
+    /// The snapshot encoding version is not supported
+    UnsupportedVersion(u8),
+    /// The snapshot belongs to another algorithm
+    AlgorithmMismatch,
+    /// An LFSR cell is zero or out of the 31-bit range
+    InvalidCell {
+        /// index of the cell
+        index: usize,
+    },
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::UnsupportedVersion(v) => write!(f, "unsupported state version {v}"),
+            StateError::AlgorithmMismatch => write!(f, "state of another algorithm"),
+            StateError::InvalidCell { index } => write!(f, "invalid LFSR cell s{index}"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+impl Zuc {
+    /// Encodes the state
+    ///
+    /// The snapshot holds the LFSR cells `s0..s15` and then `R1`, `R2`, as big-endian
+    /// 32-bit words. The unused top bits of `s0..s7` carry the format byte,
+    /// `s0` holding its most significant bit: the encoding version in the high nibble
+    /// and the algorithm in the low nibble.
+    pub fn to_state_bytes(&self, alg: StateAlgorithm) -> [u8; STATE_LEN] {
+        let format = (STATE_VERSION << 4) | alg as u8;
+        let mut out = [0; STATE_LEN];
+        for (i, chunk) in out[..64].chunks_exact_mut(4).enumerate() {
+            let flag = if i < 8 { (format >> (7 - i)) & 1 } else { 0 };
+            let cell = self.s[i] | (u32::from(flag) << 31);
+            chunk.copy_from_slice(&cell.to_be_bytes());
+        }
+        out[64..68].copy_from_slice(&self.r1.to_be_bytes());
+        out[68..72].copy_from_slice(&self.r2.to_be_bytes());
+        out
+    }
+
+    /// Decodes a state encoded by [`Zuc::to_state_bytes`]
+    pub fn from_state_bytes(
+        bytes: &[u8; STATE_LEN],
+        alg: StateAlgorithm,
+    ) -> Result<Self, StateError> {
+        let word = |i: usize| {
+            let mut w = [0; 4];
+            w.copy_from_slice(&bytes[i * 4..i * 4 + 4]);
+            u32::from_be_bytes(w)
+        };
+
+        let mut zuc = Zuc::zeroed();
+        let mut format = 0;
+        for i in 0..16 {
+            let cell = word(i);
+            if i < 8 {
+                format = (format << 1) | u8::from(cell >> 31 == 1);
+            } else if cell >> 31 == 1 {
+                return Err(StateError::InvalidCell { index: i });
+            }
+            zuc.s[i] = cell & 0x7FFF_FFFF;
+        }
+        if format >> 4 != STATE_VERSION {
+            return Err(StateError::UnsupportedVersion(format >> 4));
+        }
+        if format & 0xF != alg as u8 {
+            return Err(StateError::AlgorithmMismatch);
+        }
+        if let Some(index) = zuc.s.iter().position(|&cell| cell == 0) {
+            return Err(StateError::InvalidCell { index });
+        }
+        zuc.r1 = word(16);
+        zuc.r2 = word(17);
+        Ok(zuc)
+    }
+}
+
+/// Deserializes a state snapshot from bytes or a sequence of bytes
+#[cfg(feature = "serde")]
+pub(crate) struct StateVisitor;
+
+#[cfg(feature = "serde")]
+impl<'de> serde::de::Visitor<'de> for StateVisitor {
+    type Value = [u8; STATE_LEN];
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{STATE_LEN} bytes of ZUC state")
+    }
+
+    fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        v.try_into().map_err(|_| E::invalid_length(v.len(), &self))
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut out = [0; STATE_LEN];
+        for (i, b) in out.iter_mut().enumerate() {
+            *b = seq
+                .next_element()?
+                .ok_or_else(|| serde::de::Error::invalid_length(i, &self))?;
+        }
+        if seq.next_element::<u8>()?.is_some() {
+            return Err(serde::de::Error::invalid_length(STATE_LEN + 1, &self));
+        }
+        Ok(out)
+    }
+}
+
+/// Maximum number of recorded checkpoints, 72 bytes each
+pub(crate) const MAX_CHECKPOINTS: usize = 1 << 16;
+
+/// Keystream position and optional state checkpoints for seeking
+///
+/// Seeking backwards without checkpoints rewinds the current state word by word.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Position {
+    /// number of words generated
+    pos: u64,
+    /// recorded states, `None` unless a checkpoint interval is set
+    pub(crate) checkpoints: Option<Box<Checkpoints>>,
+}
+
+/// States recorded at regular keystream positions
+///
+/// The states are key-equivalent, so they are only kept when enabled.
+#[derive(Clone, Debug)]
+pub(crate) struct Checkpoints {
+    /// number of words between checkpoints
+    interval: NonZeroU64,
+    /// states at multiples of `interval`, by position, at most [`MAX_CHECKPOINTS`]
+    pub(crate) states: Vec<(u64, Zuc)>,
+}
+
+impl Checkpoints {
+    /// Records `zuc`, at position `pos`, if a checkpoint is due there
+    #[inline]
+    fn record(&mut self, zuc: &Zuc, pos: u64) {
+        if pos % self.interval == 0 {
+            self.insert(zuc, pos);
+        }
+    }
+
+    /// Records `zuc` at position `pos`, unless already recorded or full
+    fn insert(&mut self, zuc: &Zuc, pos: u64) {
+        let len = self.states.len();
+        if len == MAX_CHECKPOINTS {
+            return;
+        }
+        let i = self.states.partition_point(|&(p, _)| p < pos);
+        if self.states.get(i).is_some_and(|&(p, _)| p == pos) {
+            return;
+        }
+        // `Vec::insert` would move the states to a new buffer and free the old one
+        // without dropping them, leaving copies of the states behind with the
+        // `zeroize` feature: copy them instead, so the old ones are wiped on drop.
+        if len == self.states.capacity() {
+            let mut states = Vec::with_capacity((len * 2).clamp(4, MAX_CHECKPOINTS));
+            states.extend(self.states.iter().cloned());
+            self.states = states;
+        }
+        self.states.insert(i, (pos, zuc.clone()));
+    }
+
+    /// Returns the nearest checkpoint at or before `pos`
+    fn nearest(&self, pos: u64) -> Option<(u64, &Zuc)> {
+        let i = self.states.partition_point(|&(p, _)| p <= pos);
+        let (p, zuc) = self.states.get(i.checked_sub(1)?)?;
+        Some((*p, zuc))
+    }
+}
+
+impl Position {
+    /// Returns the number of words generated
+    pub fn pos(&self) -> u64 {
+        self.pos
+    }
+
+    /// Sets the checkpoint interval, replacing the recorded checkpoints
+    /// with the current state `zuc`
+    pub fn set_interval(&mut self, zuc: &Zuc, interval: NonZeroU64) {
+        let mut checkpoints = Checkpoints {
+            interval,
+            states: Vec::new(),
+        };
+        checkpoints.insert(zuc, self.pos);
+        self.checkpoints = Some(Box::new(checkpoints));
+    }
+
+    /// Generates the next word, recording a checkpoint if one is due
+    #[inline(always)]
+    pub fn generate(&mut self, zuc: &mut Zuc) -> u32 {
+        if let Some(checkpoints) = &mut self.checkpoints {
+            checkpoints.record(zuc, self.pos);
+        }
+        self.pos += 1;
+        zuc.generate()
+    }
+
+    /// Rewinds `zuc` by `n` words
+    ///
+    /// # Panics
+    /// + Panics if `n` is greater than the number of words generated.
+    pub fn rewind(&mut self, zuc: &mut Zuc, n: u64) {
+        assert!(n <= self.pos, "cannot rewind before the initial state");
+        for _ in 0..n {
+            zuc.rewind();
+        }
+        self.pos -= n;
+    }
+
+    /// Moves `zuc` to the keystream position `pos`
+    ///
+    /// Starts from the nearest checkpoint before `pos` or from the current state,
+    /// whichever needs fewer steps, then steps forward, or backward from the current state.
+    /// Checkpoints passed while stepping forward are recorded, as when generating.
+    pub fn seek(&mut self, zuc: &mut Zuc, pos: u64) {
+        let nearest = self.checkpoints.as_ref().and_then(|c| c.nearest(pos));
+        let distance = self.pos.abs_diff(pos);
+        if let Some((start, state)) = nearest {
+            if pos - start < distance {
+                *zuc = state.clone();
+                self.pos = start;
+            }
+        }
+        if pos < self.pos {
+            self.rewind(zuc, self.pos - pos);
+        }
+        while self.pos < pos {
+            self.generate(zuc);
+        }
     }
 }
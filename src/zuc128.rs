@@ -1,11 +1,13 @@
 //! ZUC128 Algorithms
 
-use crate::zuc::Zuc;
+use crate::zuc::{Position, StateAlgorithm, StateError, Zuc, STATE_LEN};
+
+use std::num::NonZeroU64;
 
 use cipher::consts::{U1, U16, U4};
 
 /// (d<<8) constants
-static D: [u32; 16] = [
+pub(crate) static D: [u32; 16] = [
     0b_0100_0100_1101_0111_0000_0000,
     0b_0010_0110_1011_1100_0000_0000,
     0b_0110_0010_0110_1011_0000_0000,
@@ -24,35 +26,113 @@ static D: [u32; 16] = [
     0b_0100_0111_1010_1100_0000_0000,
 ];
 
+/// Loads the key, IV and (d<<8) constants into the LFSR
+pub(crate) fn load(key: &[u8; 16], iv: &[u8; 16], d: &[u32; 16]) -> Zuc {
+    let mut zuc = Zuc::zeroed();
+    for i in 0..16 {
+        let k_i = u32::from(key[i]);
+        let iv_i = u32::from(iv[i]);
+        zuc.s[i] = (k_i << 23) | d[i] | iv_i;
+    }
+    zuc
+}
+
 /// ZUC128 stream cipher
 /// ([GB/T 33133.1-2016](https://openstd.samr.gov.cn/bzgk/gb/newGbInfo?hcno=8C41A3AEECCA52B5C0011C8010CF0715))
 pub type Zuc128 = cipher::StreamCipherCoreWrapper<Zuc128Core>;
 
 /// ZUC128 keystream generator
 /// ([GB/T 33133.1-2016](https://openstd.samr.gov.cn/bzgk/gb/newGbInfo?hcno=8C41A3AEECCA52B5C0011C8010CF0715))
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Zuc128Core {
     /// zuc core
     core: Zuc,
+    /// keystream position and checkpoints
+    position: Position,
 }
 
 impl Zuc128Core {
     /// Creates a ZUC128 keystream generator
+    ///
+    /// # Panics
+    /// + Panics with the `auto-self-test` feature if the power-on self-tests failed.
     #[must_use]
     pub fn new(key: &[u8; 16], iv: &[u8; 16]) -> Self {
-        let mut zuc = Zuc::zeroed();
-        for i in 0..16 {
-            let k_i = u32::from(key[i]);
-            let iv_i = u32::from(iv[i]);
-            zuc.s[i] = (k_i << 23) | D[i] | iv_i;
-        }
+        #[cfg(feature = "auto-self-test")]
+        crate::self_test::ensure();
+        let mut zuc = load(key, iv, &D);
         zuc.init();
-        Self { core: zuc }
+        Self {
+            core: zuc,
+            position: Position::default(),
+        }
     }
 
     ///  Generates the next 32-bit word in ZUC128 keystream
     pub fn generate(&mut self) -> u32 {
-        self.core.generate()
+        self.position.generate(&mut self.core)
+    }
+
+    /// Rewinds the keystream by `n` words
+    ///
+    /// The next `n` words generated repeat the last `n` words.
+    ///
+    /// # Panics
+    /// + Panics if `n` is greater than the number of words generated.
+    pub fn rewind(&mut self, n: u64) {
+        self.position.rewind(&mut self.core, n);
+    }
+
+    /// Exports the keystream generator state
+    ///
+    /// The snapshot does not include the keystream position or the checkpoints.
+    #[must_use]
+    pub fn to_state_bytes(&self) -> [u8; STATE_LEN] {
+        self.core.to_state_bytes(StateAlgorithm::Zuc128)
+    }
+
+    /// Restores a keystream generator from a state exported by [`Zuc128Core::to_state_bytes`]
+    ///
+    /// The restored generator continues the keystream from the snapshot,
+    /// which becomes its initial state for seeking and rewinding.
+    ///
+    /// # Errors
+    /// + Returns [`StateError::UnsupportedVersion`] if the encoding version is unknown.
+    /// + Returns [`StateError::AlgorithmMismatch`] if the snapshot is not a ZUC-128 state.
+    /// + Returns [`StateError::InvalidCell`] if an LFSR cell is zero or out of the 31-bit range.
+    pub fn from_state_bytes(bytes: &[u8; STATE_LEN]) -> Result<Self, StateError> {
+        #[cfg(feature = "auto-self-test")]
+        crate::self_test::ensure();
+        let zuc = Zuc::from_state_bytes(bytes, StateAlgorithm::Zuc128)?;
+        Ok(Self {
+            core: zuc,
+            position: Position::default(),
+        })
+    }
+
+    /// Records a state checkpoint every `words` keystream words generated or passed while seeking
+    ///
+    /// The current state is recorded as the first checkpoint.
+    /// Seeking restores the nearest checkpoint before the target and steps forward,
+    /// so it costs at most `words` steps once the target has been reached before,
+    /// for 72 bytes of memory per checkpoint and at most 65536 checkpoints.
+    /// Without checkpoints seeking steps forward or rewinds from the current position.
+    ///
+    /// The checkpoints are copies of the state, as sensitive as the key.
+    ///
+    /// # Panics
+    /// + Panics if `words` is zero.
+    #[must_use]
+    pub fn with_checkpoint_interval(mut self, words: u64) -> Self {
+        let interval = NonZeroU64::new(words).expect("checkpoint interval is zero");
+        self.position.set_interval(&self.core, interval);
+        self
+    }
+
+    /// Internal state, for fault injection
+    #[cfg(test)]
+    pub(crate) fn core_mut(&mut self) -> &mut Zuc {
+        &mut self.core
     }
 }
 
@@ -65,6 +145,34 @@ impl Iterator for Zuc128Core {
     }
 }
 
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl serde::Serialize for Zuc128Core {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.to_state_bytes())
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl<'de> serde::Deserialize<'de> for Zuc128Core {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = deserializer.deserialize_bytes(crate::zuc::StateVisitor)?;
+        Self::from_state_bytes(&bytes).map_err(serde::de::Error::custom)
+    }
+}
+
+impl core::fmt::Debug for Zuc128Core {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("Zuc128Core { ... }")
+    }
+}
+
+/// The state is wiped on drop
+#[cfg(feature = "zeroize")]
+#[cfg_attr(docsrs, doc(cfg(feature = "zeroize")))]
+impl zeroize::ZeroizeOnDrop for Zuc128Core {}
+
 impl cipher::AlgorithmName for Zuc128Core {
     fn write_alg_name(f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(f, "Zuc128")
@@ -110,6 +218,18 @@ impl cipher::StreamCipherCore for Zuc128Core {
     }
 }
 
+impl cipher::StreamCipherSeekCore for Zuc128Core {
+    type Counter = u64;
+
+    fn get_block_pos(&self) -> u64 {
+        self.position.pos()
+    }
+
+    fn set_block_pos(&mut self, pos: u64) {
+        self.position.seek(&mut self.core, pos);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -245,9 +365,9 @@ mod tests {
 
             {
                 let mut zuc = zuc.clone();
+                assert_eq!(zuc.core.bit_reconstruction(), expected[0][..4]);
                 zuc.core.lfsr_with_work_mode();
 
-                // assert_eq!(zuc.core.x, expected[0][..4]);
                 assert_eq!(zuc.core.r1, expected[0][4]);
                 assert_eq!(zuc.core.r2, expected[0][5]);
                 assert_eq!(zuc.core.s[15], expected[0][7]);
@@ -257,9 +377,9 @@ mod tests {
 
             {
                 let mut zuc = zuc.clone();
+                assert_eq!(zuc.core.bit_reconstruction(), expected[1][..4]);
                 zuc.core.lfsr_with_work_mode();
 
-                // assert_eq!(zuc.core.x, expected[1][..4]);
                 assert_eq!(zuc.core.r1, expected[1][4]);
                 assert_eq!(zuc.core.r2, expected[1][5]);
                 assert_eq!(z1, expected[1][6]);
@@ -270,9 +390,9 @@ mod tests {
 
             {
                 let mut zuc = zuc.clone();
+                assert_eq!(zuc.core.bit_reconstruction(), expected[2][..4]);
                 zuc.core.lfsr_with_work_mode();
 
-                // assert_eq!(zuc.core.x, expected[2][..4]);
                 assert_eq!(zuc.core.r1, expected[2][4]);
                 assert_eq!(zuc.core.r2, expected[2][5]);
                 assert_eq!(z2, expected[2][6]);
@@ -294,4 +414,183 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn seek() {
+        use cipher::{StreamCipher, StreamCipherSeek};
+
+        let (k, iv) = ([0x3d; 16], [0x84; 16]);
+        let mut expected = vec![0u8; 5000];
+        crate::Zuc128::from_core(Zuc128Core::new(&k, &iv)).apply_keystream(&mut expected);
+
+        for interval in [None, Some(1), Some(7), Some(64)] {
+            let mut core = Zuc128Core::new(&k, &iv);
+            if let Some(words) = interval {
+                core = core.with_checkpoint_interval(words);
+            }
+            let mut zuc = crate::Zuc128::from_core(core);
+            for (pos, len) in [
+                (4000, 3),
+                (0, 10),
+                (1234, 7),
+                (1233, 900),
+                (4999, 1),
+                (2, 4998),
+                (17, 0),
+            ] {
+                zuc.seek(pos);
+                assert_eq!(zuc.current_pos::<usize>(), pos);
+                let mut buf = vec![0u8; len];
+                zuc.apply_keystream(&mut buf);
+                assert_eq!(buf, expected[pos..pos + len]);
+                assert_eq!(zuc.current_pos::<usize>(), pos + len);
+            }
+        }
+    }
+
+    #[test]
+    fn checkpoints() {
+        use cipher::StreamCipherSeekCore;
+
+        let mut core = Zuc128Core::new(&[0x3d; 16], &[0x84; 16]);
+        core.set_block_pos(100);
+        core.set_block_pos(10);
+        assert!(core.position.checkpoints.is_none());
+
+        let mut core = core.with_checkpoint_interval(1);
+        let max = crate::zuc::MAX_CHECKPOINTS as u64;
+        core.set_block_pos(max + 10);
+        let checkpoints = core.position.checkpoints.as_ref().unwrap();
+        assert_eq!(checkpoints.states.len() as u64, max);
+        assert!(checkpoints.states.capacity() as u64 <= max);
+    }
+
+    #[test]
+    fn generated_checkpoints() {
+        use cipher::{StreamCipher, StreamCipherSeek};
+
+        let (k, iv) = ([0x3d; 16], [0x84; 16]);
+        let mut zuc = Zuc128Core::new(&k, &iv).with_checkpoint_interval(64);
+        let expected: Vec<u8> = (0..1000)
+            .flat_map(|_| zuc.generate().to_be_bytes())
+            .collect();
+
+        let checkpoints = zuc.position.checkpoints.as_mut().unwrap();
+        let positions: Vec<u64> = checkpoints.states.iter().map(|&(p, _)| p).collect();
+        assert_eq!(positions, (0..16).map(|i| i * 64).collect::<Vec<_>>());
+
+        // replace the checkpoints at 0 and 128 to tell a restore from a rewind
+        let other = Zuc128Core::new(&[0x11; 16], &iv);
+        checkpoints.states[0].1 = other.core.clone();
+        checkpoints.states[2].1 = other.core.clone();
+        let mut zuc = crate::Zuc128::from_core(zuc);
+        let mut other = crate::Zuc128::from_core(other);
+        let mut from_other = [0u8; 8];
+        other.apply_keystream(&mut from_other);
+
+        for pos in [0, 128 * 4] {
+            zuc.seek(pos);
+            let mut buf = [0u8; 8];
+            zuc.apply_keystream(&mut buf);
+            assert_eq!(buf, from_other);
+            assert_ne!(buf, expected[pos..pos + 8]);
+        }
+
+        // a position after an untouched checkpoint
+        zuc.seek(1000);
+        let mut buf = [0u8; 8];
+        zuc.apply_keystream(&mut buf);
+        assert_eq!(buf, expected[1000..1008]);
+    }
+
+    #[test]
+    fn rewind() {
+        for _ in 0..20 {
+            let k: [u8; 16] = rand::random();
+            let iv: [u8; 16] = rand::random();
+            let initial = Zuc128Core::new(&k, &iv);
+            let mut core = initial.clone();
+            let words: Vec<u32> = (0..300).map(|_| core.generate()).collect();
+
+            core.rewind(100);
+            let again: Vec<u32> = (0..100).map(|_| core.generate()).collect();
+            assert_eq!(again, words[200..]);
+
+            core.rewind(300);
+            assert_eq!(core.core.s, initial.core.s);
+            assert_eq!(core.core.r1, initial.core.r1);
+            assert_eq!(core.core.r2, initial.core.r2);
+            assert_eq!(core.generate(), words[0]);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot rewind before the initial state")]
+    fn rewind_too_far() {
+        let mut core = Zuc128Core::new(&[0; 16], &[0; 16]);
+        let _ = core.generate();
+        core.rewind(2);
+    }
+
+    #[test]
+    fn state_bytes() {
+        let mut core = Zuc128Core::new(&[0x3d; 16], &[0x84; 16]);
+        for _ in 0..1000 {
+            let _ = core.generate();
+        }
+        let state = core.to_state_bytes();
+        let mut restored = Zuc128Core::from_state_bytes(&state).unwrap();
+        for _ in 0..1000 {
+            assert_eq!(restored.generate(), core.generate());
+        }
+
+        assert_eq!(
+            crate::Zuc256Core::from_state_bytes(&state).unwrap_err(),
+            StateError::AlgorithmMismatch
+        );
+
+        let mut bad = state;
+        bad[0] ^= 0x80;
+        assert_eq!(
+            Zuc128Core::from_state_bytes(&bad).unwrap_err(),
+            StateError::UnsupportedVersion(9)
+        );
+
+        let mut bad = state;
+        bad[40..44].fill(0);
+        assert_eq!(
+            Zuc128Core::from_state_bytes(&bad).unwrap_err(),
+            StateError::InvalidCell { index: 10 }
+        );
+        bad[40] = 0x80;
+        assert_eq!(
+            Zuc128Core::from_state_bytes(&bad).unwrap_err(),
+            StateError::InvalidCell { index: 10 }
+        );
+    }
+
+    #[test]
+    fn redacted_debug() {
+        let core = Zuc128Core::new(&[0x3d; 16], &[0x84; 16]);
+        assert_eq!(format!("{core:?}"), "Zuc128Core { ... }");
+        let core = crate::Zuc256Core::new(&[0x3d; 32], &[0x84; 23]);
+        assert_eq!(format!("{core:?}"), "Zuc256Core { ... }");
+        let mac = crate::Zuc128Mac::new(&[0x3d; 16], &[0x84; 16]);
+        assert_eq!(format!("{mac:?}"), "Zuc128Mac { ... }");
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn zeroize() {
+        fn zeroize_on_drop<T: zeroize::ZeroizeOnDrop>() {}
+        zeroize_on_drop::<Zuc128Core>();
+        zeroize_on_drop::<crate::Zuc256Core>();
+        zeroize_on_drop::<crate::Zuc128Mac>();
+        zeroize_on_drop::<crate::Zuc256Mac<u128>>();
+
+        let mut core = Zuc128Core::new(&[0x3d; 16], &[0x84; 16]);
+        zeroize::Zeroize::zeroize(core.core_mut());
+        let zuc = core.core_mut();
+        assert_eq!((zuc.s, zuc.r1, zuc.r2), ([0; 16], 0, 0));
+    }
 }
@@ -1,11 +1,13 @@
 //! ZUC-256 Algorithms
 
-use crate::zuc::Zuc;
+use crate::zuc::{Position, StateAlgorithm, StateError, Zuc, STATE_LEN};
+
+use std::num::NonZeroU64;
 
 use cipher::consts::{U1, U23, U32, U4};
 
 /// d constants
-static D: [u8; 16] = [
+pub(crate) static D: [u8; 16] = [
     0b_0010_0010, //
     0b_0010_1111, //
     0b_0010_0100, //
@@ -29,20 +31,57 @@ fn concat_bits(a: u8, b: u8, c: u8, d: u8) -> u32 {
     (u32::from(a) << 23) | (u32::from(b) << 16) | (u32::from(c) << 8) | u32::from(d)
 }
 
+/// Loads the key, IV and d constants into the LFSR
+pub(crate) fn load(k: &[u8; 32], iv: &[u8; 23], d: &[u8; 16]) -> Zuc {
+    let mut zuc = Zuc::zeroed();
+    // extend from 184bit iv[0..=22] (u8*23) to iv[0..=24](8bit*17 + 6bit *8)
+    let iv17: u8 = iv[17] >> 2;
+    let iv18: u8 = ((iv[17] & 0x3) << 4) | (iv[18] >> 4);
+    let iv19: u8 = ((iv[18] & 0xf) << 2) | (iv[19] >> 6);
+    let iv20: u8 = iv[19] & 0x3f;
+    let iv21: u8 = iv[20] >> 2;
+    let iv22: u8 = ((iv[20] & 0x3) << 4) | (iv[21] >> 4);
+    let iv23: u8 = ((iv[21] & 0xf) << 2) | (iv[22] >> 6);
+    let iv24: u8 = iv[22] & 0x3f;
+
+    zuc.s[0] = concat_bits(k[0], d[0], k[21], k[16]);
+    zuc.s[1] = concat_bits(k[1], d[1], k[22], k[17]);
+    zuc.s[2] = concat_bits(k[2], d[2], k[23], k[18]);
+    zuc.s[3] = concat_bits(k[3], d[3], k[24], k[19]);
+    zuc.s[4] = concat_bits(k[4], d[4], k[25], k[20]);
+    zuc.s[5] = concat_bits(iv[0], d[5] | iv17, k[5], k[26]);
+    zuc.s[6] = concat_bits(iv[1], d[6] | iv18, k[6], k[27]);
+    zuc.s[7] = concat_bits(iv[10], d[7] | iv19, k[7], iv[2]);
+    zuc.s[8] = concat_bits(k[8], d[8] | iv20, iv[3], iv[11]);
+    zuc.s[9] = concat_bits(k[9], d[9] | iv21, iv[12], iv[4]);
+    zuc.s[10] = concat_bits(iv[5], d[10] | iv22, k[10], k[28]);
+    zuc.s[11] = concat_bits(k[11], d[11] | iv23, iv[6], iv[13]);
+    zuc.s[12] = concat_bits(k[12], d[12] | iv24, iv[7], iv[14]);
+    zuc.s[13] = concat_bits(k[13], d[13], iv[15], iv[8]);
+    zuc.s[14] = concat_bits(k[14], d[14] | (k[31] >> 4), iv[16], iv[9]);
+    zuc.s[15] = concat_bits(k[15], d[15] | (k[31] & 0b_1111), k[30], k[29]);
+    zuc
+}
+
 /// ZUC256 stream cipher
 /// ([ZUC256-version1.1](http://www.is.cas.cn/ztzl2016/zouchongzhi/201801/W020180416526664982687.pdf))
 pub type Zuc256 = cipher::StreamCipherCoreWrapper<Zuc256Core>;
 
 /// ZUC256 keystream generator
 /// ([ZUC256-version1.1](http://www.is.cas.cn/ztzl2016/zouchongzhi/201801/W020180416526664982687.pdf))
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Zuc256Core {
     /// zuc core
     core: Zuc,
+    /// keystream position and checkpoints
+    position: Position,
 }
 
 impl Zuc256Core {
     /// Creates a ZUC256 keystream generator
+    ///
+    /// # Panics
+    /// + Panics with the `auto-self-test` feature if the power-on self-tests failed.
     #[must_use]
     pub fn new(k: &[u8; 32], iv: &[u8; 23]) -> Self {
         Zuc256Core::new_with_d(k, iv, &D)
@@ -50,44 +89,94 @@ impl Zuc256Core {
 
     /// Creates a [`Zuc256Core`] with specific d constants
     pub(crate) fn new_with_d(k: &[u8; 32], iv: &[u8; 23], d: &[u8; 16]) -> Self {
-        let mut zuc = Zuc::zeroed();
-        // extend from 184bit iv[0..=22] (u8*23) to iv[0..=24](8bit*17 + 6bit *8)
-        let iv17: u8 = iv[17] >> 2;
-        let iv18: u8 = ((iv[17] & 0x3) << 4) | (iv[18] >> 4);
-        let iv19: u8 = ((iv[18] & 0xf) << 2) | (iv[19] >> 6);
-        let iv20: u8 = iv[19] & 0x3f;
-        let iv21: u8 = iv[20] >> 2;
-        let iv22: u8 = ((iv[20] & 0x3) << 4) | (iv[21] >> 4);
-        let iv23: u8 = ((iv[21] & 0xf) << 2) | (iv[22] >> 6);
-        let iv24: u8 = iv[22] & 0x3f;
-
-        zuc.s[0] = concat_bits(k[0], d[0], k[21], k[16]);
-        zuc.s[1] = concat_bits(k[1], d[1], k[22], k[17]);
-        zuc.s[2] = concat_bits(k[2], d[2], k[23], k[18]);
-        zuc.s[3] = concat_bits(k[3], d[3], k[24], k[19]);
-        zuc.s[4] = concat_bits(k[4], d[4], k[25], k[20]);
-        zuc.s[5] = concat_bits(iv[0], d[5] | iv17, k[5], k[26]);
-        zuc.s[6] = concat_bits(iv[1], d[6] | iv18, k[6], k[27]);
-        zuc.s[7] = concat_bits(iv[10], d[7] | iv19, k[7], iv[2]);
-        zuc.s[8] = concat_bits(k[8], d[8] | iv20, iv[3], iv[11]);
-        zuc.s[9] = concat_bits(k[9], d[9] | iv21, iv[12], iv[4]);
-        zuc.s[10] = concat_bits(iv[5], d[10] | iv22, k[10], k[28]);
-        zuc.s[11] = concat_bits(k[11], d[11] | iv23, iv[6], iv[13]);
-        zuc.s[12] = concat_bits(k[12], d[12] | iv24, iv[7], iv[14]);
-        zuc.s[13] = concat_bits(k[13], d[13], iv[15], iv[8]);
-        zuc.s[14] = concat_bits(k[14], d[14] | (k[31] >> 4), iv[16], iv[9]);
-        zuc.s[15] = concat_bits(k[15], d[15] | (k[31] & 0b_1111), k[30], k[29]);
+        #[cfg(feature = "auto-self-test")]
+        crate::self_test::ensure();
+        let mut zuc = load(k, iv, d);
         zuc.init();
-        Self { core: zuc }
+        Self {
+            core: zuc,
+            position: Position::default(),
+        }
     }
 
     ///  Generates the next 32-bit word in ZUC256 keystream
     #[must_use]
     pub fn generate(&mut self) -> u32 {
-        self.core.generate()
+        self.position.generate(&mut self.core)
+    }
+
+    /// Rewinds the keystream by `n` words
+    ///
+    /// The next `n` words generated repeat the last `n` words.
+    ///
+    /// # Panics
+    /// + Panics if `n` is greater than the number of words generated.
+    pub fn rewind(&mut self, n: u64) {
+        self.position.rewind(&mut self.core, n);
+    }
+
+    /// Exports the keystream generator state
+    ///
+    /// The snapshot does not include the keystream position or the checkpoints.
+    #[must_use]
+    pub fn to_state_bytes(&self) -> [u8; STATE_LEN] {
+        self.core.to_state_bytes(StateAlgorithm::Zuc256)
+    }
+
+    /// Restores a keystream generator from a state exported by [`Zuc256Core::to_state_bytes`]
+    ///
+    /// The restored generator continues the keystream from the snapshot,
+    /// which becomes its initial state for seeking and rewinding.
+    ///
+    /// # Errors
+    /// + Returns [`StateError::UnsupportedVersion`] if the encoding version is unknown.
+    /// + Returns [`StateError::AlgorithmMismatch`] if the snapshot is not a ZUC-256 state.
+    /// + Returns [`StateError::InvalidCell`] if an LFSR cell is zero or out of the 31-bit range.
+    pub fn from_state_bytes(bytes: &[u8; STATE_LEN]) -> Result<Self, StateError> {
+        #[cfg(feature = "auto-self-test")]
+        crate::self_test::ensure();
+        let zuc = Zuc::from_state_bytes(bytes, StateAlgorithm::Zuc256)?;
+        Ok(Self {
+            core: zuc,
+            position: Position::default(),
+        })
+    }
+
+    /// Records a state checkpoint every `words` keystream words generated or passed while seeking
+    ///
+    /// The current state is recorded as the first checkpoint.
+    /// Seeking restores the nearest checkpoint before the target and steps forward,
+    /// so it costs at most `words` steps once the target has been reached before,
+    /// for 72 bytes of memory per checkpoint and at most 65536 checkpoints.
+    /// Without checkpoints seeking steps forward or rewinds from the current position.
+    ///
+    /// The checkpoints are copies of the state, as sensitive as the key.
+    ///
+    /// # Panics
+    /// + Panics if `words` is zero.
+    #[must_use]
+    pub fn with_checkpoint_interval(mut self, words: u64) -> Self {
+        let interval = NonZeroU64::new(words).expect("checkpoint interval is zero");
+        self.position.set_interval(&self.core, interval);
+        self
+    }
+
+    /// Internal state, for fault injection
+    #[cfg(test)]
+    pub(crate) fn core_mut(&mut self) -> &mut Zuc {
+        &mut self.core
     }
 }
 
+/// Derives the IV of chunk or record `index` by XOR-ing the index into the first 8 bytes of `iv`
+pub(crate) fn indexed_iv(iv: &[u8; 23], index: u64) -> [u8; 23] {
+    let mut iv = *iv;
+    for (b, x) in iv.iter_mut().zip(index.to_be_bytes()) {
+        *b ^= x;
+    }
+    iv
+}
+
 impl Iterator for Zuc256Core {
     type Item = u32;
 
@@ -97,6 +186,34 @@ impl Iterator for Zuc256Core {
     }
 }
 
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl serde::Serialize for Zuc256Core {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.to_state_bytes())
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl<'de> serde::Deserialize<'de> for Zuc256Core {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = deserializer.deserialize_bytes(crate::zuc::StateVisitor)?;
+        Self::from_state_bytes(&bytes).map_err(serde::de::Error::custom)
+    }
+}
+
+impl core::fmt::Debug for Zuc256Core {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("Zuc256Core { ... }")
+    }
+}
+
+/// The state is wiped on drop
+#[cfg(feature = "zeroize")]
+#[cfg_attr(docsrs, doc(cfg(feature = "zeroize")))]
+impl zeroize::ZeroizeOnDrop for Zuc256Core {}
+
 impl cipher::AlgorithmName for Zuc256Core {
     fn write_alg_name(f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(f, "Zuc256")
@@ -142,6 +259,18 @@ impl cipher::StreamCipherCore for Zuc256Core {
     }
 }
 
+impl cipher::StreamCipherSeekCore for Zuc256Core {
+    type Counter = u64;
+
+    fn get_block_pos(&self) -> u64 {
+        self.position.pos()
+    }
+
+    fn set_block_pos(&mut self, pos: u64) {
+        self.position.seek(&mut self.core, pos);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -230,4 +359,131 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn seek() {
+        use cipher::{StreamCipher, StreamCipherSeek};
+
+        let (k, iv) = ([0x3d; 32], [0x84; 23]);
+        let mut expected = vec![0u8; 5000];
+        crate::Zuc256::from_core(Zuc256Core::new(&k, &iv)).apply_keystream(&mut expected);
+
+        for interval in [None, Some(1), Some(7), Some(64)] {
+            let mut core = Zuc256Core::new(&k, &iv);
+            if let Some(words) = interval {
+                core = core.with_checkpoint_interval(words);
+            }
+            let mut zuc = crate::Zuc256::from_core(core);
+            for (pos, len) in [
+                (4000, 3),
+                (0, 10),
+                (1234, 7),
+                (1233, 900),
+                (4999, 1),
+                (2, 4998),
+                (17, 0),
+            ] {
+                zuc.seek(pos);
+                assert_eq!(zuc.current_pos::<usize>(), pos);
+                let mut buf = vec![0u8; len];
+                zuc.apply_keystream(&mut buf);
+                assert_eq!(buf, expected[pos..pos + len]);
+                assert_eq!(zuc.current_pos::<usize>(), pos + len);
+            }
+        }
+    }
+
+    #[test]
+    fn rewind() {
+        for _ in 0..20 {
+            let k: [u8; 32] = rand::random();
+            let iv: [u8; 23] = rand::random();
+            let initial = Zuc256Core::new(&k, &iv);
+            let mut core = initial.clone();
+            let words: Vec<u32> = (0..300).map(|_| core.generate()).collect();
+
+            core.rewind(100);
+            let again: Vec<u32> = (0..100).map(|_| core.generate()).collect();
+            assert_eq!(again, words[200..]);
+
+            core.rewind(300);
+            assert_eq!(core.core.s, initial.core.s);
+            assert_eq!(core.core.r1, initial.core.r1);
+            assert_eq!(core.core.r2, initial.core.r2);
+            assert_eq!(core.generate(), words[0]);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot rewind before the initial state")]
+    fn rewind_too_far() {
+        let mut core = Zuc256Core::new(&[0; 32], &[0; 23]);
+        let _ = core.generate();
+        core.rewind(2);
+    }
+
+    #[test]
+    fn state_bytes() {
+        let mut core = Zuc256Core::new(&[0x3d; 32], &[0x84; 23]);
+        for _ in 0..1000 {
+            let _ = core.generate();
+        }
+        let state = core.to_state_bytes();
+        let mut restored = Zuc256Core::from_state_bytes(&state).unwrap();
+        for _ in 0..1000 {
+            assert_eq!(restored.generate(), core.generate());
+        }
+
+        assert_eq!(
+            crate::Zuc128Core::from_state_bytes(&state).unwrap_err(),
+            StateError::AlgorithmMismatch
+        );
+
+        let mut bad = state;
+        bad[0] ^= 0x80;
+        assert_eq!(
+            Zuc256Core::from_state_bytes(&bad).unwrap_err(),
+            StateError::UnsupportedVersion(9)
+        );
+
+        let mut bad = state;
+        bad[40..44].fill(0);
+        assert_eq!(
+            Zuc256Core::from_state_bytes(&bad).unwrap_err(),
+            StateError::InvalidCell { index: 10 }
+        );
+        bad[40] = 0x80;
+        assert_eq!(
+            Zuc256Core::from_state_bytes(&bad).unwrap_err(),
+            StateError::InvalidCell { index: 10 }
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        use serde::de::value::{BytesDeserializer, Error, SeqDeserializer};
+        use serde::Deserialize;
+        use serde_test::{assert_ser_tokens, Token};
+
+        let mut core = Zuc256Core::new(&[0x3d; 32], &[0x84; 23]);
+        let _ = core.generate();
+        let state = core.to_state_bytes();
+        let bytes: &'static [u8] = Box::leak(Box::new(state));
+        assert_ser_tokens(&core, &[Token::Bytes(bytes)]);
+
+        let de = BytesDeserializer::<Error>::new(&state);
+        let mut restored = Zuc256Core::deserialize(de).unwrap();
+        assert_eq!(restored.generate(), core.generate());
+
+        let de = SeqDeserializer::<_, Error>::new(state.into_iter());
+        let mut restored = Zuc256Core::deserialize(de).unwrap();
+        let _ = restored.generate();
+        assert_eq!(restored.generate(), core.generate());
+
+        let de = BytesDeserializer::<Error>::new(&state[..3]);
+        assert!(Zuc256Core::deserialize(de).is_err());
+        let de = SeqDeserializer::<_, Error>::new(state[..3].iter().copied());
+        assert!(Zuc256Core::deserialize(de).is_err());
+    }
 }
@@ -20,16 +20,30 @@ const D_128: [u8; 16] = [
     0b100_0000, 0b100_0000, 0b100_0000, 0b100_0000, 0b100_0000, 0b101_0010, 0b001_0000, 0b011_0000,
 ];
 
-mod private {
+pub(crate) mod private {
     //! private for sealed trait
 
     use std::mem::size_of;
     use std::ops::{BitXorAssign, ShlAssign};
 
+    /// Values that can be wiped, when the `zeroize` feature is enabled
+    #[cfg(feature = "zeroize")]
+    pub trait Wipe: zeroize::Zeroize {}
+
+    #[cfg(feature = "zeroize")]
+    impl<T: zeroize::Zeroize> Wipe for T {}
+
+    /// Values that can be wiped, when the `zeroize` feature is enabled
+    #[cfg(not(feature = "zeroize"))]
+    pub trait Wipe {}
+
+    #[cfg(not(feature = "zeroize"))]
+    impl<T> Wipe for T {}
+
     /// Mac Word
     pub trait MacWord
     where
-        Self: Sized + Copy,
+        Self: Sized + Copy + Wipe,
         Self: BitXorAssign,
         Self: ShlAssign<usize>,
     {
@@ -55,7 +69,7 @@ mod private {
     /// Mac Key Pair
     pub trait MacKeyPair
     where
-        Self: Sized + Copy,
+        Self: Sized + Copy + Wipe,
         Self: ShlAssign<usize>,
     {
         /// Mac Word Type
@@ -220,6 +234,16 @@ where
     T::from_chunk(&buf[..T::BYTE_SIZE])
 }
 
+/// d constants for the MAC size
+fn mac_d<T: MacWord>() -> &'static [u8; 16] {
+    match T::BIT_SIZE {
+        32 => &D_32,
+        64 => &D_64,
+        128 => &D_128,
+        _ => unreachable!(),
+    }
+}
+
 /// ZUC256 MAC generation algorithm
 /// ([ZUC256-version1.1](http://www.is.cas.cn/ztzl2016/zouchongzhi/201801/W020180416526664982687.pdf))
 ///
@@ -248,14 +272,7 @@ where
         "`length` is greater than the length of `m`"
     );
 
-    let d = match T::BIT_SIZE {
-        32 => &D_32,
-        64 => &D_64,
-        128 => &D_128,
-        _ => unreachable!(),
-    };
-
-    let mut zuc = Zuc256Core::new_with_d(ik, iv, d);
+    let mut zuc = Zuc256Core::new_with_d(ik, iv, mac_d::<T>());
     let mut gen = || zuc.generate();
 
     let mut tag: T = T::gen_word(&mut gen);
@@ -283,9 +300,128 @@ where
         tag ^= key.high();
     }
 
+    #[cfg(feature = "zeroize")]
+    zeroize::Zeroize::zeroize(&mut key);
+
     tag
 }
 
+/// Incremental ZUC256 MAC generation
+///
+/// Computes the same MAC as [`zuc256_generate_mac`] over a byte-aligned message
+/// fed in pieces.
+#[derive(Clone)]
+pub struct Zuc256Mac<T: MacWord> {
+    /// keystream generator
+    zuc: Zuc256Core,
+    /// accumulated tag
+    tag: T,
+    /// current key window
+    key: T::KeyPair,
+    /// pending bytes of the current word
+    buf: [u8; 16],
+    /// number of pending bytes
+    buf_len: usize,
+}
+
+impl<T: MacWord> Zuc256Mac<T> {
+    /// Creates an incremental MAC generator
+    ///
+    /// Input:
+    /// - `ik`:         256bit          integrity key
+    /// - `iv`:         184bit          initial vector
+    #[must_use]
+    pub fn new(ik: &[u8; 32], iv: &[u8; 23]) -> Self {
+        let mut zuc = Zuc256Core::new_with_d(ik, iv, mac_d::<T>());
+        let mut gen = || zuc.generate();
+        let tag = T::gen_word(&mut gen);
+        let key = T::KeyPair::gen_key_pair(&mut gen);
+        Self {
+            zuc,
+            tag,
+            key,
+            buf: [0; 16],
+            buf_len: 0,
+        }
+    }
+
+    /// Processes one full word
+    fn process_word(&mut self, chunk: &[u8]) {
+        let mut bits = T::from_chunk(chunk);
+        for _ in 0..T::BIT_SIZE {
+            zuc_256_mac_xor_t(&mut bits, &mut self.key, &mut self.tag);
+        }
+        let zuc = &mut self.zuc;
+        self.key.set_low(T::gen_word(&mut || zuc.generate()));
+    }
+
+    /// Feeds message bytes
+    pub fn update(&mut self, mut data: &[u8]) {
+        if self.buf_len > 0 {
+            let n = data.len().min(T::BYTE_SIZE - self.buf_len);
+            self.buf[self.buf_len..self.buf_len + n].copy_from_slice(&data[..n]);
+            self.buf_len += n;
+            data = &data[n..];
+            if self.buf_len < T::BYTE_SIZE {
+                return;
+            }
+            let buf = self.buf;
+            self.process_word(&buf[..T::BYTE_SIZE]);
+            self.buf_len = 0;
+        }
+        let mut chunks = data.chunks_exact(T::BYTE_SIZE);
+        for chunk in &mut chunks {
+            self.process_word(chunk);
+        }
+        let rest = chunks.remainder();
+        self.buf[..rest.len()].copy_from_slice(rest);
+        self.buf_len = rest.len();
+    }
+
+    /// Returns the MAC of the bytes fed so far
+    #[must_use]
+    pub fn finish(mut self) -> T {
+        let mut bits = {
+            self.buf[self.buf_len..].fill(0);
+            T::from_chunk(&self.buf[..T::BYTE_SIZE])
+        };
+        for _ in 0..self.buf_len * 8 {
+            zuc_256_mac_xor_t(&mut bits, &mut self.key, &mut self.tag);
+        }
+        self.tag ^= self.key.high();
+        self.tag
+    }
+
+    /// Keystream generator, for fault injection
+    #[cfg(test)]
+    pub(crate) fn zuc_mut(&mut self) -> &mut Zuc256Core {
+        &mut self.zuc
+    }
+}
+
+impl<T: MacWord> core::fmt::Debug for Zuc256Mac<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("Zuc256Mac { ... }")
+    }
+}
+
+/// Wipes the tag, the keystream window and the pending bytes
+#[cfg(feature = "zeroize")]
+impl<T: MacWord> Drop for Zuc256Mac<T> {
+    fn drop(&mut self) {
+        use zeroize::Zeroize;
+
+        self.tag.zeroize();
+        self.key.zeroize();
+        self.buf.zeroize();
+    }
+}
+
+/// The state is wiped on drop
+#[cfg(feature = "zeroize")]
+#[cfg_attr(docsrs, doc(cfg(feature = "zeroize")))]
+impl<T: MacWord> zeroize::ZeroizeOnDrop for Zuc256Mac<T> {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -382,4 +518,30 @@ mod tests {
             assert_eq!(mac_32, expected_32, "actual = {mac_32:08x}");
         }
     }
+
+    #[test]
+    fn incremental() {
+        fn check<T: MacWord + PartialEq + std::fmt::Debug>(k: &[u8; 32], iv: &[u8; 23], m: &[u8]) {
+            let bitlen = u32::try_from(m.len() * 8).unwrap();
+            let expected = zuc256_generate_mac::<T>(k, iv, bitlen, m);
+            for step in [1, 3, 16, 17, 500] {
+                let mut mac = Zuc256Mac::<T>::new(k, iv);
+                m.chunks(step).for_each(|piece| mac.update(piece));
+                assert_eq!(mac.finish(), expected);
+            }
+        }
+
+        let m: Vec<u8> = (0..=u8::MAX).cycle().take(500).collect();
+        for x in [&EXAMPLE_MAC_1, &EXAMPLE_MAC_3] {
+            for len in [0, 1, 4, 15, 16, 50, 500] {
+                check::<u32>(&x.k, &x.iv, &m[..len]);
+                check::<u64>(&x.k, &x.iv, &m[..len]);
+                check::<u128>(&x.k, &x.iv, &m[..len]);
+            }
+        }
+
+        let mut mac = Zuc256Mac::<u128>::new(&EXAMPLE_MAC_2.k, &EXAMPLE_MAC_2.iv);
+        mac.update(EXAMPLE_MAC_2.m);
+        assert_eq!(mac.finish(), EXAMPLE_MAC_2.expected_128);
+    }
 }
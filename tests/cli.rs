@@ -0,0 +1,467 @@
+//! Integration tests of the `zuc` binary
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use const_str::hex;
+
+/// Runs the binary with `args`, feeding `stdin`, and returns stdout
+fn zuc(args: &[&str], stdin: &[u8]) -> Vec<u8> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_zuc"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output.stdout
+}
+
+/// Runs the binary with `args` and expects a failure
+fn zuc_fails(args: &[&str]) {
+    let output = Command::new(env!("CARGO_BIN_EXE_zuc"))
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+/// Lowercase hex with a trailing newline
+fn hex_line(bytes: &[u8]) -> Vec<u8> {
+    let mut s: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    s.push('\n');
+    s.into_bytes()
+}
+
+struct Eea3Example {
+    ck: &'static str,
+    count: &'static str,
+    bearer: &'static str,
+    direction: &'static str,
+    length: &'static str,
+    ibs: &'static [u8],
+    obs: &'static [u8],
+}
+
+/// Test Sets 1 and 2
+/// FROM <https://www.gsma.com/solutions-and-impact/technologies/security/wp-content/uploads/2019/05/eea3eia3testdatav11.pdf>
+static EEA3_EXAMPLES: [Eea3Example; 2] = [
+    Eea3Example {
+        ck: "173d14ba5003731d7a60049470f00a29",
+        count: "0x66035492",
+        bearer: "0xf",
+        direction: "0",
+        length: "193",
+        ibs: &hex!("6cf65340 735552ab 0c9752fa 6f9025fe 0bd675d9 005875b2 00000000"),
+        obs: &hex!("a6c85fc6 6afb8533 aafc2518 dfe78494 0ee1e4b0 30238cc8 00000000"),
+    },
+    Eea3Example {
+        ck: "e5bd3ea0eb55ade866c6ac58bd54302a",
+        count: "0x56823",
+        bearer: "24",
+        direction: "1",
+        length: "800",
+        ibs: &hex!([
+            "14a8ef69 3d678507 bbe7270a 7f67ff50 06c3525b 9807e467 c4e56000 ba338f5d",
+            "42955903 67518222 46c80d3b 38f07f4b e2d8ff58 05f51322 29bde93b bbdcaf38",
+            "2bf1ee97 2fbf9977 bada8945 847a2a6c 9ad34a66 7554e04d 1f7fa2c3 3241bd8f",
+            "01ba220d",
+        ]),
+        obs: &hex!([
+            "131d43e0 dea1be5c 5a1bfd97 1d852cbf 712d7b4f 57961fea 3208afa8 bca433f4",
+            "56ad09c7 417e58bc 69cf8866 d1353f74 865e8078 1d202dfb 3ecff7fc bc3b190f",
+            "e82a204e d0e350fc 0f6f2613 b2f2bca6 df5a473a 57a4a00d 985ebad8 80d6f238",
+            "64a07b01",
+        ]),
+    },
+];
+
+struct Eia3Example {
+    ik: &'static str,
+    count: &'static str,
+    bearer: &'static str,
+    direction: &'static str,
+    length: &'static str,
+    m: &'static [u8],
+    mac: &'static str,
+}
+
+/// Test Sets 1 to 3
+/// FROM <https://www.gsma.com/solutions-and-impact/technologies/security/wp-content/uploads/2019/05/eea3eia3testdatav11.pdf>
+static EIA3_EXAMPLES: [Eia3Example; 3] = [
+    Eia3Example {
+        ik: "00000000000000000000000000000000",
+        count: "0",
+        bearer: "0",
+        direction: "0",
+        length: "1",
+        m: &hex!("00000000"),
+        mac: "c8a9595e\n",
+    },
+    Eia3Example {
+        ik: "47054125561eb2dda94059da05097850",
+        count: "0x561eb2dd",
+        bearer: "0x14",
+        direction: "0",
+        length: "90",
+        m: &hex!("00000000 00000000 00000000"),
+        mac: "6719a088\n",
+    },
+    Eia3Example {
+        ik: "c9e6cec4607c72db000aefa88385ab0a",
+        count: "0xa94059da",
+        bearer: "0xa",
+        direction: "1",
+        length: "577",
+        m: &hex!([
+            "983b41d4 7d780c9e 1ad11d7e b70391b1 de0b35da 2dc62f83 e7b78d63 06ca0ea0",
+            "7e941b7b e91348f9 fcb170e2 217fecd9 7f9f68ad b16e5d7d 21e569d2 80ed775c",
+            "ebde3f40 93c53881 00000000",
+        ]),
+        mac: "fae8ff0b\n",
+    },
+];
+
+#[test]
+fn eea3() {
+    for x in &EEA3_EXAMPLES {
+        let link = [
+            "--key",
+            x.ck,
+            "--count",
+            x.count,
+            "--bearer",
+            x.bearer,
+            "--direction",
+            x.direction,
+            "--length",
+            x.length,
+        ];
+
+        let args = [&["eea3"], &link[..], &["--output-format", "raw"]].concat();
+        assert_eq!(zuc(&args, x.ibs), x.obs);
+
+        let args = [&["eea3"], &link[..], &["--input-format", "hex"]].concat();
+        let obs = zuc(&args, &hex_line(x.obs));
+        assert_eq!(obs, hex_line(x.ibs));
+    }
+}
+
+#[test]
+fn eia3() {
+    for x in &EIA3_EXAMPLES {
+        let args = [
+            "eia3",
+            "--key",
+            x.ik,
+            "--count",
+            x.count,
+            "--bearer",
+            x.bearer,
+            "--direction",
+            x.direction,
+            "--length",
+            x.length,
+        ];
+        assert_eq!(zuc(&args, x.m), x.mac.as_bytes());
+    }
+}
+
+#[test]
+fn files_and_base64() {
+    let dir = std::env::temp_dir().join(format!("zuc-cli-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("m.b64");
+    let output = dir.join("mac.bin");
+
+    let x = &EIA3_EXAMPLES[2];
+    std::fs::write(&input, "mDtB1H14DJ4a0R1+twORsd4LNdotxi+D57eNYwbKDqB+lBt76RNI+fyxcOIhf+zZ\nf59orbFuXX0h5WnSgO13XOveP0CTxTiBAAAAAA==\n").unwrap();
+    let args = [
+        "eia3",
+        "--key",
+        x.ik,
+        "--count",
+        x.count,
+        "--bearer",
+        x.bearer,
+        "--direction",
+        x.direction,
+        "--length",
+        x.length,
+        "--input",
+        input.to_str().unwrap(),
+        "--input-format",
+        "base64",
+        "--output",
+        output.to_str().unwrap(),
+        "--output-format",
+        "raw",
+    ];
+    assert_eq!(zuc(&args, &[]), []);
+    assert_eq!(std::fs::read(&output).unwrap(), hex!("fae8ff0b"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn keystream() {
+    let out = zuc(
+        &[
+            "zuc128-keystream",
+            "--key",
+            "00000000000000000000000000000000",
+            "--iv",
+            "00000000000000000000000000000000",
+            "--words",
+            "2",
+        ],
+        &[],
+    );
+    assert_eq!(out, b"27bede74018082da\n");
+
+    let key = "00".repeat(32);
+    let iv = "00".repeat(23);
+    let out = zuc(
+        &[
+            "zuc256-keystream",
+            "--key",
+            &key,
+            "--iv",
+            &iv,
+            "--words",
+            "0x2",
+            "--output-format",
+            "base64",
+        ],
+        &[],
+    );
+    assert_eq!(out, b"WNA61i4DLOI=\n");
+}
+
+/// examples from <http://www.is.cas.cn/ztzl2016/zouchongzhi/201801/W020180416526664982687.pdf>
+#[test]
+fn zuc256_mac() {
+    let key = "ff".repeat(32);
+    let iv = "ff".repeat(23);
+    let m = [0x11; 500];
+    for (tag_bits, expected) in [
+        ("32", "5c7c8b88\n"),
+        ("64", "ea1dee544bb6223b\n"),
+        ("128", "3a83b554be408ca5494124ed9d473205\n"),
+    ] {
+        let args = [
+            "zuc256-mac",
+            "--key",
+            &key,
+            "--iv",
+            &iv,
+            "--tag-bits",
+            tag_bits,
+        ];
+        assert_eq!(zuc(&args, &m), expected.as_bytes());
+    }
+}
+
+#[test]
+fn invalid_arguments() {
+    let key = "00".repeat(16);
+    let signed = format!("+f{}", "00".repeat(15));
+    zuc_fails(&[
+        "eea3",
+        "--key",
+        &signed,
+        "--count",
+        "0",
+        "--bearer",
+        "0",
+        "--direction",
+        "0",
+    ]);
+    zuc_fails(&[
+        "eea3",
+        "--key",
+        "00",
+        "--count",
+        "0",
+        "--bearer",
+        "0",
+        "--direction",
+        "0",
+    ]);
+    zuc_fails(&[
+        "eea3",
+        "--key",
+        &key,
+        "--count",
+        "0",
+        "--bearer",
+        "32",
+        "--direction",
+        "0",
+    ]);
+    zuc_fails(&[
+        "eea3",
+        "--key",
+        &key,
+        "--count",
+        "0",
+        "--bearer",
+        "0",
+        "--direction",
+        "2",
+    ]);
+    zuc_fails(&[
+        "eea3",
+        "--key",
+        &key,
+        "--count",
+        "0",
+        "--bearer",
+        "0",
+        "--direction",
+        "0",
+        "--length",
+        "8",
+    ]);
+    zuc_fails(&[
+        "zuc256-mac",
+        "--key",
+        &key,
+        "--iv",
+        &key,
+        "--tag-bits",
+        "48",
+    ]);
+    zuc_fails(&["stats", "--sequences", "0"]);
+    zuc_fails(&["stats", "--alpha", "1.5"]);
+}
+
+#[test]
+fn stats() {
+    let args = [
+        "stats",
+        "--alg",
+        "zuc256",
+        "--sequences",
+        "10",
+        "--bits",
+        "40000",
+        "--seed",
+        "7",
+    ];
+    let report = String::from_utf8(zuc(&args, &[])).unwrap();
+    assert_eq!(report.lines().count(), 10, "{report}");
+    assert!(
+        report.lines().skip(1).all(|l| l.ends_with("PASS")),
+        "{report}"
+    );
+    assert!(report.contains("cumulative-sums"), "{report}");
+    assert!(report.lines().next().unwrap().contains("C10"), "{report}");
+    assert_eq!(zuc(&args, &[]), report.as_bytes());
+}
+
+/// Wraps a UDP payload in IPv4 and UDP headers
+fn ipv4_udp(payload: &[u8]) -> Vec<u8> {
+    let total = u16::try_from(28 + payload.len()).unwrap();
+    let udp_len = total - 20;
+    let mut v = hex!("45 00 00 00 00 00 40 00 40 11 00 00 0a 00 00 01 0a 00 00 02").to_vec();
+    v[2..4].copy_from_slice(&total.to_be_bytes());
+    v.extend_from_slice(&hex!("d4 31 26 94"));
+    v.extend_from_slice(&udp_len.to_be_bytes());
+    v.extend_from_slice(&[0, 0]);
+    v.extend_from_slice(payload);
+    v
+}
+
+#[test]
+fn pcap_decrypt() {
+    use zuc::nas::{NasConfig, NasSecurityContext, NasSystem, SecurityHeaderType};
+    use zuc::pcap::{Capture, Frame, NasFrame, Packet};
+    use zuc::Direction;
+
+    let k_nas_enc = "d4552a8fd6e61cc81a2009141a29c10b";
+    let k_nas_int = "6b8b08ee79e0b5982d6d128ea9f220cb";
+
+    let mut ue = NasSecurityContext::new(NasConfig {
+        system: NasSystem::Eps,
+        direction: Direction::Uplink,
+        bearer: 0,
+        ciphering_key: Some(hex!("d4552a8fd6e61cc81a2009141a29c10b")),
+        integrity_key: hex!("6b8b08ee79e0b5982d6d128ea9f220cb"),
+    });
+    let messages = [
+        &hex!("07 41 71 08 29 80 39 10 00 00 00 00 10")[..],
+        &hex!("07 5e"),
+    ];
+    let mut packets = Vec::new();
+    for (i, message) in messages.iter().enumerate() {
+        let header_type = if i == 0 {
+            SecurityHeaderType::IntegrityProtectedAndCipheredWithNewContext
+        } else {
+            SecurityHeaderType::IntegrityProtectedAndCiphered
+        };
+        let frame = Frame::Nas(NasFrame {
+            ue_id: 1,
+            system: NasSystem::Eps,
+            direction: Direction::Uplink,
+            message: ue.protect(header_type, message).unwrap(),
+        });
+        packets.push(Packet {
+            ts_sec: 0,
+            ts_usec: 0,
+            orig_len: 0,
+            data: ipv4_udp(&frame.encode()),
+        });
+    }
+    // tampered copy of the second message
+    let mut tampered = packets[1].clone();
+    *tampered.data.last_mut().unwrap() ^= 0xff;
+    packets.push(tampered);
+
+    let dir = std::env::temp_dir().join(format!("zuc-cli-pcap-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("in.pcap");
+    let output = dir.join("out.pcap");
+    let capture = Capture {
+        link_type: 101,
+        packets,
+    };
+    std::fs::write(&input, capture.to_pcap()).unwrap();
+
+    let report = zuc(
+        &[
+            "pcap-decrypt",
+            "--input",
+            input.to_str().unwrap(),
+            "--output",
+            output.to_str().unwrap(),
+            "--nas-enc-key",
+            k_nas_enc,
+            "--nas-int-key",
+            k_nas_int,
+        ],
+        &[],
+    );
+    let report = String::from_utf8(report).unwrap();
+    assert!(report.contains("decrypted:          2"), "{report}");
+    assert!(report.contains("integrity failures: 1"), "{report}");
+    assert!(report.contains("packet 3: UE 1 Uplink: NAS: integrity check failed"));
+
+    let decrypted = Capture::parse(&std::fs::read(&output).unwrap()).unwrap();
+    for (packet, message) in decrypted.packets.iter().zip(messages) {
+        match Frame::decode(&packet.data[28..]) {
+            Some(Frame::Nas(f)) => assert_eq!(f.message, message),
+            other => panic!("unexpected frame {other:?}"),
+        }
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}